@@ -1,7 +1,7 @@
-fn expected_build_id() -> Option<Vec<u8>> {
+fn expected_build_id() -> Option<buildid::BuildId> {
     let expected_build_id = std::env::var("BUILD_ID_TEST_EXPECTED");
     match expected_build_id {
-        Ok(v) => Some(hex::decode(v).unwrap()),
+        Ok(v) => Some(v.parse().unwrap()),
         Err(std::env::VarError::NotPresent) => None,
         Err(e) => panic!("{}", e),
     }
@@ -13,7 +13,7 @@ fn has_build_id() {
     let id = buildid::build_id().unwrap();
     assert!(!id.is_empty());
 
-    println!("{}", hex::encode(id));
+    println!("{}", buildid::BuildId::current().unwrap());
     if let Some(expected_id) = expected_build_id() {
         assert_eq!(expected_id, id);
     }
@@ -0,0 +1,191 @@
+//! Command line access to build-ids in files
+//!
+//! ```text
+//! buildid show FILE...
+//! buildid set FILE (HEX | --recompute STYLE) [-o OUTPUT]
+//! buildid add FILE [--style STYLE] [-o OUTPUT]
+//! buildid modules (CORE | --pid PID)
+//! ```
+//!
+//! `set` overwrites the build-id in place (or writes the result to `OUTPUT`), with either the
+//! given value or a hash of the file's content computed like `STYLE` (for example, `ld-sha1` or
+//! `lld-fast`). The new build-id must be the same length as the existing one.
+//!
+//! `add` adds a build-id note to a file linked without one, computing the build-id like `STYLE`
+//! (`ld-sha1` by default).
+//!
+//! `modules` lists the files mapped into the process that produced the core dump `CORE` (or the
+//! running process `PID`), as `START+SIZE BUILDID PATH`, with `-` for a missing build-id. For core
+//! dumps, the address of the build-id follows it as `BUILDID@ADDR`.
+use buildid::core_file::CoreFile;
+use buildid::recompute::{self, Style};
+use buildid::{elf_file, inject, BuildId, Kind};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: buildid show FILE...
+       buildid set FILE (HEX | --recompute STYLE) [-o OUTPUT]
+       buildid add FILE [--style STYLE] [-o OUTPUT]
+       buildid modules (CORE | --pid PID)";
+
+/// Parse a style given as its name in kebab-case (for example, `lld-fast`)
+fn parse_style(s: &str) -> Result<Style, String> {
+    let want = s.replace('-', "");
+    Style::ALL
+        .into_iter()
+        .find(|style| format!("{:?}", style).eq_ignore_ascii_case(&want))
+        .ok_or_else(|| format!("unknown style {:?}", s))
+}
+
+enum NewId {
+    Value(BuildId),
+    Recompute(Style),
+}
+
+fn set(args: &[String]) -> Result<(), String> {
+    let mut file = None;
+    let mut new = None;
+    let mut output = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name| args.next().ok_or_else(|| format!("{} needs a value", name));
+        match arg.as_str() {
+            "--recompute" => new = Some(NewId::Recompute(parse_style(value("--recompute")?)?)),
+            "-o" => output = Some(PathBuf::from(value("-o")?)),
+            _ if file.is_none() => file = Some(PathBuf::from(arg)),
+            _ if new.is_none() => {
+                let id = arg
+                    .parse()
+                    .map_err(|e| format!("invalid build-id {:?}: {}", arg, e))?;
+                new = Some(NewId::Value(id));
+            }
+            _ => return Err(format!("unexpected argument {:?}", arg)),
+        }
+    }
+    let (file, new) = file.zip(new).ok_or_else(|| USAGE.to_owned())?;
+
+    let mut data = std::fs::read(&file).map_err(|e| format!("{}: {}", file.display(), e))?;
+    let id = match new {
+        NewId::Value(id) => elf_file::set_build_id(&mut data, id.as_bytes()).map(|()| id),
+        NewId::Recompute(style) => recompute::update(&mut data, style),
+    }
+    .map_err(|e| format!("{}: {}", file.display(), e))?;
+
+    let output = output.unwrap_or(file);
+    std::fs::write(&output, data).map_err(|e| format!("{}: {}", output.display(), e))?;
+    println!("{}", id);
+    Ok(())
+}
+
+fn add(args: &[String]) -> Result<(), String> {
+    let mut file = None;
+    let mut style = Style::LdSha1;
+    let mut output = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name| args.next().ok_or_else(|| format!("{} needs a value", name));
+        match arg.as_str() {
+            "--style" => style = parse_style(value("--style")?)?,
+            "-o" => output = Some(PathBuf::from(value("-o")?)),
+            _ if file.is_none() => file = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument {:?}", arg)),
+        }
+    }
+    let file = file.ok_or_else(|| USAGE.to_owned())?;
+
+    let data = std::fs::read(&file).map_err(|e| format!("{}: {}", file.display(), e))?;
+    let data =
+        inject::add_build_id(&data, style).map_err(|e| format!("{}: {}", file.display(), e))?;
+    let id = elf_file::build_id(&data).map_err(|e| format!("{}: {}", file.display(), e))?;
+    let id = BuildId::new(Kind::Gnu, id).ok_or("build-id is too long")?;
+
+    let output = output.unwrap_or(file);
+    std::fs::write(&output, data).map_err(|e| format!("{}: {}", output.display(), e))?;
+    println!("{}", id);
+    Ok(())
+}
+
+/// Print a line of the module table
+fn print_module(range: std::ops::Range<u64>, id: Option<String>, path: &str) {
+    let id = id.as_deref().unwrap_or("-");
+    println!(
+        "{:#x}+{:#x} {} {}",
+        range.start,
+        range.end - range.start,
+        id,
+        path
+    );
+}
+
+fn core_modules(file: &str) -> Result<(), String> {
+    let data = std::fs::read(file).map_err(|e| format!("{}: {}", file, e))?;
+    let core = CoreFile::parse(&data).map_err(|e| format!("{}: {}", file, e))?;
+    for module in core.modules().map_err(|e| format!("{}: {}", file, e))? {
+        let module = module.map_err(|e| format!("{}: {}", file, e))?;
+        let id = module.build_id().and_then(|id| BuildId::new(Kind::Gnu, id));
+        let id = id.map(|id| format!("{}@{:#x}", id, module.build_id_addr().unwrap_or(0)));
+        print_module(module.range(), id, &String::from_utf8_lossy(module.path()));
+    }
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn process_modules(pid: &str) -> Result<(), String> {
+    let pid = pid
+        .parse()
+        .map_err(|e| format!("invalid pid {:?}: {}", pid, e))?;
+    let modules = buildid::process::modules(pid).map_err(|e| format!("{}: {}", pid, e))?;
+    for module in modules {
+        let id = module.build_id().map(|id| id.to_string());
+        let mut path = module.path().display().to_string();
+        if module.deleted() {
+            path += " (deleted)";
+        }
+        print_module(module.range(), id, &path);
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn process_modules(_pid: &str) -> Result<(), String> {
+    Err("--pid is only supported on Linux".to_owned())
+}
+
+fn modules(args: &[String]) -> Result<(), String> {
+    match args {
+        [flag, pid] if flag == "--pid" => process_modules(pid),
+        [file] => core_modules(file),
+        _ => Err(USAGE.to_owned()),
+    }
+}
+
+fn show(args: &[String]) -> Result<(), String> {
+    if args.is_empty() {
+        return Err(USAGE.to_owned());
+    }
+    for arg in args {
+        match buildid::from_path(arg) {
+            Ok(id) => println!("{} {}", id, arg),
+            Err(e) => return Err(format!("{}: {}", arg, e)),
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("show") => show(&args[1..]),
+        Some("set") => set(&args[1..]),
+        Some("add") => add(&args[1..]),
+        Some("modules") => modules(&args[1..]),
+        _ => Err(USAGE.to_owned()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
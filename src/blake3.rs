@@ -0,0 +1,274 @@
+//! BLAKE3 (hash mode only), as used by lld's `--build-id=sha1` and `--build-id=md5`
+
+const IV: [u32; 8] = [
+    0x6a09_e667,
+    0xbb67_ae85,
+    0x3c6e_f372,
+    0xa54f_f53a,
+    0x510e_527f,
+    0x9b05_688c,
+    0x1f83_d9ab,
+    0x5be0_cd19,
+];
+
+const MSG_PERMUTATION: [usize; 16] = [2, 6, 3, 10, 7, 0, 4, 13, 1, 11, 12, 5, 9, 14, 15, 8];
+
+const BLOCK_LEN: usize = 64;
+const CHUNK_LEN: usize = 1024;
+
+const CHUNK_START: u32 = 1 << 0;
+const CHUNK_END: u32 = 1 << 1;
+const PARENT: u32 = 1 << 2;
+const ROOT: u32 = 1 << 3;
+
+fn g(s: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize, mx: u32, my: u32) {
+    s[a] = s[a].wrapping_add(s[b]).wrapping_add(mx);
+    s[d] = (s[d] ^ s[a]).rotate_right(16);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_right(12);
+    s[a] = s[a].wrapping_add(s[b]).wrapping_add(my);
+    s[d] = (s[d] ^ s[a]).rotate_right(8);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_right(7);
+}
+
+fn compress(
+    cv: &[u32; 8],
+    block: &[u8; BLOCK_LEN],
+    counter: u64,
+    len: usize,
+    flags: u32,
+) -> [u32; 16] {
+    let mut m = [0u32; 16];
+    for (m, b) in m.iter_mut().zip(block.chunks_exact(4)) {
+        *m = u32::from_le_bytes(b.try_into().unwrap());
+    }
+    let mut s = [
+        cv[0],
+        cv[1],
+        cv[2],
+        cv[3],
+        cv[4],
+        cv[5],
+        cv[6],
+        cv[7],
+        IV[0],
+        IV[1],
+        IV[2],
+        IV[3],
+        counter as u32,
+        (counter >> 32) as u32,
+        len as u32,
+        flags,
+    ];
+    for round in 0..7 {
+        g(&mut s, 0, 4, 8, 12, m[0], m[1]);
+        g(&mut s, 1, 5, 9, 13, m[2], m[3]);
+        g(&mut s, 2, 6, 10, 14, m[4], m[5]);
+        g(&mut s, 3, 7, 11, 15, m[6], m[7]);
+        g(&mut s, 0, 5, 10, 15, m[8], m[9]);
+        g(&mut s, 1, 6, 11, 12, m[10], m[11]);
+        g(&mut s, 2, 7, 8, 13, m[12], m[13]);
+        g(&mut s, 3, 4, 9, 14, m[14], m[15]);
+        if round < 6 {
+            m = MSG_PERMUTATION.map(|i| m[i]);
+        }
+    }
+    for i in 0..8 {
+        s[i] ^= s[i + 8];
+        s[i + 8] ^= cv[i];
+    }
+    s
+}
+
+fn first_8(s: [u32; 16]) -> [u32; 8] {
+    s[..8].try_into().unwrap()
+}
+
+/// The input to the compression of a node, which becomes either its chaining value or the root
+struct Output {
+    cv: [u32; 8],
+    block: [u8; BLOCK_LEN],
+    counter: u64,
+    len: usize,
+    flags: u32,
+}
+
+impl Output {
+    fn chaining_value(&self) -> [u32; 8] {
+        first_8(compress(
+            &self.cv,
+            &self.block,
+            self.counter,
+            self.len,
+            self.flags,
+        ))
+    }
+
+    fn parent(left: [u32; 8], right: [u32; 8]) -> Self {
+        let mut block = [0u8; BLOCK_LEN];
+        for (b, w) in block.chunks_exact_mut(4).zip(left.iter().chain(&right)) {
+            b.copy_from_slice(&w.to_le_bytes());
+        }
+        Output {
+            cv: IV,
+            block,
+            counter: 0,
+            len: BLOCK_LEN,
+            flags: PARENT,
+        }
+    }
+}
+
+pub(crate) struct Blake3 {
+    cv: [u32; 8],
+    chunk: u64,
+    block: [u8; BLOCK_LEN],
+    block_len: usize,
+    blocks: usize,
+    // chaining values of completed subtrees, one per set bit of the chunk count
+    stack: [[u32; 8]; 54],
+    stack_len: usize,
+}
+
+impl Blake3 {
+    pub(crate) const LEN: usize = 32;
+
+    pub(crate) fn new() -> Self {
+        Blake3 {
+            cv: IV,
+            chunk: 0,
+            block: [0; BLOCK_LEN],
+            block_len: 0,
+            blocks: 0,
+            stack: [[0; 8]; 54],
+            stack_len: 0,
+        }
+    }
+
+    fn start_flag(&self) -> u32 {
+        if self.blocks == 0 {
+            CHUNK_START
+        } else {
+            0
+        }
+    }
+
+    fn chunk_output(&self) -> Output {
+        Output {
+            cv: self.cv,
+            block: self.block,
+            counter: self.chunk,
+            len: self.block_len,
+            flags: self.start_flag() | CHUNK_END,
+        }
+    }
+
+    fn finish_chunk(&mut self) {
+        let mut cv = self.chunk_output().chaining_value();
+        self.chunk += 1;
+        let mut total = self.chunk;
+        while total & 1 == 0 {
+            self.stack_len -= 1;
+            cv = Output::parent(self.stack[self.stack_len], cv).chaining_value();
+            total >>= 1;
+        }
+        self.stack[self.stack_len] = cv;
+        self.stack_len += 1;
+
+        self.cv = IV;
+        self.block = [0; BLOCK_LEN];
+        self.block_len = 0;
+        self.blocks = 0;
+    }
+
+    pub(crate) fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            // a full block is only compressed once more input arrives, as the last block of the
+            // last chunk is compressed differently
+            if self.block_len == BLOCK_LEN {
+                if self.blocks * BLOCK_LEN + self.block_len == CHUNK_LEN {
+                    self.finish_chunk();
+                } else {
+                    let flags = self.start_flag();
+                    self.cv = first_8(compress(
+                        &self.cv,
+                        &self.block,
+                        self.chunk,
+                        BLOCK_LEN,
+                        flags,
+                    ));
+                    self.blocks += 1;
+                    self.block = [0; BLOCK_LEN];
+                    self.block_len = 0;
+                }
+            }
+            let n = (BLOCK_LEN - self.block_len).min(data.len());
+            self.block[self.block_len..self.block_len + n].copy_from_slice(&data[..n]);
+            self.block_len += n;
+            data = &data[n..];
+        }
+    }
+
+    pub(crate) fn finish(self) -> [u8; Self::LEN] {
+        let mut output = self.chunk_output();
+        for cv in self.stack[..self.stack_len].iter().rev() {
+            output = Output::parent(*cv, output.chaining_value());
+        }
+        let s = compress(
+            &output.cv,
+            &output.block,
+            0,
+            output.len,
+            output.flags | ROOT,
+        );
+        let mut out = [0u8; Self::LEN];
+        for (o, w) in out.chunks_exact_mut(4).zip(s) {
+            o.copy_from_slice(&w.to_le_bytes());
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Blake3;
+
+    #[test]
+    fn vectors() {
+        // from the BLAKE3 test vectors, where the input is the bytes 0, 1, ..., 250, 0, 1, ...
+        let input: alloc::vec::Vec<u8> = (0..=250u8).cycle().take(8193).collect();
+        for (len, expected) in [
+            (
+                0,
+                "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262",
+            ),
+            (
+                1,
+                "2d3adedff11b61f14c886e35afa036736dcd87a74d27b5c1510225d0f592e213",
+            ),
+            (
+                1024,
+                "42214739f095a406f3fc83deb889744ac00df831c10daa55189b5d121c855af7",
+            ),
+            (
+                1025,
+                "d00278ae47eb27b34faecf67b4fe263f82d5412916c1ffd97c8cb7fb814b8444",
+            ),
+            (
+                8193,
+                "bab6c09cb8ce8cf459261398d2e7aef35700bf488116ceb94a36d0f5f1b7bc3b",
+            ),
+        ] {
+            let mut h = Blake3::new();
+            // split the input, to cover buffering
+            let (a, b) = input[..len].split_at(len / 3);
+            h.update(a);
+            h.update(b);
+            assert_eq!(
+                crate::BuildId::new(crate::Kind::Gnu, &h.finish()).unwrap(),
+                expected.parse::<crate::BuildId>().unwrap()
+            );
+        }
+    }
+}
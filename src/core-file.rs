@@ -0,0 +1,305 @@
+//! Read the build-ids of the files mapped into a process from its ELF core dump
+//!
+//! Linux records each file mapped into the process in the `NT_FILE` note of the core dump. With
+//! the default `coredump_filter`, it also dumps the first page of each mapped ELF file, which holds
+//! its program headers and (usually) its notes. [`CoreFile::modules()`] combines the two into a
+//! module table, like the one `eu-unstrip -n --core` prints.
+//!
+//! ```no_run
+//! # fn example(data: &[u8]) -> Result<(), buildid::Error> {
+//! let core = buildid::core_file::CoreFile::parse(data)?;
+//! for module in core.modules()? {
+//!     let module = module?;
+//!     println!("{:x?} {:x?} {:?}", module.range(), module.build_id(), module.path());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The vDSO is not a file, so it does not appear in the table.
+use crate::elf_file::{loaded_build_id, ElfFile, FileHeader, Reader, PT_LOAD};
+use crate::Error;
+use core::ops::Range;
+
+/// Note type of the list of mapped files (when the note name is `"CORE\0"`)
+pub const NT_FILE: u32 = 0x4649_4c45;
+
+const ET_CORE: u16 = 4;
+
+/// An ELF core dump held in memory
+#[derive(Debug, Clone, Copy)]
+pub struct CoreFile<'a> {
+    elf: ElfFile<'a>,
+}
+
+impl<'a> CoreFile<'a> {
+    /// Parse the ELF header of `data`, which holds an entire core dump
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        let elf = ElfFile::parse(data)?;
+        if elf.header().e_type != ET_CORE {
+            return Err(Error::Malformed("not a core dump"));
+        }
+        Ok(CoreFile { elf })
+    }
+
+    /// The core dump, as an ELF file
+    pub fn elf(&self) -> &ElfFile<'a> {
+        &self.elf
+    }
+
+    /// The memory of the process at `addr`, up to the end of the segment containing it
+    ///
+    /// Returns `None` if the core dump does not include the memory at `addr`.
+    pub fn memory(&self, addr: u64) -> Result<Option<&'a [u8]>, Error> {
+        for phdr in self.elf.program_headers()? {
+            let phdr = phdr?;
+            let off = addr.wrapping_sub(phdr.p_vaddr);
+            if phdr.p_type == PT_LOAD && addr >= phdr.p_vaddr && off < phdr.p_filesz {
+                let data = self.elf.segment_data(&phdr)?;
+                return Ok(Some(&data[off as usize..]));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The address in the process of `bytes`, which are part of the core dump
+    fn address_of(&self, bytes: &[u8]) -> Result<Option<u64>, Error> {
+        let off = (bytes.as_ptr() as usize - self.elf.data().as_ptr() as usize) as u64;
+        for phdr in self.elf.program_headers()? {
+            let phdr = phdr?;
+            if phdr.p_type == PT_LOAD && phdr.file_range()?.contains(&off) {
+                let addr = phdr
+                    .p_vaddr
+                    .checked_add(off - phdr.p_offset)
+                    .ok_or(Error::Malformed("PT_LOAD segment wraps around"))?;
+                return Ok(Some(addr));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The bytes of the process's memory in `range`, if the core dump holds all of them
+    fn read(&self, range: Range<u64>) -> Result<&'a [u8], Error> {
+        let data = self.memory(range.start)?.ok_or(Error::NotCaptured)?;
+        let len = usize::try_from(range.end - range.start).map_err(|_| Error::NotCaptured)?;
+        data.get(..len).ok_or(Error::NotCaptured)
+    }
+
+    /// Iterate over the files mapped into the process, as listed by the `NT_FILE` note
+    pub fn mapped_files(&self) -> Result<MappedFiles<'a>, Error> {
+        let mut err = None;
+        for ni in self.elf.note_segments()? {
+            let notes = match ni {
+                Ok(v) => v,
+                Err(e) => {
+                    err.get_or_insert(e);
+                    continue;
+                }
+            };
+            for note in notes {
+                match note {
+                    Ok(n) if n.name() == b"CORE\0" && n.type_() == NT_FILE => {
+                        return MappedFiles::parse(n.desc(), self.elf.header().reader());
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        err.get_or_insert(e.into());
+                    }
+                }
+            }
+        }
+        Err(err.unwrap_or(Error::NotFound))
+    }
+
+    /// Iterate over the ELF files mapped into the process, along with their build-ids
+    ///
+    /// Consecutive mappings of the same file are combined into one [`Module`]. Mapped files that
+    /// are not ELF files are skipped, when the core dump shows that.
+    pub fn modules(&self) -> Result<Modules<'a>, Error> {
+        Ok(Modules {
+            core: *self,
+            files: self.mapped_files()?,
+        })
+    }
+
+    /// Find the build-id of the ELF file whose headers are mapped at `addr`, and its address
+    fn build_id_at(&self, addr: u64) -> Result<(u64, &'a [u8]), Error> {
+        // a file that isn't an ELF file is recognized even if less than a file header was dumped
+        let image = self.memory(addr)?.ok_or(Error::NotCaptured)?;
+        if FileHeader::parse(image) == Err(Error::UnrecognizedFormat) {
+            return Err(Error::UnrecognizedFormat);
+        }
+        let (notes, id) = loaded_build_id(addr, |range| self.read(range))?;
+        let id = &notes[id];
+        let id_addr = self.address_of(id)?.ok_or(Error::Malformed(
+            "build-id is outside of the process's memory",
+        ))?;
+        Ok((id_addr, id))
+    }
+}
+
+/// A file mapped into the process, from the `NT_FILE` note
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MappedFile<'a> {
+    /// Address range of the mapping
+    pub range: Range<u64>,
+    /// Offset in the file of the start of the mapping
+    pub offset: u64,
+    /// Path of the file, as the kernel reported it
+    pub path: &'a [u8],
+}
+
+/// Iterate over the files mapped into a process, from the `NT_FILE` note
+///
+/// Created by [`CoreFile::mapped_files()`].
+#[derive(Debug, Clone)]
+pub struct MappedFiles<'a> {
+    reader: Reader,
+    page_size: u64,
+    /// (start, end, page offset) of each remaining mapping
+    entries: &'a [u8],
+    /// nul terminated path of each remaining mapping
+    paths: &'a [u8],
+}
+
+impl<'a> MappedFiles<'a> {
+    /// Parse the descriptor of an `NT_FILE` note
+    fn parse(desc: &'a [u8], reader: Reader) -> Result<Self, Error> {
+        let word = reader.class.word_size();
+        if desc.len() < word * 2 {
+            return Err(Error::Truncated);
+        }
+        let mut d = desc;
+        let count = reader.word(&mut d);
+        let page_size = reader.word(&mut d);
+        let entries_len = usize::try_from(count)
+            .ok()
+            .and_then(|c| c.checked_mul(word * 3))
+            .filter(|&l| l <= d.len())
+            .ok_or(Error::Truncated)?;
+        let (entries, paths) = d.split_at(entries_len);
+        Ok(MappedFiles {
+            reader,
+            page_size,
+            entries,
+            paths,
+        })
+    }
+}
+
+impl<'a> Iterator for MappedFiles<'a> {
+    type Item = Result<MappedFile<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        let start = self.reader.word(&mut self.entries);
+        let end = self.reader.word(&mut self.entries);
+        let page_offset = self.reader.word(&mut self.entries);
+
+        let Some(len) = self.paths.iter().position(|&c| c == 0) else {
+            // don't keep returning the same error
+            self.entries = &[];
+            return Some(Err(Error::Malformed("NT_FILE path is not nul terminated")));
+        };
+        let path = &self.paths[..len];
+        self.paths = &self.paths[len + 1..];
+
+        Some(Ok(MappedFile {
+            range: start..end,
+            offset: page_offset.wrapping_mul(self.page_size),
+            path,
+        }))
+    }
+}
+
+/// An ELF file mapped into the process, along with its build-id
+///
+/// Created by [`CoreFile::modules()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Module<'a> {
+    range: Range<u64>,
+    path: &'a [u8],
+    build_id: Result<(u64, &'a [u8]), Error>,
+}
+
+impl<'a> Module<'a> {
+    /// Address range from the start of the first mapping of the file to the end of the last
+    pub fn range(&self) -> Range<u64> {
+        self.range.clone()
+    }
+
+    /// Path of the file, as the kernel reported it
+    pub fn path(&self) -> &'a [u8] {
+        self.path
+    }
+
+    /// The GNU build-id of the file, if the core dump includes it
+    pub fn build_id(&self) -> Option<&'a [u8]> {
+        self.try_build_id().ok()
+    }
+
+    /// The GNU build-id of the file, or the reason it could not be found
+    ///
+    /// [`Error::NotCaptured`] means the core dump does not include the memory holding the file's
+    /// headers or notes.
+    pub fn try_build_id(&self) -> Result<&'a [u8], Error> {
+        self.build_id.map(|(_, id)| id)
+    }
+
+    /// Address of the build-id in the process's memory
+    pub fn build_id_addr(&self) -> Option<u64> {
+        self.build_id.ok().map(|(addr, _)| addr)
+    }
+}
+
+/// Iterate over the ELF files mapped into a process
+///
+/// Created by [`CoreFile::modules()`].
+#[derive(Debug, Clone)]
+pub struct Modules<'a> {
+    core: CoreFile<'a>,
+    files: MappedFiles<'a>,
+}
+
+impl<'a> Iterator for Modules<'a> {
+    type Item = Result<Module<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let first = match self.files.next()? {
+                Ok(v) => v,
+                Err(e) => return Some(Err(e)),
+            };
+            let mut end = first.range.end;
+            let mut header = (first.offset == 0).then_some(first.range.start);
+            loop {
+                let mut rest = self.files.clone();
+                match rest.next() {
+                    Some(Ok(f)) if f.path == first.path => {
+                        end = f.range.end;
+                        if f.offset == 0 {
+                            header.get_or_insert(f.range.start);
+                        }
+                        self.files = rest;
+                    }
+                    _ => break,
+                }
+            }
+
+            let build_id = match header {
+                Some(addr) => self.core.build_id_at(addr),
+                None => Err(Error::NotCaptured),
+            };
+            if build_id == Err(Error::UnrecognizedFormat) {
+                continue;
+            }
+            return Some(Ok(Module {
+                range: first.range.start..end,
+                path: first.path,
+                build_id,
+            }));
+        }
+    }
+}
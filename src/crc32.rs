@@ -0,0 +1,43 @@
+//! CRC-32 (the IEEE/zlib polynomial), as used by `.gnu_debuglink`
+
+const TABLE: [u32; 256] = {
+    let mut t = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        t[i] = c;
+        i += 1;
+    }
+    t
+};
+
+/// Continue a CRC over `data`. Start with a `crc` of 0.
+pub(crate) fn update(crc: u32, data: &[u8]) -> u32 {
+    let mut c = !crc;
+    for &b in data {
+        c = TABLE[((c ^ b as u32) & 0xff) as usize] ^ (c >> 8);
+    }
+    !c
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn check() {
+        assert_eq!(super::update(0, b""), 0);
+        assert_eq!(super::update(0, b"123456789"), 0xcbf4_3926);
+        assert_eq!(
+            super::update(super::update(0, b"1234"), b"56789"),
+            0xcbf4_3926
+        );
+    }
+}
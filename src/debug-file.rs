@@ -0,0 +1,348 @@
+//! Locate separate debug files (and executables) by build-id
+//!
+//! Distributions and debuggers store files under a `.build-id` directory within each debug
+//! directory, named by their build-id: the first byte (as hex) is a subdirectory and the
+//! remaining bytes name the file. Debug files use a `.debug` suffix, and the executable (usually
+//! a symlink) uses no suffix:
+//!
+//! ```text
+//! /usr/lib/debug/.build-id/ab/cdef0123456789.debug
+//! /usr/lib/debug/.build-id/ab/cdef0123456789
+//! ```
+//!
+//! [`build_id_path()`] formats the path relative to a debug directory. With the `std` feature,
+//! `DebugDirs` probes a list of debug directories (like gdb's `debug-file-directory`) for a
+//! file whose own build-id matches.
+//!
+//! [`verify()`] checks that a stripped object and a separate debug file belong together, and
+//! reports why they don't.
+//!
+//! ```no_run
+//! # #[cfg(feature = "std")]
+//! # fn example() {
+//! use buildid::debug_file::{DebugDirs, FileType};
+//!
+//! let id = buildid::BuildId::current().unwrap();
+//! if let Some(path) = DebugDirs::default().find(&id, FileType::Debug) {
+//!     println!("debug info is in {}", path.display());
+//! }
+//! # }
+//! ```
+#[cfg(feature = "std")]
+use crate::debug_link::{DebugAltLink, DebugLink};
+use crate::{BuildId, Error};
+use core::fmt;
+
+/// Directory searched for debug files when no other is configured
+pub const DEFAULT_DEBUG_DIR: &str = "/usr/lib/debug";
+
+/// Which file stored under a `.build-id` directory to refer to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileType {
+    /// The separate debug file (`.debug` suffix)
+    Debug,
+    /// The executable or shared library (no suffix)
+    Executable,
+}
+
+impl FileType {
+    /// The suffix appended to the file name
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Self::Debug => ".debug",
+            Self::Executable => "",
+        }
+    }
+}
+
+/// Path of a file under a debug directory, named by its build-id. See [`build_id_path()`].
+///
+/// `Display` emits the path relative to the debug directory (for example,
+/// `.build-id/ab/cdef0123.debug`).
+#[derive(Debug, Clone, Copy)]
+pub struct BuildIdPath<'a> {
+    id: &'a BuildId,
+    file_type: FileType,
+}
+
+impl fmt::Display for BuildIdPath<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (first, rest) = self.id.as_bytes().split_at(1);
+        write!(f, ".build-id/{:02x}/", first[0])?;
+        for b in rest {
+            write!(f, "{:02x}", b)?;
+        }
+        f.write_str(self.file_type.suffix())
+    }
+}
+
+/// Return the path of the file with build-id `id`, relative to a debug directory
+///
+/// Returns `None` if `id` is shorter than 2 bytes, as there would be no file name.
+pub fn build_id_path(id: &BuildId, file_type: FileType) -> Option<BuildIdPath<'_>> {
+    if id.len() < 2 {
+        return None;
+    }
+    Some(BuildIdPath { id, file_type })
+}
+
+/// Why an object and a candidate debug file don't belong together. See [`verify()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Mismatch {
+    /// Neither file has a build-id, so they can't be matched. The object was probably linked
+    /// without `--build-id`.
+    NoBuildIds,
+    /// Only the debug file has a build-id
+    MissingInObject,
+    /// Only the object has a build-id. The debug file may have been produced by a tool that
+    /// doesn't copy notes.
+    MissingInDebug,
+    /// The build-ids have different lengths, so the files were likely linked by different
+    /// linkers (GNU ld uses a 20 byte sha1, lld an 8 byte hash by default or 16 byte md5/uuid)
+    DifferentLengths { object: usize, debug: usize },
+    /// The build-ids have the same length but differ: the debug file is from a different build
+    Different,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoBuildIds => write!(f, "neither file has a build-id"),
+            Self::MissingInObject => write!(f, "only the debug file has a build-id"),
+            Self::MissingInDebug => write!(f, "the debug file has no build-id"),
+            Self::DifferentLengths { object, debug } => write!(
+                f,
+                "build-id lengths differ ({} and {} bytes), likely from different linkers",
+                object, debug
+            ),
+            Self::Different => write!(f, "build-ids differ"),
+        }
+    }
+}
+
+/// Compare the results of looking up the build-ids of an object and a debug file
+fn compare(object: Result<&[u8], Error>, debug: Result<&[u8], Error>) -> Result<(), Error> {
+    fn found(r: Result<&[u8], Error>) -> Result<Option<&[u8]>, Error> {
+        match r {
+            Ok(id) => Ok(Some(id)),
+            Err(Error::NotFound) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    let mismatch = match (found(object)?, found(debug)?) {
+        (Some(o), Some(d)) if o == d => return Ok(()),
+        (Some(o), Some(d)) if o.len() != d.len() => Mismatch::DifferentLengths {
+            object: o.len(),
+            debug: d.len(),
+        },
+        (Some(_), Some(_)) => Mismatch::Different,
+        (None, None) => Mismatch::NoBuildIds,
+        (None, Some(_)) => Mismatch::MissingInObject,
+        (Some(_), None) => Mismatch::MissingInDebug,
+    };
+    Err(Error::Mismatch(mismatch))
+}
+
+/// Check that `debug` (the content of a separate debug file) belongs to `object` (the content of
+/// a stripped ELF executable or library) by comparing their GNU build-ids
+///
+/// Returns the shared build-id, or [`Error::Mismatch`] describing why they don't match. Other
+/// errors mean one of the files could not be parsed.
+pub fn verify(object: &[u8], debug: &[u8]) -> Result<BuildId, Error> {
+    let object = crate::elf_file::build_id(object);
+    compare(object, crate::elf_file::build_id(debug))?;
+    let id = object?;
+    BuildId::new(crate::Kind::Gnu, id).ok_or(Error::TooLong(id.len()))
+}
+
+/// Like [`verify()`], but reads only the parts of the files needed to find their build-ids
+#[cfg(feature = "std")]
+pub fn verify_paths<P: AsRef<std::path::Path>, Q: AsRef<std::path::Path>>(
+    object: P,
+    debug: Q,
+) -> Result<BuildId, Error> {
+    let read = |p: &std::path::Path| -> Result<BuildId, Error> {
+        let mut f = std::io::BufReader::new(std::fs::File::open(p)?);
+        crate::elf_file::read_build_id(&mut f)
+    };
+    let object = read(object.as_ref());
+    let debug = read(debug.as_ref());
+    compare(
+        object.as_ref().map(BuildId::as_bytes).map_err(|e| *e),
+        debug.as_ref().map(BuildId::as_bytes).map_err(|e| *e),
+    )?;
+    object
+}
+
+/// A list of debug directories to search, in order
+///
+/// The default contains only [`DEFAULT_DEBUG_DIR`].
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugDirs {
+    dirs: std::vec::Vec<std::path::PathBuf>,
+}
+
+#[cfg(feature = "std")]
+impl Default for DebugDirs {
+    fn default() -> Self {
+        DebugDirs {
+            dirs: std::vec![DEFAULT_DEBUG_DIR.into()],
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl DebugDirs {
+    /// An empty list of debug directories
+    pub fn new() -> Self {
+        DebugDirs {
+            dirs: std::vec::Vec::new(),
+        }
+    }
+
+    /// Parse a list of directories separated by `:`, in the format of gdb's
+    /// `debug-file-directory` setting. Empty entries are ignored.
+    pub fn from_search_path(path: &str) -> Self {
+        DebugDirs {
+            dirs: path
+                .split(':')
+                .filter(|d| !d.is_empty())
+                .map(Into::into)
+                .collect(),
+        }
+    }
+
+    /// Add a directory to search after the existing ones
+    pub fn push<P: Into<std::path::PathBuf>>(&mut self, dir: P) {
+        self.dirs.push(dir.into());
+    }
+
+    /// The directories that are searched, in order
+    pub fn dirs(&self) -> &[std::path::PathBuf] {
+        &self.dirs
+    }
+
+    /// Paths where the file with build-id `id` may be stored, in the order they are searched
+    pub fn candidates<'a>(
+        &'a self,
+        id: &BuildId,
+        file_type: FileType,
+    ) -> impl Iterator<Item = std::path::PathBuf> + 'a {
+        use std::string::ToString;
+
+        let rel = build_id_path(id, file_type).map(|p| p.to_string());
+        self.dirs
+            .iter()
+            .filter_map(move |d| rel.as_ref().map(|r| d.join(r)))
+    }
+
+    /// Return the first candidate path that exists and contains build-id `id`
+    ///
+    /// Files with a different (or no) build-id are skipped, so a stale file left behind by an
+    /// earlier build isn't returned.
+    pub fn find(&self, id: &BuildId, file_type: FileType) -> Option<std::path::PathBuf> {
+        self.candidates(id, file_type)
+            .find(|p| crate::from_path(p).is_ok_and(|found| found.as_bytes() == id.as_bytes()))
+    }
+
+    /// Paths where the debug file named by a `.gnu_debuglink` section may be stored, in the
+    /// order they are searched
+    ///
+    /// `object` is the path of the file containing the section (ideally with symlinks
+    /// resolved). As gdb does, the directory containing `object`, its `.debug` subdirectory, and
+    /// the same directory under each debug directory are searched.
+    pub fn debuglink_candidates<'a>(
+        &'a self,
+        object: &std::path::Path,
+        link: &DebugLink<'_>,
+    ) -> impl Iterator<Item = std::path::PathBuf> + 'a {
+        use std::path::{Component, PathBuf};
+
+        let name = bytes_to_path(link.file_name).map(std::path::Path::to_path_buf);
+        let dir = object.parent().unwrap_or(std::path::Path::new(""));
+        let relative_dir: PathBuf = dir
+            .components()
+            .filter(|c| matches!(c, Component::Normal(_)))
+            .collect();
+        let local = name
+            .clone()
+            .map(|n| [dir.join(&n), dir.join(".debug").join(&n)]);
+
+        local.into_iter().flatten().chain(
+            self.dirs
+                .iter()
+                .filter_map(move |d| name.as_ref().map(|n| d.join(&relative_dir).join(n))),
+        )
+    }
+
+    /// Return the first candidate path for the debug file named by a `.gnu_debuglink` section
+    /// that has the expected CRC
+    ///
+    /// See [`DebugDirs::debuglink_candidates()`].
+    pub fn find_debuglink(
+        &self,
+        object: &std::path::Path,
+        link: &DebugLink<'_>,
+    ) -> Option<std::path::PathBuf> {
+        self.debuglink_candidates(object, link)
+            .find(|p| link.matches_file(p).unwrap_or(false))
+    }
+
+    /// Return the supplementary debug file named by a `.gnu_debugaltlink` section, if it can be
+    /// found with the expected build-id
+    ///
+    /// `debug_file` is the path of the file containing the section. The path in the section is
+    /// tried first (relative paths are relative to the directory containing `debug_file`), and
+    /// then the `.build-id` directory of each debug directory.
+    pub fn find_debugaltlink(
+        &self,
+        debug_file: &std::path::Path,
+        link: &DebugAltLink<'_>,
+    ) -> Option<std::path::PathBuf> {
+        let dir = debug_file.parent().unwrap_or(std::path::Path::new(""));
+        bytes_to_path(link.file_name)
+            .map(|n| dir.join(n))
+            .filter(|p| link.matches_file(p).unwrap_or(false))
+            .or_else(|| self.find(&link.build_id, FileType::Debug))
+    }
+}
+
+/// Interpret a file name from an object file as a path
+#[cfg(feature = "std")]
+fn bytes_to_path(name: &[u8]) -> Option<&std::path::Path> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Some(std::ffi::OsStr::from_bytes(name).as_ref())
+    }
+    #[cfg(not(unix))]
+    {
+        core::str::from_utf8(name).ok().map(std::path::Path::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Kind;
+    use alloc::format;
+
+    #[test]
+    fn path() {
+        let id = BuildId::new(Kind::Gnu, &[0xab, 0xcd, 0xef, 0x01]).unwrap();
+        assert_eq!(
+            format!("{}", build_id_path(&id, FileType::Debug).unwrap()),
+            ".build-id/ab/cdef01.debug"
+        );
+        assert_eq!(
+            format!("{}", build_id_path(&id, FileType::Executable).unwrap()),
+            ".build-id/ab/cdef01"
+        );
+
+        let id = BuildId::new(Kind::Gnu, &[0xab]).unwrap();
+        assert!(build_id_path(&id, FileType::Debug).is_none());
+    }
+}
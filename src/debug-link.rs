@@ -0,0 +1,194 @@
+//! `.gnu_debuglink` and `.gnu_debugaltlink`: references from an ELF file to its debug files
+//!
+//! Stripped files may name their separate debug file with a `.gnu_debuglink` section (a file
+//! name and the CRC-32 of the debug file) instead of, or in addition to, a build-id. Debug files
+//! processed by `dwz` refer to a supplementary file shared between packages with a
+//! `.gnu_debugaltlink` section (a file name and the build-id of the supplementary file).
+//!
+//! See [`ElfFile::debuglink()`](crate::elf_file::ElfFile::debuglink) and
+//! [`ElfFile::debugaltlink()`](crate::elf_file::ElfFile::debugaltlink) to read them from a file
+//! in memory. With the `std` feature, `read_debug_links()` reads them from a file without reading
+//! the whole file, and `DebugDirs` (in [`debug_file`](crate::debug_file)) locates the files they
+//! refer to.
+use crate::{BuildId, Endian, Error, Kind};
+
+/// Name of the section holding a [`DebugLink`]
+pub const GNU_DEBUGLINK: &[u8] = b".gnu_debuglink";
+/// Name of the section holding a [`DebugAltLink`]
+pub const GNU_DEBUGALTLINK: &[u8] = b".gnu_debugaltlink";
+
+/// Split a nul terminated file name off the start of `data`, returning it (without the nul) and
+/// the remaining bytes
+fn file_name(data: &[u8]) -> Result<(&[u8], &[u8]), Error> {
+    let len = data
+        .iter()
+        .position(|&c| c == 0)
+        .ok_or(Error::Malformed("debug link file name is not terminated"))?;
+    if len == 0 {
+        return Err(Error::Malformed("debug link file name is empty"));
+    }
+    Ok((&data[..len], &data[len + 1..]))
+}
+
+/// Content of a `.gnu_debuglink` section
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugLink<'a> {
+    /// File name (without a directory) of the debug file. Not necessarily UTF-8.
+    pub file_name: &'a [u8],
+    /// CRC-32 of the entire debug file
+    pub crc: u32,
+}
+
+impl<'a> DebugLink<'a> {
+    /// Parse the content of a `.gnu_debuglink` section
+    ///
+    /// The file name is followed by padding to a multiple of 4 bytes, and then the CRC, stored
+    /// in the byte order of the ELF file.
+    pub fn parse(data: &'a [u8], endian: Endian) -> Result<Self, Error> {
+        let (file_name, _) = file_name(data)?;
+        let crc_off = crate::align::align_up(file_name.len() + 1, 4);
+        let crc = data.get(crc_off..crc_off + 4).ok_or(Error::Truncated)?;
+        Ok(DebugLink {
+            file_name,
+            crc: endian.u32(crc),
+        })
+    }
+
+    /// Return true if `data` (the entire content of a candidate debug file) has the expected
+    /// CRC
+    pub fn matches(&self, data: &[u8]) -> bool {
+        crate::crc32::update(0, data) == self.crc
+    }
+
+    /// Return true if the file at `path` has the expected CRC
+    #[cfg(feature = "std")]
+    pub fn matches_file<P: AsRef<std::path::Path>>(&self, path: P) -> Result<bool, Error> {
+        use std::io::Read;
+
+        let mut f = std::fs::File::open(path)?;
+        let mut buf = std::vec![0u8; 64 << 10];
+        let mut crc = 0;
+        loop {
+            match f.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => crc = crate::crc32::update(crc, &buf[..n]),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(crc == self.crc)
+    }
+}
+
+/// Content of a `.gnu_debugaltlink` section
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugAltLink<'a> {
+    /// Path of the supplementary debug file. Usually absolute, otherwise relative to the
+    /// directory of the debug file that refers to it. Not necessarily UTF-8.
+    pub file_name: &'a [u8],
+    /// GNU build-id of the supplementary debug file
+    pub build_id: BuildId,
+}
+
+impl<'a> DebugAltLink<'a> {
+    /// Parse the content of a `.gnu_debugaltlink` section
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        let (file_name, id) = file_name(data)?;
+        if id.is_empty() {
+            return Err(Error::NotFound);
+        }
+        Ok(DebugAltLink {
+            file_name,
+            build_id: BuildId::new(Kind::Gnu, id).ok_or(Error::TooLong(id.len()))?,
+        })
+    }
+
+    /// Return true if `data` (a candidate supplementary file) has the expected build-id
+    pub fn matches(&self, data: &[u8]) -> bool {
+        crate::elf_file::build_id(data).is_ok_and(|id| id == self.build_id.as_bytes())
+    }
+
+    /// Return true if the file at `path` has the expected build-id
+    #[cfg(feature = "std")]
+    pub fn matches_file<P: AsRef<std::path::Path>>(&self, path: P) -> Result<bool, Error> {
+        match crate::from_path(path) {
+            Ok(id) => Ok(id == self.build_id),
+            Err(Error::NotFound) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// The `.gnu_debuglink` and `.gnu_debugaltlink` sections of a file, read by
+/// [`read_debug_links()`]
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugLinks {
+    endian: Endian,
+    debuglink: Option<std::vec::Vec<u8>>,
+    debugaltlink: Option<std::vec::Vec<u8>>,
+}
+
+#[cfg(feature = "std")]
+impl DebugLinks {
+    /// The parsed `.gnu_debuglink` section, if there is one
+    pub fn debuglink(&self) -> Result<Option<DebugLink<'_>>, Error> {
+        self.debuglink
+            .as_deref()
+            .map(|d| DebugLink::parse(d, self.endian))
+            .transpose()
+    }
+
+    /// The parsed `.gnu_debugaltlink` section, if there is one
+    pub fn debugaltlink(&self) -> Result<Option<DebugAltLink<'_>>, Error> {
+        self.debugaltlink
+            .as_deref()
+            .map(DebugAltLink::parse)
+            .transpose()
+    }
+}
+
+/// Read the `.gnu_debuglink` and `.gnu_debugaltlink` sections of an ELF file, without reading
+/// the whole file
+#[cfg(feature = "std")]
+pub fn read_debug_links<R: std::io::Read + std::io::Seek>(r: &mut R) -> Result<DebugLinks, Error> {
+    let (header, [debuglink, debugaltlink]) =
+        crate::elf_file::read_named_sections(r, [GNU_DEBUGLINK, GNU_DEBUGALTLINK])?;
+    Ok(DebugLinks {
+        endian: header.endian,
+        debuglink,
+        debugaltlink,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debuglink() {
+        let mut d = *b"app.debug\0\0\0\x78\x56\x34\x12";
+        let l = DebugLink::parse(&d, Endian::Little).unwrap();
+        assert_eq!(l.file_name, b"app.debug");
+        assert_eq!(l.crc, 0x12345678);
+        assert_eq!(DebugLink::parse(&d, Endian::Big).unwrap().crc, 0x78563412);
+        assert!(DebugLink::parse(&d[..13], Endian::Little).is_err());
+        d[9] = b'x';
+        d[10] = b'x';
+        d[11] = b'x';
+        assert!(DebugLink::parse(&d, Endian::Little).is_err());
+        assert!(DebugLink {
+            file_name: b"",
+            crc: 0xcbf4_3926
+        }
+        .matches(b"123456789"));
+    }
+
+    #[test]
+    fn debugaltlink() {
+        let l = DebugAltLink::parse(b"/usr/lib/debug/.dwz/x.debug\0\x01\x02\x03").unwrap();
+        assert_eq!(l.file_name, b"/usr/lib/debug/.dwz/x.debug");
+        assert_eq!(l.build_id, &[1u8, 2, 3][..]);
+        assert_eq!(DebugAltLink::parse(b"x\0"), Err(Error::NotFound));
+    }
+}
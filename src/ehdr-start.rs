@@ -0,0 +1,42 @@
+// NOTE: GNU ld (bfd), gold, lld, and mold all define `__ehdr_start` at the ELF file header,
+// whenever the headers are part of a loaded segment (as they are in executables and shared
+// libraries), without needing a linker script. Unlike the `dl_iterate_phdr()` walk, this needs
+// neither libc nor the dynamic loader, so it also works for static (and static-pie) executables
+// and for programs that don't use libc at all.
+//
+// NOTE: like the other lookup methods, this finds the build-id of the object this crate is linked
+// into, which is the one whose `__ehdr_start` the linker resolves our reference to.
+use crate::elf_file::{loaded_build_id, FileHeader};
+use crate::{Endian, Error};
+
+extern "C" {
+    static __ehdr_start: u8;
+}
+
+/// The `len` bytes of our own image at `addr`
+///
+/// # Safety
+///
+/// The bytes must be mapped (and remain so).
+unsafe fn image(addr: u64, len: u64) -> Result<&'static [u8], Error> {
+    let addr = usize::try_from(addr).map_err(|_| Error::Malformed("address out of range"))?;
+    let len = usize::try_from(len).map_err(|_| Error::Malformed("segment too large"))?;
+    Ok(core::slice::from_raw_parts(addr as *const u8, len))
+}
+
+pub fn build_id() -> Result<&'static [u8], Error> {
+    let base = unsafe { &__ehdr_start as *const u8 } as u64;
+
+    // the file header is at most 64 bytes, and the program headers follow it in the same segment
+    let header = FileHeader::parse(unsafe { image(base, 64)? })?;
+    if header.endian != Endian::NATIVE || header.class.word_size() != core::mem::size_of::<usize>()
+    {
+        return Err(Error::Malformed("__ehdr_start is not a native ELF header"));
+    }
+    // SAFETY: only the headers, and notes within the file-backed part of a PT_LOAD segment, are
+    // read, and all of those are mapped along with the headers
+    let (notes, id) = loaded_build_id(base, |range| unsafe {
+        image(range.start, range.end - range.start)
+    })?;
+    Ok(&notes[id])
+}
@@ -0,0 +1,952 @@
+//! Parse the build-id out of an ELF file held in memory
+//!
+//! This works on any host, for both 32 and 64 bit ELF files of either byte order. The file does
+//! not need to be loaded or mapped the way the dynamic loader would: offsets in the file are used
+//! directly.
+//!
+//! ```no_run
+//! # fn example(data: &[u8]) -> Result<(), buildid::Error> {
+//! let elf = buildid::elf_file::ElfFile::parse(data)?;
+//! println!("{:x?}", elf.build_id()?);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! With the `std` feature enabled, `read_build_id()` reads only the parts of a file needed to
+//! find the build-id through `std::io::Read` and `std::io::Seek`.
+use crate::debug_link::{DebugAltLink, DebugLink, GNU_DEBUGALTLINK, GNU_DEBUGLINK};
+use crate::note::NoteIter;
+#[cfg(feature = "std")]
+use crate::{BuildId, Kind};
+use crate::{Endian, Error};
+use core::ops::Range;
+
+const ELFMAG: &[u8; 4] = b"\x7fELF";
+const EI_CLASS: usize = 4;
+const EI_DATA: usize = 5;
+const ELFCLASS32: u8 = 1;
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const ELFDATA2MSB: u8 = 2;
+
+/// Program header type of a loadable segment
+pub const PT_LOAD: u32 = 1;
+/// Program header type of a note segment
+pub const PT_NOTE: u32 = 4;
+
+/// Section header type of a note section
+pub const SHT_NOTE: u32 = 7;
+/// Section header type of a section which occupies no space in the file (ie: `.bss`)
+pub const SHT_NOBITS: u32 = 8;
+
+const SHN_UNDEF: u16 = 0;
+const SHN_XINDEX: u16 = 0xffff;
+const PN_XNUM: u16 = 0xffff;
+
+/// Width of the fields in an ELF file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Class {
+    Elf32,
+    Elf64,
+}
+
+impl Class {
+    /// Size of the ELF file header
+    pub fn ehdr_size(self) -> usize {
+        match self {
+            Self::Elf32 => 52,
+            Self::Elf64 => 64,
+        }
+    }
+
+    /// Size of a program header
+    pub fn phdr_size(self) -> usize {
+        match self {
+            Self::Elf32 => 32,
+            Self::Elf64 => 56,
+        }
+    }
+
+    /// Size of a section header
+    pub fn shdr_size(self) -> usize {
+        match self {
+            Self::Elf32 => 40,
+            Self::Elf64 => 64,
+        }
+    }
+
+    /// Size of an address or offset field
+    pub fn word_size(self) -> usize {
+        match self {
+            Self::Elf32 => 4,
+            Self::Elf64 => 8,
+        }
+    }
+}
+
+/// Reads fields of a particular class and byte order
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Reader {
+    pub(crate) class: Class,
+    pub(crate) endian: Endian,
+}
+
+impl Reader {
+    /// Read an address/offset sized field (`Elf32_Addr`, `Elf64_Off`, etc), advancing `data`
+    pub(crate) fn word(&self, data: &mut &[u8]) -> u64 {
+        match self.class {
+            Class::Elf32 => self.u32(data) as u64,
+            Class::Elf64 => {
+                let v = self.endian.u64(data);
+                *data = &data[8..];
+                v
+            }
+        }
+    }
+
+    fn u32(&self, data: &mut &[u8]) -> u32 {
+        let v = self.endian.u32(data);
+        *data = &data[4..];
+        v
+    }
+
+    fn u16(&self, data: &mut &[u8]) -> u16 {
+        let v = self.endian.u16(data);
+        *data = &data[2..];
+        v
+    }
+}
+
+/// The ELF file header (`Elf32_Ehdr`/`Elf64_Ehdr`)
+///
+/// Fields that are not needed to locate notes are omitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileHeader {
+    pub class: Class,
+    pub endian: Endian,
+    pub e_type: u16,
+    pub e_machine: u16,
+    pub e_phoff: u64,
+    pub e_shoff: u64,
+    pub e_phentsize: u16,
+    pub e_phnum: u16,
+    pub e_shentsize: u16,
+    pub e_shnum: u16,
+    pub e_shstrndx: u16,
+}
+
+impl FileHeader {
+    /// Parse the ELF file header at the start of `data`
+    ///
+    /// `data` only needs to contain the header itself (at most 64 bytes).
+    pub fn parse(data: &[u8]) -> Result<Self, Error> {
+        if data.len() < EI_DATA + 1 || &data[..4] != ELFMAG {
+            return Err(Error::UnrecognizedFormat);
+        }
+
+        let class = match data[EI_CLASS] {
+            ELFCLASS32 => Class::Elf32,
+            ELFCLASS64 => Class::Elf64,
+            _ => return Err(Error::Malformed("unknown ELF class")),
+        };
+        let endian = match data[EI_DATA] {
+            ELFDATA2LSB => Endian::Little,
+            ELFDATA2MSB => Endian::Big,
+            _ => return Err(Error::Malformed("unknown ELF data encoding")),
+        };
+        if data.len() < class.ehdr_size() {
+            return Err(Error::Truncated);
+        }
+
+        let r = Reader { class, endian };
+        // skip e_ident
+        let mut d = &data[16..];
+        let e_type = r.u16(&mut d);
+        let e_machine = r.u16(&mut d);
+        let _e_version = r.u32(&mut d);
+        let _e_entry = r.word(&mut d);
+        let e_phoff = r.word(&mut d);
+        let e_shoff = r.word(&mut d);
+        let _e_flags = r.u32(&mut d);
+        let _e_ehsize = r.u16(&mut d);
+        let e_phentsize = r.u16(&mut d);
+        let e_phnum = r.u16(&mut d);
+        let e_shentsize = r.u16(&mut d);
+        let e_shnum = r.u16(&mut d);
+        let e_shstrndx = r.u16(&mut d);
+
+        let h = FileHeader {
+            class,
+            endian,
+            e_type,
+            e_machine,
+            e_phoff,
+            e_shoff,
+            e_phentsize,
+            e_phnum,
+            e_shentsize,
+            e_shnum,
+            e_shstrndx,
+        };
+
+        if h.e_phnum != 0 && (h.e_phentsize as usize) < class.phdr_size() {
+            return Err(Error::Malformed("e_phentsize too small"));
+        }
+        if h.e_shoff != 0 && (h.e_shentsize as usize) < class.shdr_size() {
+            return Err(Error::Malformed("e_shentsize too small"));
+        }
+
+        Ok(h)
+    }
+
+    pub(crate) fn reader(&self) -> Reader {
+        Reader {
+            class: self.class,
+            endian: self.endian,
+        }
+    }
+
+    /// Return true if the real number of program or section headers is stored in the first
+    /// section header (because they didn't fit in the file header)
+    pub fn needs_section_zero(&self) -> bool {
+        self.e_phnum == PN_XNUM || (self.e_shnum == 0 && self.e_shoff != 0)
+    }
+
+    /// Number of program headers, given the first section header (if it is present)
+    pub fn phnum(&self, section_zero: Option<&SectionHeader>) -> Result<usize, Error> {
+        if self.e_phnum != PN_XNUM {
+            return Ok(self.e_phnum as usize);
+        }
+        match section_zero {
+            Some(s) => Ok(s.sh_info as usize),
+            None => Err(Error::Malformed(
+                "e_phnum is PN_XNUM, but there are no sections",
+            )),
+        }
+    }
+
+    /// Number of section headers, given the first section header (if it is present)
+    ///
+    /// Without a section header table (`e_shoff` is 0), this is 0 whatever `e_shnum` says.
+    pub fn shnum(&self, section_zero: Option<&SectionHeader>) -> Result<usize, Error> {
+        if self.e_shoff == 0 {
+            return Ok(0);
+        }
+        if self.e_shnum != 0 {
+            return Ok(self.e_shnum as usize);
+        }
+        match section_zero {
+            Some(s) => Ok(s.sh_size as usize),
+            None => Ok(0),
+        }
+    }
+
+    /// Location of the program header table in the file
+    ///
+    /// `phnum` is the number of program headers, which may differ from `e_phnum` (see
+    /// [`ElfFile::phnum()`]).
+    pub fn ph_table(&self, phnum: usize) -> Result<Range<u64>, Error> {
+        table_range(self.e_phoff, self.e_phentsize, phnum)
+    }
+
+    /// Location of the section header table in the file
+    ///
+    /// `shnum` is the number of section headers, which may differ from `e_shnum` (see
+    /// [`ElfFile::shnum()`]).
+    pub fn sh_table(&self, shnum: usize) -> Result<Range<u64>, Error> {
+        table_range(self.e_shoff, self.e_shentsize, shnum)
+    }
+}
+
+fn table_range(off: u64, entsize: u16, num: usize) -> Result<Range<u64>, Error> {
+    let len = (entsize as u64)
+        .checked_mul(num as u64)
+        .ok_or(Error::Truncated)?;
+    let end = off.checked_add(len).ok_or(Error::Truncated)?;
+    Ok(off..end)
+}
+
+/// A program header (`Elf32_Phdr`/`Elf64_Phdr`), with all fields widened to 64 bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgramHeader {
+    pub p_type: u32,
+    pub p_flags: u32,
+    pub p_offset: u64,
+    pub p_vaddr: u64,
+    pub p_paddr: u64,
+    pub p_filesz: u64,
+    pub p_memsz: u64,
+    pub p_align: u64,
+}
+
+impl ProgramHeader {
+    /// Parse a program header at the start of `data`, which must be at least
+    /// [`Class::phdr_size()`] bytes long
+    pub fn parse(data: &[u8], class: Class, endian: Endian) -> Result<Self, Error> {
+        if data.len() < class.phdr_size() {
+            return Err(Error::Truncated);
+        }
+        let r = Reader { class, endian };
+        let mut d = data;
+        // the position of p_flags differs between the 32 and 64 bit layouts
+        Ok(match class {
+            Class::Elf32 => {
+                let p_type = r.u32(&mut d);
+                let p_offset = r.word(&mut d);
+                let p_vaddr = r.word(&mut d);
+                let p_paddr = r.word(&mut d);
+                let p_filesz = r.word(&mut d);
+                let p_memsz = r.word(&mut d);
+                let p_flags = r.u32(&mut d);
+                let p_align = r.word(&mut d);
+                ProgramHeader {
+                    p_type,
+                    p_flags,
+                    p_offset,
+                    p_vaddr,
+                    p_paddr,
+                    p_filesz,
+                    p_memsz,
+                    p_align,
+                }
+            }
+            Class::Elf64 => {
+                let p_type = r.u32(&mut d);
+                let p_flags = r.u32(&mut d);
+                ProgramHeader {
+                    p_type,
+                    p_flags,
+                    p_offset: r.word(&mut d),
+                    p_vaddr: r.word(&mut d),
+                    p_paddr: r.word(&mut d),
+                    p_filesz: r.word(&mut d),
+                    p_memsz: r.word(&mut d),
+                    p_align: r.word(&mut d),
+                }
+            }
+        })
+    }
+
+    /// Location of the segment's content in the file
+    pub fn file_range(&self) -> Result<Range<u64>, Error> {
+        let end = self
+            .p_offset
+            .checked_add(self.p_filesz)
+            .ok_or(Error::Truncated)?;
+        Ok(self.p_offset..end)
+    }
+}
+
+/// A section header (`Elf32_Shdr`/`Elf64_Shdr`), with all fields widened to 64 bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SectionHeader {
+    pub sh_name: u32,
+    pub sh_type: u32,
+    pub sh_flags: u64,
+    pub sh_addr: u64,
+    pub sh_offset: u64,
+    pub sh_size: u64,
+    pub sh_link: u32,
+    pub sh_info: u32,
+    pub sh_addralign: u64,
+    pub sh_entsize: u64,
+}
+
+impl SectionHeader {
+    /// Parse a section header at the start of `data`, which must be at least
+    /// [`Class::shdr_size()`] bytes long
+    pub fn parse(data: &[u8], class: Class, endian: Endian) -> Result<Self, Error> {
+        if data.len() < class.shdr_size() {
+            return Err(Error::Truncated);
+        }
+        let r = Reader { class, endian };
+        let mut d = data;
+        Ok(SectionHeader {
+            sh_name: r.u32(&mut d),
+            sh_type: r.u32(&mut d),
+            sh_flags: r.word(&mut d),
+            sh_addr: r.word(&mut d),
+            sh_offset: r.word(&mut d),
+            sh_size: r.word(&mut d),
+            sh_link: r.u32(&mut d),
+            sh_info: r.u32(&mut d),
+            sh_addralign: r.word(&mut d),
+            sh_entsize: r.word(&mut d),
+        })
+    }
+
+    /// Location of the section's content in the file
+    ///
+    /// `SHT_NOBITS` sections have no content in the file, and return an empty range.
+    pub fn file_range(&self) -> Result<Range<u64>, Error> {
+        if self.sh_type == SHT_NOBITS {
+            return Ok(self.sh_offset..self.sh_offset);
+        }
+        let end = self
+            .sh_offset
+            .checked_add(self.sh_size)
+            .ok_or(Error::Truncated)?;
+        Ok(self.sh_offset..end)
+    }
+}
+
+/// Return the part of `data` described by `range`
+pub(crate) fn slice(data: &[u8], range: Range<u64>) -> Result<&[u8], Error> {
+    let start = usize::try_from(range.start).map_err(|_| Error::Truncated)?;
+    let end = usize::try_from(range.end).map_err(|_| Error::Truncated)?;
+    data.get(start..end).ok_or(Error::Truncated)
+}
+
+/// Return the nul terminated string starting at `offset` in `strtab`, without the nul
+pub(crate) fn strtab_entry(strtab: &[u8], offset: u32) -> Result<&[u8], Error> {
+    let s = strtab
+        .get(offset as usize..)
+        .ok_or(Error::Malformed("string table offset out of range"))?;
+    let len = s
+        .iter()
+        .position(|&c| c == 0)
+        .ok_or(Error::Malformed("string table entry is not nul terminated"))?;
+    Ok(&s[..len])
+}
+
+/// Find the descriptor of the first build-id note in the note segments/sections
+pub(crate) fn find_build_id<'a, I: Iterator<Item = Result<NoteIter<'a>, Error>>>(
+    iter: I,
+) -> Result<Option<&'a [u8]>, Error> {
+    let mut err = None;
+    for ni in iter {
+        let ni = match ni {
+            Ok(v) => v,
+            Err(e) => {
+                err.get_or_insert(e);
+                continue;
+            }
+        };
+        match ni.build_id() {
+            Ok(Some(id)) => return Ok(Some(id)),
+            Ok(None) => {}
+            Err(e) => {
+                err.get_or_insert(e.into());
+            }
+        }
+    }
+
+    match err {
+        Some(e) => Err(e),
+        None => Ok(None),
+    }
+}
+
+/// Find the build-id of the ELF file whose headers are loaded at `addr`, in an address space that
+/// `read` reads from (such as our own memory, another process's, or a core dump's)
+///
+/// Only notes within the part of a `PT_LOAD` segment that comes from the file are read. Returns
+/// the note segment holding the build-id, and where the build-id is in it.
+pub(crate) fn loaded_build_id<D: AsRef<[u8]>>(
+    addr: u64,
+    mut read: impl FnMut(Range<u64>) -> Result<D, Error>,
+) -> Result<(D, Range<usize>), Error> {
+    let range = |start: u64, len: u64| Some(start..start.checked_add(len)?);
+
+    let ehdr = read(range(addr, 64).ok_or(Error::Truncated)?)?;
+    let header = FileHeader::parse(ehdr.as_ref())?;
+    let phnum = header.phnum(None)?;
+    if phnum == 0 {
+        // e_phentsize is only checked when there are program headers
+        return Err(Error::NotFound);
+    }
+    let table = header.ph_table(phnum)?;
+    let table = addr
+        .checked_add(table.start)
+        .and_then(|start| range(start, table.end - table.start))
+        .ok_or(Error::Truncated)?;
+    let table = read(table)?;
+    let phdrs = || {
+        table
+            .as_ref()
+            .chunks(header.e_phentsize as usize)
+            .filter_map(|e| ProgramHeader::parse(e, header.class, header.endian).ok())
+    };
+
+    // the headers are at offset 0 of the file, which the first PT_LOAD maps
+    let first = phdrs()
+        .find(|p| p.p_type == PT_LOAD)
+        .ok_or(Error::Malformed("no PT_LOAD segment"))?;
+    let bias = addr.wrapping_sub(first.p_vaddr.wrapping_sub(first.p_offset));
+
+    let mut err = None;
+    for phdr in phdrs().filter(|p| p.p_type == PT_NOTE) {
+        let end = phdr.p_vaddr.checked_add(phdr.p_filesz);
+        let loaded = end.is_some_and(|end| {
+            phdrs().any(|l| {
+                l.p_type == PT_LOAD
+                    && l.p_vaddr <= phdr.p_vaddr
+                    && end <= l.p_vaddr.saturating_add(l.p_filesz)
+            })
+        });
+        if !loaded {
+            err.get_or_insert(Error::Malformed(
+                "PT_NOTE segment is outside of the loaded segments",
+            ));
+            continue;
+        }
+
+        let notes = range(bias.wrapping_add(phdr.p_vaddr), phdr.p_filesz)
+            .ok_or(Error::Truncated)
+            .and_then(&mut read);
+        let notes = match notes {
+            Ok(v) => v,
+            Err(e) => {
+                err.get_or_insert(e);
+                continue;
+            }
+        };
+        let bytes = notes.as_ref();
+        let found =
+            match NoteIter::with_align(bytes, header.endian, phdr.p_align as usize).build_id() {
+                Ok(Some(id)) => {
+                    let start = id.as_ptr() as usize - bytes.as_ptr() as usize;
+                    Some(start..start + id.len())
+                }
+                Ok(None) => None,
+                Err(e) => {
+                    err.get_or_insert(e.into());
+                    None
+                }
+            };
+        if let Some(found) = found {
+            return Ok((notes, found));
+        }
+    }
+    Err(err.unwrap_or(Error::NotFound))
+}
+
+/// An ELF file held in memory
+#[derive(Debug, Clone, Copy)]
+pub struct ElfFile<'a> {
+    data: &'a [u8],
+    header: FileHeader,
+}
+
+impl<'a> ElfFile<'a> {
+    /// Parse the ELF header of `data`, which holds an entire ELF file
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        Ok(ElfFile {
+            data,
+            header: FileHeader::parse(data)?,
+        })
+    }
+
+    pub fn header(&self) -> &FileHeader {
+        &self.header
+    }
+
+    /// The entire ELF file
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// The first section header, which holds the real section and program header counts when
+    /// they don't fit into the file header
+    fn section_zero(&self) -> Result<Option<SectionHeader>, Error> {
+        if self.header.e_shoff == 0 {
+            return Ok(None);
+        }
+        let r = self.header.sh_table(1)?;
+        Ok(Some(SectionHeader::parse(
+            slice(self.data, r)?,
+            self.header.class,
+            self.header.endian,
+        )?))
+    }
+
+    /// Number of program headers
+    pub fn phnum(&self) -> Result<usize, Error> {
+        if !self.header.needs_section_zero() {
+            return self.header.phnum(None);
+        }
+        self.header.phnum(self.section_zero()?.as_ref())
+    }
+
+    /// Number of section headers
+    pub fn shnum(&self) -> Result<usize, Error> {
+        if !self.header.needs_section_zero() {
+            return self.header.shnum(None);
+        }
+        self.header.shnum(self.section_zero()?.as_ref())
+    }
+
+    /// Iterate over the program headers
+    pub fn program_headers(&self) -> Result<ProgramHeaders<'a>, Error> {
+        let phnum = self.phnum()?;
+        let table = slice(self.data, self.header.ph_table(phnum)?)?;
+        Ok(ProgramHeaders {
+            table,
+            entsize: self.header.e_phentsize as usize,
+            reader: self.header.reader(),
+        })
+    }
+
+    /// Iterate over the section headers
+    pub fn section_headers(&self) -> Result<SectionHeaders<'a>, Error> {
+        let shnum = self.shnum()?;
+        let table = slice(self.data, self.header.sh_table(shnum)?)?;
+        Ok(SectionHeaders {
+            table,
+            entsize: self.header.e_shentsize as usize,
+            reader: self.header.reader(),
+        })
+    }
+
+    /// Content of the segment described by `phdr`
+    pub fn segment_data(&self, phdr: &ProgramHeader) -> Result<&'a [u8], Error> {
+        slice(self.data, phdr.file_range()?)
+    }
+
+    /// Content of the section described by `shdr`
+    pub fn section_data(&self, shdr: &SectionHeader) -> Result<&'a [u8], Error> {
+        slice(self.data, shdr.file_range()?)
+    }
+
+    /// The section header string table (`.shstrtab`), which holds section names
+    fn shstrtab(&self) -> Result<&'a [u8], Error> {
+        let idx = match self.header.e_shstrndx {
+            SHN_UNDEF => return Err(Error::Malformed("no section name string table")),
+            SHN_XINDEX => match self.section_zero()? {
+                Some(s) => s.sh_link as usize,
+                None => return Err(Error::Malformed("no section name string table")),
+            },
+            i => i as usize,
+        };
+        let shdr = self
+            .section_headers()?
+            .nth(idx)
+            .ok_or(Error::Malformed("e_shstrndx out of range"))??;
+        self.section_data(&shdr)
+    }
+
+    /// Name of the section described by `shdr`
+    pub fn section_name(&self, shdr: &SectionHeader) -> Result<&'a [u8], Error> {
+        strtab_entry(self.shstrtab()?, shdr.sh_name)
+    }
+
+    /// Find the first section named `name`
+    pub fn section_by_name(&self, name: &[u8]) -> Result<Option<SectionHeader>, Error> {
+        let strtab = self.shstrtab()?;
+        for shdr in self.section_headers()? {
+            let shdr = shdr?;
+            if strtab_entry(strtab, shdr.sh_name)? == name {
+                return Ok(Some(shdr));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Iterate over the notes in each `PT_NOTE` segment
+    pub fn note_segments(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<NoteIter<'a>, Error>> + 'a, Error> {
+        let this = *self;
+        Ok(self.program_headers()?.filter_map(move |phdr| {
+            let phdr = match phdr {
+                Ok(v) => v,
+                Err(e) => return Some(Err(e)),
+            };
+            if phdr.p_type != PT_NOTE {
+                return None;
+            }
+            Some(
+                this.segment_data(&phdr)
+                    .map(|d| NoteIter::with_align(d, this.header.endian, phdr.p_align as usize)),
+            )
+        }))
+    }
+
+    /// Iterate over the notes in each `SHT_NOTE` section
+    pub fn note_sections(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<NoteIter<'a>, Error>> + 'a, Error> {
+        let this = *self;
+        Ok(self.section_headers()?.filter_map(move |shdr| {
+            let shdr = match shdr {
+                Ok(v) => v,
+                Err(e) => return Some(Err(e)),
+            };
+            if shdr.sh_type != SHT_NOTE {
+                return None;
+            }
+            Some(
+                this.section_data(&shdr).map(|d| {
+                    NoteIter::with_align(d, this.header.endian, shdr.sh_addralign as usize)
+                }),
+            )
+        }))
+    }
+
+    /// The GNU build-id (the descriptor of the `NT_GNU_BUILD_ID` note)
+    ///
+    /// `PT_NOTE` segments are examined first. If none of them contain a build-id (or there are
+    /// no program headers, as in relocatable objects), `SHT_NOTE` sections are examined.
+    pub fn build_id(&self) -> Result<&'a [u8], Error> {
+        let seg_err = match find_build_id(self.note_segments()?) {
+            Ok(Some(id)) => return Ok(id),
+            Ok(None) => None,
+            Err(e) => Some(e),
+        };
+
+        match find_build_id(self.note_sections()?) {
+            Ok(Some(id)) => Ok(id),
+            Ok(None) => Err(seg_err.unwrap_or(Error::NotFound)),
+            Err(e) => Err(seg_err.unwrap_or(e)),
+        }
+    }
+
+    /// The parsed `.gnu_debuglink` section, if there is one
+    pub fn debuglink(&self) -> Result<Option<DebugLink<'a>>, Error> {
+        match self.section_by_name(GNU_DEBUGLINK)? {
+            Some(shdr) => DebugLink::parse(self.section_data(&shdr)?, self.header.endian).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// The parsed `.gnu_debugaltlink` section, if there is one
+    pub fn debugaltlink(&self) -> Result<Option<DebugAltLink<'a>>, Error> {
+        match self.section_by_name(GNU_DEBUGALTLINK)? {
+            Some(shdr) => DebugAltLink::parse(self.section_data(&shdr)?).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Iterate over program headers of an [`ElfFile`]
+#[derive(Debug, Clone)]
+pub struct ProgramHeaders<'a> {
+    table: &'a [u8],
+    entsize: usize,
+    reader: Reader,
+}
+
+impl Iterator for ProgramHeaders<'_> {
+    type Item = Result<ProgramHeader, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.table.is_empty() {
+            return None;
+        }
+        let (e, rest) = self.table.split_at(self.entsize.min(self.table.len()));
+        self.table = rest;
+        Some(ProgramHeader::parse(
+            e,
+            self.reader.class,
+            self.reader.endian,
+        ))
+    }
+}
+
+/// Iterate over section headers of an [`ElfFile`]
+#[derive(Debug, Clone)]
+pub struct SectionHeaders<'a> {
+    table: &'a [u8],
+    entsize: usize,
+    reader: Reader,
+}
+
+impl Iterator for SectionHeaders<'_> {
+    type Item = Result<SectionHeader, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.table.is_empty() {
+            return None;
+        }
+        let (e, rest) = self.table.split_at(self.entsize.min(self.table.len()));
+        self.table = rest;
+        Some(SectionHeader::parse(
+            e,
+            self.reader.class,
+            self.reader.endian,
+        ))
+    }
+}
+
+/// Return the GNU build-id of the ELF file held in `data`
+///
+/// See [`ElfFile::build_id()`].
+pub fn build_id(data: &[u8]) -> Result<&[u8], Error> {
+    ElfFile::parse(data)?.build_id()
+}
+
+/// Return the location of the GNU build-id within `data`
+///
+/// This is the build-id returned by [`build_id()`], as a range of file offsets.
+pub fn build_id_range(data: &[u8]) -> Result<Range<usize>, Error> {
+    let id = build_id(data)?;
+    let start = id.as_ptr() as usize - data.as_ptr() as usize;
+    Ok(start..start + id.len())
+}
+
+/// Overwrite the GNU build-id of the ELF file held in `data` with `id`
+///
+/// Only the note's descriptor is changed, so `id` must be the same length as the existing
+/// build-id. If it is not, [`Error::LengthMismatch`] is returned and `data` is left unchanged.
+pub fn set_build_id(data: &mut [u8], id: &[u8]) -> Result<(), Error> {
+    let range = build_id_range(data)?;
+    if range.len() != id.len() {
+        return Err(Error::LengthMismatch {
+            stored: range.len(),
+            new: id.len(),
+        });
+    }
+    data[range].copy_from_slice(id);
+    Ok(())
+}
+
+/// Largest note segment/section, or header table, that [`read_build_id()`] will read
+#[cfg(feature = "std")]
+const MAX_READ: u64 = 16 << 20;
+
+/// Read `range` of the file
+#[cfg(feature = "std")]
+pub(crate) fn read_range<R: std::io::Read + std::io::Seek>(
+    r: &mut R,
+    range: Range<u64>,
+) -> Result<std::vec::Vec<u8>, Error> {
+    let len = range.end.saturating_sub(range.start);
+    if len > MAX_READ {
+        return Err(Error::Malformed("table or note is too large to read"));
+    }
+    let mut buf = std::vec![0u8; len as usize];
+    r.seek(std::io::SeekFrom::Start(range.start))?;
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Read the file header, and section zero if the header refers to it
+#[cfg(feature = "std")]
+pub(crate) fn read_header<R: std::io::Read + std::io::Seek>(
+    r: &mut R,
+) -> Result<(FileHeader, Option<SectionHeader>), Error> {
+    use std::io::Read;
+
+    let mut ehdr = std::vec::Vec::new();
+    r.seek(std::io::SeekFrom::Start(0))?;
+    r.by_ref()
+        .take(Class::Elf64.ehdr_size() as u64)
+        .read_to_end(&mut ehdr)?;
+    let header = FileHeader::parse(&ehdr)?;
+
+    let section_zero = if header.needs_section_zero() {
+        let d = read_range(r, header.sh_table(1)?)?;
+        Some(SectionHeader::parse(&d, header.class, header.endian)?)
+    } else {
+        None
+    };
+    Ok((header, section_zero))
+}
+
+/// Content of sections read by [`read_named_sections()`], `None` if there was no such section
+#[cfg(feature = "std")]
+pub(crate) type NamedSections<const N: usize> = [Option<std::vec::Vec<u8>>; N];
+
+/// Read the content of the first section with each of `names`, without reading the whole file
+#[cfg(feature = "std")]
+pub(crate) fn read_named_sections<R: std::io::Read + std::io::Seek, const N: usize>(
+    r: &mut R,
+    names: [&[u8]; N],
+) -> Result<(FileHeader, NamedSections<N>), Error> {
+    let (header, section_zero) = read_header(r)?;
+    let mut found = core::array::from_fn(|_| None);
+
+    let shnum = header.shnum(section_zero.as_ref())?;
+    let strndx = match header.e_shstrndx {
+        SHN_UNDEF => return Ok((header, found)),
+        SHN_XINDEX => match section_zero {
+            Some(s) => s.sh_link as usize,
+            None => return Err(Error::Malformed("no section name string table")),
+        },
+        i => i as usize,
+    };
+    if shnum == 0 {
+        return Ok((header, found));
+    }
+
+    let shdrs = read_range(r, header.sh_table(shnum)?)?;
+    let shdrs = shdrs
+        .chunks(header.e_shentsize as usize)
+        .map(|e| SectionHeader::parse(e, header.class, header.endian))
+        .collect::<Result<std::vec::Vec<_>, _>>()?;
+    let strtab = shdrs
+        .get(strndx)
+        .ok_or(Error::Malformed("e_shstrndx out of range"))?;
+    let strtab = read_range(r, strtab.file_range()?)?;
+
+    for shdr in &shdrs {
+        let name = strtab_entry(&strtab, shdr.sh_name)?;
+        if let Some(i) = names.iter().position(|n| *n == name) {
+            if found[i].is_none() {
+                found[i] = Some(read_range(r, shdr.file_range()?)?);
+            }
+        }
+    }
+    Ok((header, found))
+}
+
+/// Read the GNU build-id from an ELF file, without reading the whole file
+///
+/// Only the file header, the program and section header tables, and the note segments/sections
+/// are read, so this is suitable for very large files (for example, ones that contain debug
+/// info). The notes are located the same way as [`ElfFile::build_id()`] does.
+#[cfg(feature = "std")]
+pub fn read_build_id<R: std::io::Read + std::io::Seek>(r: &mut R) -> Result<BuildId, Error> {
+    let (header, section_zero) = read_header(r)?;
+
+    // examine one note segment/section, recording the first error we see
+    fn check(
+        d: &[u8],
+        endian: Endian,
+        align: usize,
+        err: &mut Option<Error>,
+    ) -> Option<Result<BuildId, Error>> {
+        match NoteIter::with_align(d, endian, align).build_id() {
+            Ok(Some(id)) => Some(BuildId::new(Kind::Gnu, id).ok_or(Error::TooLong(id.len()))),
+            Ok(None) => None,
+            Err(e) => {
+                err.get_or_insert(e.into());
+                None
+            }
+        }
+    }
+
+    let mut err = None;
+
+    let phnum = header.phnum(section_zero.as_ref())?;
+    if phnum > 0 {
+        let phdrs = read_range(r, header.ph_table(phnum)?)?;
+        for e in phdrs.chunks(header.e_phentsize as usize) {
+            let phdr = ProgramHeader::parse(e, header.class, header.endian)?;
+            if phdr.p_type != PT_NOTE {
+                continue;
+            }
+            let d = read_range(r, phdr.file_range()?)?;
+            if let Some(v) = check(&d, header.endian, phdr.p_align as usize, &mut err) {
+                return v;
+            }
+        }
+    }
+
+    let shnum = header.shnum(section_zero.as_ref())?;
+    if shnum > 0 {
+        let shdrs = read_range(r, header.sh_table(shnum)?)?;
+        for e in shdrs.chunks(header.e_shentsize as usize) {
+            let shdr = SectionHeader::parse(e, header.class, header.endian)?;
+            if shdr.sh_type != SHT_NOTE {
+                continue;
+            }
+            let d = read_range(r, shdr.file_range()?)?;
+            if let Some(v) = check(&d, header.endian, shdr.sh_addralign as usize, &mut err) {
+                return v;
+            }
+        }
+    }
+
+    Err(err.unwrap_or(Error::NotFound))
+}
@@ -1,287 +1,754 @@
-use crate::align::align_up;
+use crate::note::{Note, NoteIter};
+use crate::{Endian, Error};
+use core::ffi::{c_void, CStr};
 use core::mem;
-use core::mem::MaybeUninit;
-use core::{convert::TryInto, fmt};
-use log::{debug, error, warn};
-
-// FIXME: dl_phdr_info references are actually unsafe here because of how glibc defines
-// dl_phdr_info (to have some fields present depending on the size provided (<glibc-2.4 omits
-// them).
-//
-// We'll probably get away with this because we're not accessing those fields, but this is
-// technically wrong because we can access those fields in safe code.
-//
+use core::ops::Range;
+use log::debug;
+
+// `dl_phdr_info` has grown over time, and the fields after `dlpi_phnum` are only present if the
+// `size` passed to the callback covers them (glibc before 2.4 omits them). So it is only accessed
+// through a raw pointer, and only the fields up to `dlpi_phnum` are read, after checking `size`.
 
 #[cfg(target_pointer_width = "64")]
-mod elf {
-    pub type ElfPhdr = libc::Elf64_Phdr;
-}
+type ElfPhdr = libc::Elf64_Phdr;
 #[cfg(target_pointer_width = "32")]
-mod elf {
-    pub type ElfPhdr = libc::Elf32_Phdr;
+type ElfPhdr = libc::Elf32_Phdr;
+
+/// Aborts the process when dropped, which only happens if a panic unwinds past it
+struct AbortOnUnwind;
+
+impl Drop for AbortOnUnwind {
+    fn drop(&mut self) {
+        // unwinding out of an `extern "C"` fn (and through the dynamic loader) is undefined
+        // behaviour
+        unsafe { libc::abort() }
+    }
 }
-use elf::*;
 
-const NT_GNU_BUILD_ID: u32 = 3;
+// Ideally, we'd use a trait alias instead of a type alias and construct the type out of the
+// trait. But that's not stable right now (see https://github.com/rust-lang/rust/issues/41517)
+unsafe extern "C" fn phdr_cb(
+    info: *mut libc::dl_phdr_info,
+    size: libc::size_t,
+    data: *mut libc::c_void,
+) -> libc::c_int {
+    let closure: &mut &mut dyn FnMut(*const libc::dl_phdr_info, usize) -> libc::c_int =
+        &mut *(data as *mut &mut dyn FnMut(*const libc::dl_phdr_info, usize) -> libc::c_int);
 
-// TODO: experiment with using more custom DST here to avoid manually extracting fields
-#[derive(Debug)]
-struct Note {
-    data: [u8],
+    let guard = AbortOnUnwind;
+    let r = closure(info, size);
+    mem::forget(guard);
+    r
 }
 
-const MIN_NOTE_SIZE: usize = mem::size_of::<usize>() * 3;
+/// Call `cb` with each loaded object, until it returns non-zero
+///
+/// Objects whose `dl_phdr_info` is inconsistent are skipped.
+fn object_map<F: FnMut(LoadedObject) -> libc::c_int>(mut cb: F) -> libc::c_int {
+    let mut cb = |info: *const libc::dl_phdr_info, size: usize| {
+        // SAFETY: `dl_iterate_phdr()` passes `size` readable bytes
+        match unsafe { LoadedObject::from_info(info, size) } {
+            Ok(obj) => cb(obj),
+            Err(e) => {
+                debug!("skipping loaded object: {}", e);
+                0
+            }
+        }
+    };
+    let mut cb: &mut dyn FnMut(*const libc::dl_phdr_info, usize) -> libc::c_int = &mut cb;
+    let cb = &mut cb;
+    unsafe { libc::dl_iterate_phdr(Some(phdr_cb), cb as *mut _ as *mut _) }
+}
 
-#[derive(Debug)]
-enum NoteError {
-    MissingHeader { size: usize },
-    Truncated { have: usize, need: usize },
+/// An object (executable, shared library, dynamic loader, or vDSO) loaded into the current
+/// process
+///
+/// This is a copy of the information `dl_iterate_phdr()` provides for the object. The references
+/// it hands out point into the mapped object, and are only valid while the object remains loaded
+/// (ie: until it is `dlclose()`ed).
+#[derive(Debug, Clone, Copy)]
+pub struct LoadedObject {
+    addr: usize,
+    name: *const libc::c_char,
+    phdr: *const ElfPhdr,
+    phnum: u16,
 }
 
-impl fmt::Display for NoteError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::MissingHeader { size } => write!(
-                f,
-                "have {} bytes, but need at least {}",
-                size, MIN_NOTE_SIZE
-            ),
-            Self::Truncated { have, need } => {
-                write!(f, "have {} bytes, but need at least {}", have, need)
+// SAFETY: the pointers refer to loader-owned, immutable data that may be read from any thread
+unsafe impl Send for LoadedObject {}
+unsafe impl Sync for LoadedObject {}
+
+impl LoadedObject {
+    /// Copy the fields we use out of the `dl_phdr_info` at `info`, which is `size` bytes long
+    ///
+    /// # Safety
+    ///
+    /// `info` must point to `size` readable bytes, as it does in a `dl_iterate_phdr()` callback.
+    unsafe fn from_info(info: *const libc::dl_phdr_info, size: usize) -> Result<Self, Error> {
+        let need = mem::offset_of!(libc::dl_phdr_info, dlpi_phnum) + mem::size_of::<u16>();
+        if info.is_null() || size < need {
+            return Err(Error::Malformed("dl_phdr_info is too short"));
+        }
+        LoadedObject {
+            addr: (*info).dlpi_addr as usize,
+            name: (*info).dlpi_name,
+            phdr: (*info).dlpi_phdr,
+            phnum: (*info).dlpi_phnum,
+        }
+        .validate()
+    }
+
+    /// Check that the program header table can be read, and that the `PT_LOAD` segments it
+    /// describes make sense
+    fn validate(self) -> Result<Self, Error> {
+        if self.phnum > 0 {
+            let table =
+                (self.phdr as usize).checked_add(self.phnum as usize * mem::size_of::<ElfPhdr>());
+            if self.phdr.is_null()
+                || self.phdr as usize & (mem::align_of::<ElfPhdr>() - 1) != 0
+                || table.is_none()
+            {
+                return Err(Error::Malformed("invalid program header table"));
+            }
+        }
+        for phdr in self.phdrs().iter().filter(|p| p.p_type == libc::PT_LOAD) {
+            let end = self
+                .addr
+                .checked_add(phdr.p_vaddr as usize)
+                .and_then(|start| start.checked_add(phdr.p_memsz as usize));
+            if phdr.p_filesz > phdr.p_memsz || end.is_none() {
+                return Err(Error::Malformed("PT_LOAD segment is inconsistent"));
             }
         }
+        Ok(self)
     }
-}
 
-impl Note {
-    // NOTE: the _standards_ say to use 8 byte alignment in 64-bit land. But llvm and others note
-    // that everyone actually uses 4 byte alignment. Perfect. Hopefully this always works.
-    const ALIGN: usize = 4;
+    // the program headers live as long as the object is loaded, like everything else we return
+    fn phdrs(&self) -> &'static [ElfPhdr] {
+        if self.phnum == 0 {
+            return &[];
+        }
+        // SAFETY: `validate()` checked the table is non-null and aligned, and the dynamic loader
+        // keeps it mapped while the object is loaded
+        unsafe { core::slice::from_raw_parts(self.phdr, self.phnum as usize) }
+    }
 
-    // technically safe because we'll only panic if I screw up
-    fn from_bytes_raw(data: &[u8]) -> &Self {
-        unsafe { &*(data as *const [u8] as *const Note) }
+    /// Describe the object whose (native) ELF header is at `base`, in the `len` bytes mapped there
+    ///
+    /// The header is at offset 0 of the file, which the first `PT_LOAD` segment maps, so that
+    /// gives the load bias. There is no name to go with it.
+    ///
+    /// # Safety
+    ///
+    /// The `len` bytes at `base` must be readable, and remain mapped while the object is loaded.
+    #[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+    unsafe fn from_header(base: usize, len: usize) -> Result<Self, Error> {
+        use crate::elf_file::FileHeader;
+
+        let header = FileHeader::parse(core::slice::from_raw_parts(base as *const u8, len))?;
+        if header.endian != Endian::NATIVE
+            || header.class.word_size() != mem::size_of::<usize>()
+            || header.e_phentsize as usize != mem::size_of::<ElfPhdr>()
+        {
+            return Err(Error::Malformed("not a native ELF header"));
+        }
+        let table = header.ph_table(header.phnum(None)?)?;
+        if table.end > len as u64 {
+            return Err(Error::Malformed(
+                "program headers are not mapped with the header",
+            ));
+        }
+        let obj = LoadedObject {
+            addr: 0,
+            name: core::ptr::null(),
+            phdr: (base + table.start as usize) as *const ElfPhdr,
+            phnum: header.e_phnum,
+        }
+        .validate()?;
+
+        let first = obj
+            .phdrs()
+            .iter()
+            .find(|p| p.p_type == libc::PT_LOAD)
+            .ok_or(Error::Malformed("no PT_LOAD segment"))?;
+        LoadedObject {
+            addr: base.wrapping_sub((first.p_vaddr as usize).wrapping_sub(first.p_offset as usize)),
+            ..obj
+        }
+        .validate()
     }
 
-    fn from_bytes(data: &[u8]) -> Result<(&Self, &[u8]), NoteError> {
-        let u = core::mem::size_of::<u32>();
-        if data.len() < u * 3 {
-            return Err(NoteError::MissingHeader { size: data.len() });
+    /// Path name of the object, as recorded by the dynamic loader
+    ///
+    /// The main executable is usually reported with an empty name.
+    pub fn name(&self) -> &'static CStr {
+        if self.name.is_null() {
+            return Default::default();
         }
+        unsafe { CStr::from_ptr(self.name) }
+    }
 
-        Self::from_bytes_raw(data).split_trailing()
+    /// Path of the file the object was loaded from
+    ///
+    /// The dynamic loader usually reports the main executable with an empty name, so
+    /// `std::env::current_exe()` is used for it instead.
+    #[cfg(feature = "std")]
+    pub fn path(&self) -> Result<std::path::PathBuf, Error> {
+        use std::os::unix::ffi::OsStrExt;
+
+        let name = self.name().to_bytes();
+        if name.is_empty() {
+            return Ok(std::env::current_exe()?);
+        }
+        Ok(std::ffi::OsStr::from_bytes(name).into())
     }
 
-    fn name_len(&self) -> usize {
-        u32::from_ne_bytes(self.data[..core::mem::size_of::<u32>()].try_into().unwrap()) as usize
+    /// The `.gnu_debuglink` and `.gnu_debugaltlink` sections of the object
+    ///
+    /// These sections are not loaded into memory, so they are read from the file the object was
+    /// loaded from (see [`LoadedObject::path()`]).
+    #[cfg(feature = "std")]
+    pub fn debug_links(&self) -> Result<crate::debug_link::DebugLinks, Error> {
+        let mut f = std::io::BufReader::new(std::fs::File::open(self.path()?)?);
+        crate::debug_link::read_debug_links(&mut f)
     }
 
-    fn desc_len(&self) -> usize {
-        let u = core::mem::size_of::<u32>();
-        u32::from_ne_bytes(self.data[u..(u + u)].try_into().unwrap()) as usize
+    /// Difference between the addresses in the object's program headers and the addresses it
+    /// is actually mapped at
+    pub fn load_bias(&self) -> usize {
+        self.addr
     }
 
-    fn type_(&self) -> u32 {
-        let u = core::mem::size_of::<u32>();
-        u32::from_ne_bytes(self.data[(u + u)..(u + u + u)].try_into().unwrap())
+    /// Address ranges of each `PT_LOAD` segment of the object, as mapped in memory
+    pub fn segments(&self) -> Segments {
+        Segments {
+            phdrs: self.phdrs().iter(),
+            bias: self.addr,
+        }
     }
 
-    fn name(&self) -> &[u8] {
-        let u = core::mem::size_of::<u32>();
-        let b = u * 3;
-        &self.data[b..(b + self.name_len())]
+    /// Return true if `addr` falls within one of the object's `PT_LOAD` segments
+    pub fn contains(&self, addr: usize) -> bool {
+        self.segments().any(|s| s.contains(&addr))
     }
 
-    fn desc(&self) -> &[u8] {
-        let u = core::mem::size_of::<u32>();
-        let b = u * 3 + align_up(self.name_len(), Self::ALIGN);
-        &self.data[b..(b + self.desc_len())]
+    /// Iterate over the notes in each of the object's `PT_NOTE` segments
+    pub fn notes(&self) -> Notes {
+        Notes {
+            object: *self,
+            phdrs: self.phdrs().iter(),
+            notes: None,
+        }
+    }
+
+    /// The GNU build-id (content of the `NT_GNU_BUILD_ID` note) of this object, if it has one
+    pub fn build_id(&self) -> Option<&'static [u8]> {
+        self.try_build_id().ok()
     }
 
-    fn split_trailing(&self) -> Result<(&Self, &[u8]), NoteError> {
-        let u = core::mem::size_of::<u32>();
-        let end =
-            u * 3 + align_up(self.name_len(), Self::ALIGN) + align_up(self.desc_len(), Self::ALIGN);
-        if end > self.data.len() {
-            Err(NoteError::Truncated {
-                need: end,
-                have: self.data.len(),
+    /// The GNU build-id of this object, or the reason it could not be found
+    ///
+    /// If a malformed note (or a `PT_NOTE` segment outside of the loaded segments) is
+    /// encountered, the remaining `PT_NOTE` segments are still searched. [`Error::InvalidNote`]
+    /// (or [`Error::Malformed`]) is only returned if none of them contain a build-id.
+    pub fn try_build_id(&self) -> Result<&'static [u8], Error> {
+        let mut err = None;
+        for phdr in self.phdrs() {
+            let found = match self.note_segment(phdr) {
+                Some(ni) => ni.and_then(|ni| ni.build_id().map_err(Error::InvalidNote)),
+                None => continue,
+            };
+
+            match found {
+                Ok(Some(id)) => return Ok(id),
+                Ok(None) => {}
+                Err(e) => {
+                    debug!("note program segment is invalid: {}", e);
+                    err.get_or_insert(e);
+                }
+            }
+        }
+
+        Err(err.unwrap_or(Error::NotFound))
+    }
+
+    /// Locate the content of a `PT_NOTE` segment of the object
+    ///
+    /// Only the first `p_filesz` bytes of a segment come from the file (the rest of `p_memsz` is
+    /// zero filled), so only those are notes. They must be within the part of a `PT_LOAD` segment
+    /// that comes from the file, which ensures they are mapped.
+    fn note_segment(&self, phdr: &ElfPhdr) -> Option<Result<NoteIter<'static>, Error>> {
+        if phdr.p_type != libc::PT_NOTE {
+            return None;
+        }
+
+        let start = phdr.p_vaddr as usize;
+        let len = phdr.p_filesz as usize;
+        let loaded = start.checked_add(len).is_some_and(|end| {
+            self.phdrs().iter().any(|l| {
+                l.p_type == libc::PT_LOAD
+                    && l.p_vaddr as usize <= start
+                    && end <= (l.p_vaddr as usize).saturating_add(l.p_filesz as usize)
             })
-        } else {
-            Ok((Self::from_bytes_raw(&self.data[0..end]), &self.data[end..]))
+        });
+        if !loaded {
+            return Some(Err(Error::Malformed(
+                "PT_NOTE segment is outside of the loaded segments",
+            )));
         }
+
+        // SAFETY: the notes are within a `PT_LOAD` segment, which `validate()` checked doesn't
+        // wrap around the address space
+        let segment =
+            unsafe { core::slice::from_raw_parts(self.addr.wrapping_add(start) as *const u8, len) };
+        Some(Ok(NoteIter::with_align(
+            segment,
+            Endian::NATIVE,
+            phdr.p_align as usize,
+        )))
     }
 }
 
-// Ideally, we'd use a trait alias instead of a type alias and construct the type out of the
-// trait. But that's not stable right now (see https://github.com/rust-lang/rust/issues/41517)
-unsafe extern "C" fn phdr_cb(
-    info: *mut libc::dl_phdr_info,
-    size: libc::size_t,
-    data: *mut libc::c_void,
-) -> libc::c_int {
-    let closure: &mut &mut dyn FnMut(&libc::dl_phdr_info, usize) -> libc::c_int = &mut *(data
-        as *mut &mut dyn for<'r> core::ops::FnMut(&'r libc::dl_phdr_info, usize) -> i32);
-    let info = &*info;
-
-    closure(info, size)
+/// Iterate over the address ranges of the `PT_LOAD` segments of a [`LoadedObject`]
+pub struct Segments {
+    phdrs: core::slice::Iter<'static, ElfPhdr>,
+    bias: usize,
 }
 
-fn object_map<F: FnMut(&'static libc::dl_phdr_info, usize) -> libc::c_int>(
-    mut cb: F,
-) -> libc::c_int {
-    let mut cb: &mut dyn FnMut(&'static libc::dl_phdr_info, usize) -> libc::c_int = &mut cb;
-    let cb = &mut cb;
-    unsafe { libc::dl_iterate_phdr(Some(phdr_cb), cb as *mut _ as *mut _) }
+impl Iterator for Segments {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for phdr in self.phdrs.by_ref() {
+            if phdr.p_type == libc::PT_LOAD {
+                let start = self.bias.wrapping_add(phdr.p_vaddr as usize);
+                return Some(start..start.wrapping_add(phdr.p_memsz as usize));
+            }
+        }
+
+        None
+    }
 }
 
-/// Iterate over program headers described by a dl_phdr_info
-struct PhdrIter<'a> {
-    info: &'a libc::dl_phdr_info,
-    i: u16,
+/// Iterate over the notes of a [`LoadedObject`]
+///
+/// Created by [`LoadedObject::notes()`]. A malformed note ends the iteration of its segment (and
+/// a segment outside of the loaded segments is skipped), but the notes of the remaining segments
+/// are still returned.
+pub struct Notes {
+    object: LoadedObject,
+    phdrs: core::slice::Iter<'static, ElfPhdr>,
+    notes: Option<NoteIter<'static>>,
 }
 
-impl<'a> Iterator for PhdrIter<'a> {
-    type Item = &'a libc::Elf64_Phdr;
+impl Iterator for Notes {
+    type Item = Result<Note<'static>, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.i >= self.info.dlpi_phnum {
-            return None;
+        loop {
+            if let Some(note) = self.notes.as_mut().and_then(Iterator::next) {
+                return Some(note.map_err(Error::InvalidNote));
+            }
+            self.notes = None;
+            match self.object.note_segment(self.phdrs.next()?) {
+                Some(Ok(notes)) => self.notes = Some(notes),
+                Some(Err(e)) => return Some(Err(e)),
+                None => {}
+            }
         }
+    }
+}
 
-        let phdr = unsafe { &*self.info.dlpi_phdr.add(self.i as usize) };
-        self.i += 1;
-        Some(phdr)
+/// Number of objects [`LoadedObjects`] collects from each walk of the loaded objects
+// small in tests, so that they cross batches
+const BATCH: usize = if cfg!(test) { 2 } else { 32 };
+
+/// Iterate over every object loaded in the current process
+///
+/// Created by [`loaded_objects()`].
+#[derive(Debug, Clone)]
+pub struct LoadedObjects {
+    /// Index of the first object in `batch`
+    start: usize,
+    batch: [Option<LoadedObject>; BATCH],
+    len: usize,
+    pos: usize,
+    /// The last walk reached the end of the list
+    done: bool,
+}
+
+impl LoadedObjects {
+    /// Collect the batch of objects following the current one
+    fn refill(&mut self) {
+        // `dl_iterate_phdr()` only offers a callback interface, so each walk of the list collects
+        // a batch of objects, which keeps us from needing an allocation to store the whole list.
+        // Most processes have fewer objects than fit in one batch, so only walk the list once.
+        self.start += self.len;
+        let (start, batch) = (self.start, &mut self.batch);
+        let mut i = 0;
+        let mut len = 0;
+        object_map(|obj| {
+            if i >= start {
+                batch[len] = Some(obj);
+                len += 1;
+            }
+            i += 1;
+            (len == BATCH) as libc::c_int
+        });
+        self.len = len;
+        self.pos = 0;
+        self.done = len < BATCH;
     }
 }
 
-impl<'a> From<&'a libc::dl_phdr_info> for PhdrIter<'a> {
-    fn from(info: &'a libc::dl_phdr_info) -> Self {
-        PhdrIter { info, i: 0 }
+impl Iterator for LoadedObjects {
+    type Item = LoadedObject;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos == self.len {
+            if self.done {
+                return None;
+            }
+            self.refill();
+        }
+        let obj = self.batch[..self.len].get(self.pos).copied().flatten()?;
+        self.pos += 1;
+        Some(obj)
     }
 }
 
-/// Iterate over notes stored in a PT_NOTE program section
-#[derive(Debug)]
-struct NoteIter<'a> {
-    segment: &'a [u8],
+/// Iterate over all objects loaded into the current process
+///
+/// This includes the main executable, all shared libraries, the dynamic loader, and the vDSO.
+///
+/// Objects loaded or unloaded (by `dlopen()`/`dlclose()`) while iterating may be skipped or
+/// reported twice.
+pub fn loaded_objects() -> LoadedObjects {
+    LoadedObjects {
+        start: 0,
+        batch: [None; BATCH],
+        len: 0,
+        pos: 0,
+        done: false,
+    }
 }
 
-impl<'a> NoteIter<'a> {
-    fn new(info: &'a libc::dl_phdr_info, phdr: &'a ElfPhdr) -> Option<Self> {
-        // NOTE: each dl_phdr_info describes multiple program segments. In this iterator, we're
-        // only examining one of them.
-        //
-        // We'll also need to iterate over program segments to pick out the PT_NOTE one we need.
+/// Find the loaded object with a `PT_LOAD` segment containing `addr`
+pub fn object_for_addr(addr: *const c_void) -> Option<LoadedObject> {
+    let addr = addr as usize;
+    let mut res = None;
+    object_map(|obj| {
+        if obj.contains(addr) {
+            res = Some(obj);
+            1
+        } else {
+            0
+        }
+    });
 
-        if phdr.p_type != libc::PT_NOTE {
+    res
+}
+
+/// Find the build-id of the loaded object containing `addr`, along with the offset of `addr` from
+/// the object's load bias
+///
+/// The offset is the address as it would appear in the object's own program headers and symbol
+/// table, which makes the pair suitable for symbolizing `addr` offline.
+pub fn build_id_for_addr(addr: *const c_void) -> Option<(&'static [u8], usize)> {
+    let obj = object_for_addr(addr)?;
+    let offset = (addr as usize).wrapping_sub(obj.load_bias());
+    match obj.build_id() {
+        Some(id) => Some((id, offset)),
+        None => {
+            debug!(
+                "object {:?} containing {:?} has no build-id",
+                obj.name(),
+                addr
+            );
             None
-        } else {
-            let segment_base = (info.dlpi_addr + phdr.p_vaddr) as *const u8;
-            let segment = unsafe {
-                // FIXME: consider p_memsz vs p_filesz question here.
-                // llvm appears to use filesz
-                core::slice::from_raw_parts(segment_base, phdr.p_filesz as usize)
-            };
-            Some(NoteIter { segment })
         }
     }
 }
 
-impl<'a> Iterator for NoteIter<'a> {
-    type Item = Result<&'a Note, NoteError>;
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.segment.is_empty() {
-            return None;
-        }
+/// Find the loaded object that contains our own code (the executable or shared library this crate
+/// is linked into)
+pub fn current_object() -> Option<LoadedObject> {
+    find_current().ok()
+}
 
-        let (n, r) = match Note::from_bytes(self.segment) {
-            Err(e) => return Some(Err(e)),
-            Ok(v) => v,
-        };
+/// An address in our own code
+fn own_code() -> *const c_void {
+    current_object as *const c_void
+}
+
+fn find_current() -> Result<LoadedObject, Error> {
+    let mut res = loader_object();
 
-        self.segment = r;
+    #[cfg(all(target_os = "linux", any(target_env = "gnu", target_env = "musl")))]
+    if res.is_err() {
+        res = auxv_object();
+    }
 
-        Some(Ok(n))
+    #[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+    if res.is_err() {
+        res = maps_object();
     }
+
+    res
 }
 
-pub fn build_id() -> Option<&'static [u8]> {
-    // find the shared object that contains our own `build_id()` fn (this fn itself)
-    let data = {
-        let mut data = MaybeUninit::uninit();
-        let addr = build_id as *const libc::c_void;
-        if unsafe { libc::dladdr(addr, data.as_mut_ptr()) } == 0 {
-            // TODO: consider if we have fallback options here
-            error!("dladdr failed to find our own symbol");
-            return None;
+/// Find the object with a segment containing our own code, with `dl_iterate_phdr()`
+///
+/// Unlike comparing the start of an object's first segment to `dladdr()`'s `dli_fbase`, this
+/// doesn't depend on how the linker laid out the segments, or on `dladdr()` being available.
+pub(crate) fn loader_object() -> Result<LoadedObject, Error> {
+    object_for_addr(own_code()).ok_or_else(|| {
+        debug!("no loaded object contains our own code at {:?}", own_code());
+        Error::ObjectNotFound
+    })
+}
+
+/// Find the main executable through the auxiliary vector, if it contains our own code
+#[cfg(all(target_os = "linux", any(target_env = "gnu", target_env = "musl")))]
+pub(crate) fn auxv_object() -> Result<LoadedObject, Error> {
+    match main_executable() {
+        Ok(obj) if obj.contains(own_code() as usize) => Ok(obj),
+        Ok(_) => {
+            debug!("the main executable does not contain our own code");
+            Err(Error::ObjectNotFound)
+        }
+        Err(e) => {
+            debug!("could not locate the main executable: {}", e);
+            Err(e)
         }
+    }
+}
+
+/// Find the object containing our own code through `/proc/self/maps`
+#[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+pub(crate) fn maps_object() -> Result<LoadedObject, Error> {
+    mapped_object(own_code() as usize).inspect_err(|e| {
+        debug!("could not locate our own code in /proc/self/maps: {}", e);
+    })
+}
 
-        unsafe { data.assume_init() }
+/// Describe the main executable, from the program headers the kernel records in the auxiliary
+/// vector
+///
+/// The load bias comes from the `PT_PHDR` segment. Without one (as in some static executables),
+/// the executable is assumed to be loaded at the addresses it was linked for.
+#[cfg(all(target_os = "linux", any(target_env = "gnu", target_env = "musl")))]
+fn main_executable() -> Result<LoadedObject, Error> {
+    let (phdr, phnum) = unsafe {
+        (
+            libc::getauxval(libc::AT_PHDR) as usize,
+            libc::getauxval(libc::AT_PHNUM),
+        )
     };
+    if phdr == 0 {
+        return Err(Error::NotFound);
+    }
+    let obj = LoadedObject {
+        addr: 0,
+        name: core::ptr::null(),
+        phdr: phdr as *const ElfPhdr,
+        phnum: u16::try_from(phnum).map_err(|_| Error::Malformed("AT_PHNUM is too large"))?,
+    }
+    .validate()?;
 
-    let mut res = None;
-    // FIXME: we probably should avoid ignoring `size` here so we can bounds check our
-    // accesses. Basically need to treat this data as a big array we happen to have pointers
-    // into, and convert those pointers to offsets.
-    object_map(|info, _size| {
-        let mut map_start = None;
-
-        for phdr in PhdrIter::from(info) {
-            // FIXME: unclear why the first PT_LOAD is the right thing to use here
-            if phdr.p_type == libc::PT_LOAD {
-                map_start = Some(info.dlpi_addr + phdr.p_vaddr);
-                break;
-            }
-        }
+    let addr = match obj.phdrs().iter().find(|p| p.p_type == libc::PT_PHDR) {
+        Some(p) => phdr.wrapping_sub(p.p_vaddr as usize),
+        None => 0,
+    };
+    LoadedObject { addr, ..obj }.validate()
+}
 
-        let map_start = match map_start {
-            Some(v) => v,
-            None => {
-                debug!(
-                    "no PT_LOAD segment found in object {:?}, skipping",
-                    info.dlpi_name
-                );
-                return 0;
-            }
-        };
+/// Find the object containing `addr` through `/proc/self/maps`, and describe it from the ELF header
+/// at the start of its first mapping
+///
+/// The dynamic loader's name for the object isn't available this way, so it has none.
+#[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+fn mapped_object(addr: usize) -> Result<LoadedObject, Error> {
+    let maps = crate::process::maps(std::process::id())?;
+    let i = maps
+        .iter()
+        .position(|m| m.range.contains(&(addr as u64)))
+        .ok_or(Error::ObjectNotFound)?;
+    let ours = &maps[i];
+    // the mappings of a file are in order of address, starting with the one containing its header
+    let header = maps[..=i]
+        .iter()
+        .rev()
+        .find(|m| m.offset == 0 && m.inode == ours.inode && m.path == ours.path)
+        .ok_or(Error::ObjectNotFound)?;
+    if !header.perms.starts_with('r') {
+        return Err(Error::Malformed("the ELF header is not readable"));
+    }
 
-        // check if this phdr (map_start) is the one that contains our fn (data.dli_fbase)
-        if map_start != data.dli_fbase as u64 {
-            debug!(
-                "map_start ({:?}) != data.dli_fbase ({:?}), skipping",
-                map_start, data.dli_fbase
-            );
-            return 0;
-        }
+    let range = &header.range;
+    let obj = unsafe {
+        LoadedObject::from_header(range.start as usize, (range.end - range.start) as usize)?
+    };
+    if !obj.contains(addr) {
+        return Err(Error::ObjectNotFound);
+    }
+    Ok(obj)
+}
 
-        'phdr: for phdr in PhdrIter::from(info) {
-            let ni = match NoteIter::new(info, phdr) {
-                Some(v) => v,
-                None => continue,
-            };
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batches() {
+        let mut n = 0;
+        object_map(|_| {
+            n += 1;
+            0
+        });
+        assert!(n > BATCH);
+        assert_eq!(loaded_objects().count(), n);
+        let mut objs = loaded_objects();
+        assert!(objs.all(|o| o.segments().next().is_some()));
+        assert!(objs.next().is_none());
+    }
 
-            // iterate over notes
-            for note in ni {
-                let note = match note {
-                    Err(e) => {
-                        warn!("note program segment had invalid note {}", e);
-                        continue 'phdr;
-                    }
-                    Ok(v) => v,
-                };
-                if note.type_() == NT_GNU_BUILD_ID
-                    && !note.desc().is_empty()
-                    && note.name() == b"GNU\0"
-                {
-                    res = Some(note.desc());
-                    break 'phdr;
-                }
-            }
+    const fn phdr(p_type: u32, p_vaddr: usize, p_memsz: usize) -> ElfPhdr {
+        ElfPhdr {
+            p_type,
+            p_flags: 0,
+            p_offset: 0,
+            p_vaddr: p_vaddr as _,
+            p_paddr: 0,
+            p_filesz: p_memsz as _,
+            p_memsz: p_memsz as _,
+            p_align: 0x1000,
         }
+    }
 
-        0
-    });
+    fn object(addr: usize, phdrs: &'static [ElfPhdr]) -> LoadedObject {
+        LoadedObject {
+            addr,
+            name: core::ptr::null(),
+            phdr: phdrs.as_ptr(),
+            phnum: phdrs.len() as u16,
+        }
+        .validate()
+        .unwrap()
+    }
 
-    res
+    #[test]
+    fn separate_code() {
+        // `ld -z separate-code`: the headers are in a read-only segment of their own, with the
+        // code in the next page
+        static PHDRS: [ElfPhdr; 5] = [
+            phdr(libc::PT_PHDR, 0x40, 0x2d8),
+            phdr(libc::PT_LOAD, 0, 0x628),
+            phdr(libc::PT_LOAD, 0x1000, 0x175),
+            phdr(libc::PT_LOAD, 0x2000, 0xf4),
+            phdr(libc::PT_LOAD, 0x3df0, 0x220),
+        ];
+        let obj = object(0x5655_4000, &PHDRS);
+        assert!(obj.contains(0x5655_5100));
+        assert!(obj.contains(0x5655_4000));
+        assert!(!obj.contains(0x5655_4800));
+        assert!(!obj.contains(0x5655_8010));
+        assert_eq!(obj.segments().count(), 4);
+    }
+
+    #[test]
+    fn lld_layout() {
+        // lld (and mold) put the code at the next page boundary in memory, but immediately after
+        // the read-only data in the file, so segments don't start at page boundaries
+        static PHDRS: [ElfPhdr; 4] = [
+            phdr(libc::PT_LOAD, 0, 0x5d4),
+            phdr(libc::PT_LOAD, 0x15e0, 0x130),
+            phdr(libc::PT_NOTE, 0x2a8, 0x24),
+            phdr(libc::PT_LOAD, 0x2710, 0x180),
+        ];
+        let obj = object(0x7000_0000, &PHDRS);
+        assert!(obj.contains(0x7000_1600));
+        assert!(!obj.contains(0x7000_1000));
+        assert!(obj.contains(0x7000_02a8));
+        assert!(obj.segments().eq([
+            0x7000_0000..0x7000_05d4,
+            0x7000_15e0..0x7000_1710,
+            0x7000_2710..0x7000_2890
+        ]));
+    }
+
+    /// A build-id note, in native byte order
+    static NOTE: [u32; 6] = [
+        4,
+        8,
+        3,
+        u32::from_ne_bytes(*b"GNU\0"),
+        0x0123_4567,
+        0x89ab_cdef,
+    ];
+
+    #[test]
+    fn notes_in_memory() {
+        static PHDRS: [ElfPhdr; 2] = [phdr(libc::PT_LOAD, 0, 24), phdr(libc::PT_NOTE, 0, 24)];
+        let obj = object(NOTE.as_ptr() as usize, &PHDRS);
+        let id = obj.try_build_id().unwrap();
+        assert_eq!(id.as_ptr(), NOTE[4..].as_ptr().cast());
+        assert_eq!(obj.notes().count(), 1);
+
+        // a note segment that isn't (entirely) loaded is never read
+        static OUTSIDE: [ElfPhdr; 2] = [phdr(libc::PT_LOAD, 0, 20), phdr(libc::PT_NOTE, 0, 24)];
+        let obj = object(NOTE.as_ptr() as usize, &OUTSIDE);
+        assert!(matches!(obj.try_build_id(), Err(Error::Malformed(_))));
+        assert!(matches!(obj.notes().next(), Some(Err(Error::Malformed(_)))));
+    }
+
+    #[cfg(all(target_os = "linux", any(target_env = "gnu", target_env = "musl")))]
+    #[test]
+    fn auxv() {
+        let addr = own_code();
+        let found = object_for_addr(addr).unwrap();
+        let obj = main_executable().unwrap();
+        assert!(obj.contains(addr as usize));
+        assert_eq!(obj.load_bias(), found.load_bias());
+        assert_eq!(obj.build_id(), found.build_id());
+    }
+
+    #[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+    #[test]
+    fn maps() {
+        let addr = own_code();
+        let found = object_for_addr(addr).unwrap();
+        let obj = mapped_object(addr as usize).unwrap();
+        assert_eq!(obj.load_bias(), found.load_bias());
+        assert_eq!(obj.build_id(), found.build_id());
+        assert!(obj.segments().eq(found.segments()));
+    }
+
+    #[test]
+    fn invalid_info() {
+        let mut info: libc::dl_phdr_info = unsafe { mem::zeroed() };
+        let size = mem::size_of_val(&info);
+        let obj = unsafe { LoadedObject::from_info(&info, size) }.unwrap();
+        assert_eq!(obj.segments().count(), 0);
+        assert!(matches!(
+            unsafe { LoadedObject::from_info(&info, 8) },
+            Err(Error::Malformed(_))
+        ));
+
+        // program headers without a table
+        info.dlpi_phnum = 1;
+        assert!(matches!(
+            unsafe { LoadedObject::from_info(&info, size) },
+            Err(Error::Malformed(_))
+        ));
+
+        // a segment with more file content than memory
+        let mut load = phdr(libc::PT_LOAD, 0, 0x100);
+        load.p_filesz = 0x200;
+        info.dlpi_phdr = &load;
+        assert!(matches!(
+            unsafe { LoadedObject::from_info(&info, size) },
+            Err(Error::Malformed(_))
+        ));
+    }
 }
@@ -0,0 +1,42 @@
+/// Byte order of the fields in an object file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+impl Endian {
+    /// Byte order of the target we're running on
+    #[cfg(target_endian = "little")]
+    pub const NATIVE: Endian = Endian::Little;
+    /// Byte order of the target we're running on
+    #[cfg(target_endian = "big")]
+    pub const NATIVE: Endian = Endian::Big;
+
+    /// Read a `u16` from the start of `data`. Panics if `data` is too short.
+    pub(crate) fn u16(self, data: &[u8]) -> u16 {
+        let b = data[..2].try_into().unwrap();
+        match self {
+            Self::Little => u16::from_le_bytes(b),
+            Self::Big => u16::from_be_bytes(b),
+        }
+    }
+
+    /// Read a `u32` from the start of `data`. Panics if `data` is too short.
+    pub(crate) fn u32(self, data: &[u8]) -> u32 {
+        let b = data[..4].try_into().unwrap();
+        match self {
+            Self::Little => u32::from_le_bytes(b),
+            Self::Big => u32::from_be_bytes(b),
+        }
+    }
+
+    /// Read a `u64` from the start of `data`. Panics if `data` is too short.
+    pub(crate) fn u64(self, data: &[u8]) -> u64 {
+        let b = data[..8].try_into().unwrap();
+        match self {
+            Self::Little => u64::from_le_bytes(b),
+            Self::Big => u64::from_be_bytes(b),
+        }
+    }
+}
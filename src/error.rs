@@ -0,0 +1,155 @@
+use crate::NoteError;
+use core::fmt;
+
+/// Reasons a build-id could not be located
+///
+/// Returned by [`try_build_id()`](crate::try_build_id). Apart from `Io`, which only exists with
+/// the `std` feature, all variants exist on every platform so that matching on them is portable,
+/// even though most can only occur with particular lookup methods.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The object was examined successfully, but contains no build-id
+    ///
+    /// For ELF objects, this usually means the linker was run without `--build-id`.
+    NotFound,
+
+    /// The loaded object containing our own code could not be located
+    ObjectNotFound,
+
+    /// A note that is part of the object is malformed
+    InvalidNote(NoteError),
+
+    /// The PE image has no optional header
+    NoOptionalHeader,
+
+    /// The PE image has no debug directory
+    NoDebugDirectory,
+
+    /// The PE debug directory entry is not CodeView (`IMAGE_DEBUG_TYPE_CODEVIEW`). Contains the
+    /// type that was found.
+    UnexpectedDebugType(u32),
+
+    /// The CodeView record does not have the `RSDS` (PDB 7.0) signature. Contains the signature
+    /// that was found.
+    UnexpectedCodeViewSignature(u32),
+
+    /// The `build_id__get` function provided for `buildid-custom-inject` returned an error code
+    Custom(i32),
+
+    /// The data is not in the expected object file format (the magic number did not match)
+    UnrecognizedFormat,
+
+    /// The object file ends before a structure it refers to
+    Truncated,
+
+    /// The object file is inconsistent or uses an unsupported encoding. Contains a description of
+    /// the problem.
+    Malformed(&'static str),
+
+    /// The file is a universal binary containing more than one architecture, so there is no
+    /// single build-id to return. Use [`mach_file`](crate::mach_file) to examine each one.
+    MultipleArchitectures,
+
+    /// The wasm module does not contain the static reserved for its build-id, so it doesn't use
+    /// this crate's runtime lookup. See [`wasm_file::embed_build_id()`](crate::wasm_file::embed_build_id).
+    MissingSlot,
+
+    /// An object and a candidate debug file don't belong together. See
+    /// [`debug_file::verify()`](crate::debug_file::verify).
+    Mismatch(crate::debug_file::Mismatch),
+
+    /// The build-id doesn't match the hash of the file's content, so the file was modified after
+    /// linking (or the build-id was not generated from the content). See
+    /// [`recompute::verify()`](crate::recompute::verify).
+    HashMismatch,
+
+    /// A replacement build-id is not the same length as the build-id stored in the file. See
+    /// [`elf_file::set_build_id()`](crate::elf_file::set_build_id).
+    LengthMismatch { stored: usize, new: usize },
+
+    /// The file already has a build-id, so one can't be added. See `inject::add_build_id()`.
+    BuildIdPresent,
+
+    /// There is no room in the file for a new note. See the `inject` module.
+    NoSpace,
+
+    /// The core dump does not include the memory holding the object's headers or notes. Which
+    /// memory the kernel dumps is controlled by `/proc/<pid>/coredump_filter`. See
+    /// [`core_file`](crate::core_file).
+    NotCaptured,
+
+    /// Reading the object file failed
+    #[cfg(feature = "std")]
+    Io(std::io::ErrorKind),
+
+    /// The build-id is longer than [`BuildId::MAX_LEN`](crate::BuildId::MAX_LEN). Contains the
+    /// length of the build-id.
+    TooLong(usize),
+
+    /// The lookup method isn't supported on this platform, or its feature isn't enabled. See
+    /// [`Method`](crate::Method).
+    Unavailable,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "object does not contain a build-id"),
+            Self::ObjectNotFound => write!(f, "could not locate the object containing our code"),
+            Self::InvalidNote(e) => write!(f, "invalid note: {}", e),
+            Self::NoOptionalHeader => write!(f, "no optional header found"),
+            Self::NoDebugDirectory => write!(f, "no debug directory found"),
+            Self::UnexpectedDebugType(t) => write!(f, "wrong debug directory type {:#x}", t),
+            Self::UnexpectedCodeViewSignature(s) => {
+                write!(f, "unexpected CodeView signature {:#x}", s)
+            }
+            Self::Custom(r) => write!(f, "build_id__get returned error: {}", r),
+            Self::UnrecognizedFormat => write!(f, "unrecognized object file format"),
+            Self::Truncated => write!(f, "object file is truncated"),
+            Self::Malformed(m) => write!(f, "malformed object file: {}", m),
+            Self::MultipleArchitectures => {
+                write!(f, "universal binary contains more than one architecture")
+            }
+            Self::MissingSlot => write!(f, "wasm module has no space reserved for a build-id"),
+            Self::Mismatch(m) => write!(f, "debug file does not match: {}", m),
+            Self::HashMismatch => write!(f, "build-id does not match the file's content"),
+            Self::BuildIdPresent => write!(f, "object already has a build-id"),
+            Self::NoSpace => write!(f, "no room for a new note in the object"),
+            Self::NotCaptured => write!(f, "core dump does not include the object's headers"),
+            Self::LengthMismatch { stored, new } => write!(
+                f,
+                "new build-id is {} bytes, but the stored build-id is {} bytes",
+                new, stored
+            ),
+            #[cfg(feature = "std")]
+            Self::Io(k) => write!(f, "reading object file failed: {}", k),
+            Self::TooLong(l) => write!(
+                f,
+                "build-id is {} bytes, but at most {} are supported",
+                l,
+                crate::BuildId::MAX_LEN
+            ),
+            Self::Unavailable => write!(f, "lookup method is not available in this build"),
+        }
+    }
+}
+
+impl From<NoteError> for Error {
+    fn from(e: NoteError) -> Self {
+        Self::InvalidNote(e)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => Self::Truncated,
+            k => Self::Io(k),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
@@ -0,0 +1,362 @@
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::str::FromStr;
+
+/// Where a [`BuildId`] came from
+///
+/// The kind determines how the bytes are rendered as a UUID/GUID (see [`BuildId::uuid()`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum Kind {
+    /// Descriptor of an ELF `NT_GNU_BUILD_ID` note. Usually a 20 byte (sha1), 16 byte (md5,
+    /// uuid), or 8 byte (lld's "fast") hash.
+    Gnu,
+    /// Mach-O `LC_UUID`. 16 bytes, stored in the order they are printed.
+    MachUuid,
+    /// GUID from a PE CodeView (`RSDS`) debug record. 16 bytes, with the first 3 fields stored
+    /// little-endian.
+    CodeView,
+    /// Content of a WebAssembly `build_id` custom section. `wasm-ld` uses the same hashes as for
+    /// [`Kind::Gnu`].
+    Wasm,
+    /// Origin is not known (for example, the build-id was parsed from a string)
+    Unknown,
+}
+
+/// A build-id (or platform equivalent), along with the kind of value it is
+///
+/// `BuildId` stores its bytes inline (it does not allocate), and can hold up to
+/// [`BuildId::MAX_LEN`] bytes.
+///
+/// Comparison, ordering, and hashing only consider the bytes, not the [`Kind`], so a `BuildId`
+/// parsed from a string compares equal to one read from an object with the same bytes. This also
+/// allows looking up a `BuildId` keyed map using a `&[u8]`.
+///
+/// The `Display` and `LowerHex` impls emit the bytes in order as lowercase hex (the format used by
+/// `gdb`, `file`, and the `.build-id` debug directory), and `UpperHex` emits uppercase hex.
+#[derive(Clone, Copy)]
+pub struct BuildId {
+    bytes: [u8; BuildId::MAX_LEN],
+    len: u8,
+    kind: Kind,
+}
+
+impl BuildId {
+    /// The largest build-id that can be stored
+    ///
+    /// This is larger than any hash emitted by common linkers, but `--build-id=0x...` allows
+    /// arbitrarily long values.
+    pub const MAX_LEN: usize = 64;
+
+    /// Create a `BuildId` by copying `bytes`
+    ///
+    /// Returns `None` if `bytes` is longer than [`BuildId::MAX_LEN`].
+    pub fn new(kind: Kind, bytes: &[u8]) -> Option<Self> {
+        if bytes.len() > Self::MAX_LEN {
+            return None;
+        }
+
+        let mut b = [0u8; Self::MAX_LEN];
+        b[..bytes.len()].copy_from_slice(bytes);
+        Some(BuildId {
+            bytes: b,
+            len: bytes.len() as u8,
+            kind,
+        })
+    }
+
+    /// The build-id of the current object, as returned by [`build_id()`](crate::build_id)
+    ///
+    /// See [`try_build_id()`](crate::try_build_id) to find out why no build-id was returned.
+    pub fn current() -> Option<Self> {
+        crate::try_build_id().ok()
+    }
+
+    /// Where the build-id came from
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Format as a UUID/GUID (`xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`)
+    ///
+    /// Returns `None` unless the build-id is 16 bytes long. For [`Kind::CodeView`], the first 3
+    /// fields are byte swapped (as the GUID is stored little-endian), so the output matches what
+    /// Windows tools print. All other kinds are printed in byte order.
+    pub fn uuid(&self) -> Option<Uuid<'_>> {
+        let bytes = self.as_bytes().try_into().ok()?;
+        Some(Uuid {
+            bytes,
+            mixed_endian: self.kind == Kind::CodeView,
+        })
+    }
+}
+
+impl AsRef<[u8]> for BuildId {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl Borrow<[u8]> for BuildId {
+    fn borrow(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl PartialEq for BuildId {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl Eq for BuildId {}
+
+impl PartialEq<[u8]> for BuildId {
+    fn eq(&self, other: &[u8]) -> bool {
+        self.as_bytes() == other
+    }
+}
+
+impl PartialEq<&[u8]> for BuildId {
+    fn eq(&self, other: &&[u8]) -> bool {
+        self.as_bytes() == *other
+    }
+}
+
+impl PartialOrd for BuildId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BuildId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_bytes().cmp(other.as_bytes())
+    }
+}
+
+impl Hash for BuildId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_bytes().hash(state)
+    }
+}
+
+impl fmt::Debug for BuildId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BuildId({:?}, {})", self.kind, self)
+    }
+}
+
+impl fmt::Display for BuildId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(self, f)
+    }
+}
+
+impl fmt::LowerHex for BuildId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in self.as_bytes() {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::UpperHex for BuildId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in self.as_bytes() {
+            write!(f, "{:02X}", b)?;
+        }
+        Ok(())
+    }
+}
+
+/// Error returned when parsing a [`BuildId`] from a hex string fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    /// The string contains a character that isn't a hex digit
+    InvalidDigit { index: usize },
+    /// The string has an odd number of hex digits
+    OddLength,
+    /// The string encodes more than [`BuildId::MAX_LEN`] bytes
+    TooLong,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidDigit { index } => write!(f, "invalid hex digit at index {}", index),
+            Self::OddLength => write!(f, "odd number of hex digits"),
+            Self::TooLong => write!(
+                f,
+                "more than {} bytes of hex digits provided",
+                BuildId::MAX_LEN
+            ),
+        }
+    }
+}
+
+impl FromStr for BuildId {
+    type Err = ParseError;
+
+    /// Parse a string of hex digits (upper or lower case) into a `BuildId` of [`Kind::Unknown`]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.as_bytes();
+        if s.len() % 2 == 1 {
+            return Err(ParseError::OddLength);
+        }
+        if s.len() / 2 > Self::MAX_LEN {
+            return Err(ParseError::TooLong);
+        }
+
+        let digit = |index: usize| -> Result<u8, ParseError> {
+            match s[index] {
+                c @ b'0'..=b'9' => Ok(c - b'0'),
+                c @ b'a'..=b'f' => Ok(c - b'a' + 10),
+                c @ b'A'..=b'F' => Ok(c - b'A' + 10),
+                _ => Err(ParseError::InvalidDigit { index }),
+            }
+        };
+
+        let mut id = BuildId {
+            bytes: [0u8; Self::MAX_LEN],
+            len: (s.len() / 2) as u8,
+            kind: Kind::Unknown,
+        };
+        for (i, b) in id.bytes[..s.len() / 2].iter_mut().enumerate() {
+            *b = (digit(i * 2)? << 4) | digit(i * 2 + 1)?;
+        }
+
+        Ok(id)
+    }
+}
+
+/// Formats a 16 byte [`BuildId`] as a UUID/GUID
+///
+/// Created by [`BuildId::uuid()`]. `Display` and `LowerHex` use lowercase, `UpperHex` uses
+/// uppercase.
+#[derive(Debug, Clone, Copy)]
+pub struct Uuid<'a> {
+    bytes: &'a [u8; 16],
+    mixed_endian: bool,
+}
+
+impl Uuid<'_> {
+    fn write(&self, f: &mut fmt::Formatter<'_>, upper: bool) -> fmt::Result {
+        let mut b = *self.bytes;
+        if self.mixed_endian {
+            b[0..4].reverse();
+            b[4..6].reverse();
+            b[6..8].reverse();
+        }
+
+        for (i, v) in b.iter().enumerate() {
+            if matches!(i, 4 | 6 | 8 | 10) {
+                f.write_str("-")?;
+            }
+            if upper {
+                write!(f, "{:02X}", v)?;
+            } else {
+                write!(f, "{:02x}", v)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Uuid<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, false)
+    }
+}
+
+impl fmt::LowerHex for Uuid<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, false)
+    }
+}
+
+impl fmt::UpperHex for Uuid<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::format;
+
+    const BYTES: [u8; 16] = [
+        0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee,
+        0xff,
+    ];
+
+    #[test]
+    fn hex() {
+        let id = BuildId::new(Kind::Gnu, &BYTES).unwrap();
+        assert_eq!(format!("{}", id), "00112233445566778899aabbccddeeff");
+        assert_eq!(format!("{:x}", id), "00112233445566778899aabbccddeeff");
+        assert_eq!(format!("{:X}", id), "00112233445566778899AABBCCDDEEFF");
+    }
+
+    #[test]
+    fn uuid() {
+        let id = BuildId::new(Kind::MachUuid, &BYTES).unwrap();
+        assert_eq!(
+            format!("{:X}", id.uuid().unwrap()),
+            "00112233-4455-6677-8899-AABBCCDDEEFF"
+        );
+
+        let id = BuildId::new(Kind::CodeView, &BYTES).unwrap();
+        assert_eq!(
+            format!("{}", id.uuid().unwrap()),
+            "33221100-5544-7766-8899-aabbccddeeff"
+        );
+
+        let id = BuildId::new(Kind::Gnu, &BYTES[..8]).unwrap();
+        assert!(id.uuid().is_none());
+    }
+
+    #[test]
+    fn parse() {
+        let id: BuildId = "00112233445566778899AAbbccddeeff".parse().unwrap();
+        assert_eq!(id.kind(), Kind::Unknown);
+        assert_eq!(id, BuildId::new(Kind::Gnu, &BYTES).unwrap());
+        assert_eq!(id, &BYTES[..]);
+
+        assert_eq!("".parse::<BuildId>().unwrap().len(), 0);
+        assert_eq!("abc".parse::<BuildId>(), Err(ParseError::OddLength));
+        assert_eq!(
+            "0g".parse::<BuildId>(),
+            Err(ParseError::InvalidDigit { index: 1 })
+        );
+        assert_eq!(
+            format!("{:0130}", 0).parse::<BuildId>(),
+            Err(ParseError::TooLong)
+        );
+        assert!(BuildId::new(Kind::Gnu, &[0; BuildId::MAX_LEN + 1]).is_none());
+    }
+
+    #[test]
+    fn ord() {
+        let a = BuildId::new(Kind::Gnu, &[1, 2]).unwrap();
+        let b = BuildId::new(Kind::Unknown, &[1, 2, 0]).unwrap();
+        let c = BuildId::new(Kind::Gnu, &[1, 3]).unwrap();
+        assert!(a < b && b < c);
+    }
+}
@@ -0,0 +1,533 @@
+//! Add a GNU build-id note to an ELF file that was linked without one
+//!
+//! The note has to be inside a loaded segment and described by a `PT_NOTE` program header, so
+//! that the runtime lookup (and anything else using `dl_iterate_phdr()`) finds it. The program
+//! header table can't grow without moving the content that follows it, so an existing entry is
+//! reused. In order of preference, [`add_build_id()`]:
+//!
+//!  1. appends the note to an existing `PT_NOTE` segment that is followed by unused (zero) bytes,
+//!  2. moves an existing `PT_NOTE` segment (or fills a `PT_NULL` entry) into unused bytes at the
+//!     end of a `PT_LOAD` segment, such as the padding GNU ld leaves between segments, or
+//!  3. turns `PT_NULL` entries into a new `PT_LOAD` segment, holding the notes, at the end of the
+//!     file.
+//!
+//! `PT_GNU_STACK` is never reused: without it, the stack of the process may become executable.
+//! Files where none of these work (such as most output of lld) give [`Error::NoSpace`], and need
+//! to be relinked with `--build-id`.
+//!
+//! A `.note.gnu.build-id` section header is also added, for tools that examine sections. Finally,
+//! the build-id is computed from the content of the new file, like [`recompute::update()`] does.
+use crate::elf_file::{
+    slice, Class, ElfFile, FileHeader, ProgramHeader, SectionHeader, PT_LOAD, PT_NOTE, SHT_NOBITS,
+    SHT_NOTE,
+};
+use crate::note::NT_GNU_BUILD_ID;
+use crate::recompute::{self, Style};
+use crate::{Endian, Error};
+use core::ops::Range;
+use std::vec::Vec;
+
+const PT_NULL: u32 = 0;
+const PF_R: u32 = 4;
+const SHF_ALLOC: u64 = 2;
+const SHN_UNDEF: u16 = 0;
+const SHN_LORESERVE: usize = 0xff00;
+
+/// Alignment of the notes we write, unless added to a segment of 8-byte aligned notes
+const NOTE_ALIGN: u64 = 4;
+const SECTION_NAME: &[u8] = b".note.gnu.build-id\0";
+
+/// Writes fields of a particular class and byte order
+#[derive(Debug, Clone, Copy)]
+struct Writer {
+    class: Class,
+    endian: Endian,
+}
+
+impl Writer {
+    fn u16(&self, out: &mut Vec<u8>, v: u16) {
+        out.extend_from_slice(&match self.endian {
+            Endian::Little => v.to_le_bytes(),
+            Endian::Big => v.to_be_bytes(),
+        });
+    }
+
+    fn u32(&self, out: &mut Vec<u8>, v: u32) {
+        out.extend_from_slice(&match self.endian {
+            Endian::Little => v.to_le_bytes(),
+            Endian::Big => v.to_be_bytes(),
+        });
+    }
+
+    fn word(&self, out: &mut Vec<u8>, v: u64) {
+        match (self.class, self.endian) {
+            (Class::Elf32, _) => self.u32(out, v as u32),
+            (Class::Elf64, Endian::Little) => out.extend_from_slice(&v.to_le_bytes()),
+            (Class::Elf64, Endian::Big) => out.extend_from_slice(&v.to_be_bytes()),
+        }
+    }
+
+    fn phdr(&self, p: &ProgramHeader) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.u32(&mut out, p.p_type);
+        // the position of p_flags differs between the 32 and 64 bit layouts
+        if self.class == Class::Elf64 {
+            self.u32(&mut out, p.p_flags);
+        }
+        self.word(&mut out, p.p_offset);
+        self.word(&mut out, p.p_vaddr);
+        self.word(&mut out, p.p_paddr);
+        self.word(&mut out, p.p_filesz);
+        self.word(&mut out, p.p_memsz);
+        if self.class == Class::Elf32 {
+            self.u32(&mut out, p.p_flags);
+        }
+        self.word(&mut out, p.p_align);
+        out
+    }
+
+    fn shdr(&self, s: &SectionHeader) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.u32(&mut out, s.sh_name);
+        self.u32(&mut out, s.sh_type);
+        self.word(&mut out, s.sh_flags);
+        self.word(&mut out, s.sh_addr);
+        self.word(&mut out, s.sh_offset);
+        self.word(&mut out, s.sh_size);
+        self.u32(&mut out, s.sh_link);
+        self.u32(&mut out, s.sh_info);
+        self.word(&mut out, s.sh_addralign);
+        self.word(&mut out, s.sh_entsize);
+        out
+    }
+}
+
+/// The file being modified
+struct Layout<'a> {
+    data: &'a [u8],
+    header: FileHeader,
+    phdrs: Vec<ProgramHeader>,
+    sections: Vec<SectionHeader>,
+}
+
+/// Where the notes go, and the program header table that describes them
+struct Placement {
+    offset: u64,
+    vaddr: u64,
+    /// alignment of the note segment, which the new note follows
+    align: u64,
+    /// existing notes, moved to `offset` ahead of the new note
+    moved: Range<u64>,
+    phdrs: Vec<ProgramHeader>,
+}
+
+fn overlaps(a: &Range<u64>, b: &Range<u64>) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+fn seg_end(p: &ProgramHeader) -> u64 {
+    p.p_offset.saturating_add(p.p_filesz)
+}
+
+impl<'a> Layout<'a> {
+    /// Return the program headers with `phdrs[owner]` extended to cover `range` of the file, if
+    /// `range` isn't used by anything else
+    fn claim(&self, owner: usize, range: &Range<u64>) -> Option<Vec<ProgramHeader>> {
+        let load = &self.phdrs[owner];
+        if range.start < load.p_offset || range.start > seg_end(load) {
+            return None;
+        }
+
+        // only unused bytes (which linkers fill with zeros) can be taken
+        let len = self.data.len() as u64;
+        if range.start < len {
+            let used = &self.data[range.start as usize..range.end.min(len) as usize];
+            if used.iter().any(|&b| b != 0) {
+                return None;
+            }
+        }
+        let h = &self.header;
+        let tables = [
+            0..h.class.ehdr_size() as u64,
+            h.e_phoff
+                ..h.e_phoff
+                    .saturating_add((self.phdrs.len() * h.class.phdr_size()) as u64),
+            h.e_shoff
+                ..h.e_shoff
+                    .saturating_add((self.sections.len() * h.class.shdr_size()) as u64),
+        ];
+        if tables.iter().any(|t| overlaps(t, range)) {
+            return None;
+        }
+        let sections = self
+            .sections
+            .iter()
+            .skip(1)
+            .filter(|s| s.sh_type != SHT_NOBITS);
+        if sections
+            .map(|s| s.sh_offset..s.sh_offset.saturating_add(s.sh_size))
+            .any(|s| overlaps(&s, range))
+        {
+            return None;
+        }
+        let others = self.phdrs.iter().enumerate().filter(|(i, _)| *i != owner);
+        if others
+            .map(|(_, p)| p.p_offset..seg_end(p))
+            .any(|p| overlaps(&p, range))
+        {
+            return None;
+        }
+
+        let mut phdrs = self.phdrs.clone();
+        if range.end > seg_end(load) {
+            // growing the segment is only possible when its memory image matches the file (no
+            // .bss), and the memory it grows into is unused
+            if load.p_filesz != load.p_memsz {
+                return None;
+            }
+            let grow = range.end - seg_end(load);
+            let mem_end = load.p_vaddr.saturating_add(load.p_memsz);
+            let mem = mem_end..mem_end.saturating_add(grow);
+            let loads = self.phdrs.iter().filter(|p| p.p_type == PT_LOAD);
+            if loads
+                .map(|p| p.p_vaddr..p.p_vaddr.saturating_add(p.p_memsz))
+                .any(|p| overlaps(&p, &mem))
+            {
+                return None;
+            }
+            phdrs[owner].p_filesz += grow;
+            phdrs[owner].p_memsz += grow;
+        }
+        Some(phdrs)
+    }
+
+    /// The `PT_LOAD` containing the start of `range`, or ending where it starts
+    fn load_for(&self, range: &Range<u64>) -> Option<usize> {
+        let loads = || {
+            self.phdrs
+                .iter()
+                .enumerate()
+                .filter(|(_, p)| p.p_type == PT_LOAD)
+        };
+        loads()
+            .find(|(_, p)| (p.p_offset..seg_end(p)).contains(&range.start))
+            .or_else(|| loads().find(|(_, p)| seg_end(p) == range.start))
+            .map(|(i, _)| i)
+    }
+
+    /// Notes we can add to, with their alignment (4 or 8)
+    fn note_segments(&self) -> impl Iterator<Item = (usize, &ProgramHeader, u64)> + '_ {
+        self.phdrs.iter().enumerate().filter_map(|(i, p)| {
+            let align = p.p_align.max(NOTE_ALIGN);
+            let usable = p.p_type == PT_NOTE
+                && align <= 8
+                && p.p_offset % align == 0
+                && p.p_filesz % align == 0;
+            usable.then_some((i, p, align))
+        })
+    }
+
+    /// The note segment to move, or a `PT_NULL` slot to use from `nulls`
+    ///
+    /// Notes that another program header also describes (such as `PT_GNU_PROPERTY`) stay where
+    /// they are.
+    fn note_slot(
+        &self,
+        mut nulls: impl Iterator<Item = usize>,
+    ) -> Option<(usize, Range<u64>, u64)> {
+        let shared = |i, note: &ProgramHeader| {
+            let others = self.phdrs.iter().enumerate().filter(|&(j, _)| j != i);
+            others
+                .filter(|(_, p)| p.p_type != PT_LOAD)
+                .any(|(_, p)| p.p_offset == note.p_offset)
+        };
+        match self.note_segments().find(|&(i, note, _)| !shared(i, note)) {
+            Some((i, note, align)) => Some((i, note.p_offset..seg_end(note), align)),
+            None => Some((nulls.next()?, 0..0, NOTE_ALIGN)),
+        }
+    }
+
+    fn nulls(&self) -> impl Iterator<Item = usize> + '_ {
+        self.phdrs
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.p_type == PT_NULL)
+            .map(|(i, _)| i)
+    }
+
+    /// Append to an existing note segment, in place
+    fn extend_note(&self, id_len: usize) -> Result<Option<Placement>, Error> {
+        for (i, note, align) in self.note_segments() {
+            let len = note_len(id_len, align);
+            let range = seg_end(note)..seg_end(note).checked_add(len).ok_or(Error::Truncated)?;
+            let Some(mut phdrs) = self.load_for(&range).and_then(|l| self.claim(l, &range)) else {
+                continue;
+            };
+            let vaddr = note
+                .p_vaddr
+                .checked_add(note.p_filesz)
+                .ok_or(Error::Malformed("PT_NOTE segment wraps around"))?;
+            phdrs[i].p_filesz += len;
+            phdrs[i].p_memsz = note
+                .p_memsz
+                .checked_add(len)
+                .ok_or(Error::Malformed("PT_NOTE segment wraps around"))?;
+            return Ok(Some(Placement {
+                offset: range.start,
+                vaddr,
+                align,
+                moved: 0..0,
+                phdrs,
+            }));
+        }
+        Ok(None)
+    }
+
+    /// Move a note segment (or fill a `PT_NULL` slot) to unused space at the end of a loaded
+    /// segment
+    fn move_note(&self, id_len: usize) -> Result<Option<Placement>, Error> {
+        let Some((slot, moved, align)) = self.note_slot(self.nulls()) else {
+            return Ok(None);
+        };
+        let total = moved.end - moved.start + note_len(id_len, align);
+        for (owner, load) in self.phdrs.iter().enumerate() {
+            if load.p_type != PT_LOAD {
+                continue;
+            }
+            // the padding up to the alignment becomes part of the segment too
+            let start = seg_end(load)
+                .checked_next_multiple_of(align)
+                .ok_or(Error::Truncated)?;
+            let end = start.checked_add(total).ok_or(Error::Truncated)?;
+            let Some(mut phdrs) = self.claim(owner, &(seg_end(load)..end)) else {
+                continue;
+            };
+            let addr = |base: u64| {
+                base.checked_add(start - load.p_offset)
+                    .ok_or(Error::Malformed("PT_LOAD segment wraps around"))
+            };
+            let vaddr = addr(load.p_vaddr)?;
+            phdrs[slot] = ProgramHeader {
+                p_type: PT_NOTE,
+                p_flags: PF_R,
+                p_offset: start,
+                p_vaddr: vaddr,
+                p_paddr: addr(load.p_paddr)?,
+                p_filesz: total,
+                p_memsz: total,
+                p_align: align,
+            };
+            return Ok(Some(Placement {
+                offset: start,
+                vaddr,
+                align,
+                moved,
+                phdrs,
+            }));
+        }
+        Ok(None)
+    }
+
+    /// Add a new loaded segment at the end of the file, using `PT_NULL` slots
+    fn append_load(&self, id_len: usize) -> Result<Option<Placement>, Error> {
+        let mut nulls = self.nulls();
+        let Some(load_slot) = nulls.next() else {
+            return Ok(None);
+        };
+        let Some((note_slot, moved, align)) = self.note_slot(nulls) else {
+            return Ok(None);
+        };
+        let total = moved.end - moved.start + note_len(id_len, align);
+
+        let loads = self.phdrs.iter().filter(|p| p.p_type == PT_LOAD);
+        let Some(page) = loads.clone().map(|p| p.p_align).max() else {
+            return Ok(None);
+        };
+        let page = page.max(0x1000);
+        let Some(mem_end) = loads.map(|p| p.p_vaddr.saturating_add(p.p_memsz)).max() else {
+            return Ok(None);
+        };
+        let offset = (self.data.len() as u64).next_multiple_of(align);
+        let vaddr = mem_end
+            .checked_next_multiple_of(page)
+            .and_then(|v| v.checked_add(offset % page))
+            .ok_or(Error::Malformed("PT_LOAD segment alignment is too large"))?;
+        let load = ProgramHeader {
+            p_type: PT_LOAD,
+            p_flags: PF_R,
+            p_offset: offset,
+            p_vaddr: vaddr,
+            p_paddr: vaddr,
+            p_filesz: total,
+            p_memsz: total,
+            p_align: page,
+        };
+        let note = ProgramHeader {
+            p_type: PT_NOTE,
+            p_align: align,
+            ..load
+        };
+
+        // loaders expect PT_LOAD entries in order of address, so the new one goes after the
+        // others
+        let mut phdrs = self.phdrs.clone();
+        phdrs[note_slot] = note;
+        phdrs.remove(load_slot);
+        let Some(last_load) = phdrs.iter().rposition(|p| p.p_type == PT_LOAD) else {
+            return Ok(None);
+        };
+        phdrs.insert(last_load + 1, load);
+        Ok(Some(Placement {
+            offset,
+            vaddr,
+            align,
+            moved,
+            phdrs,
+        }))
+    }
+
+    /// Append a section header for the note, and a section name string table that includes its
+    /// name, to `out`
+    fn add_section(&self, w: Writer, out: &mut Vec<u8>, note: &SectionHeader) -> Result<(), Error> {
+        let h = &self.header;
+        let shstrndx = h.e_shstrndx as usize;
+        if self.sections.len() + 1 >= SHN_LORESERVE
+            || h.needs_section_zero()
+            || h.e_shstrndx == SHN_UNDEF
+            || shstrndx >= self.sections.len()
+        {
+            return Ok(());
+        }
+        let mut sections = self.sections.clone();
+        let Ok(names) = slice(self.data, sections[shstrndx].file_range()?) else {
+            return Ok(());
+        };
+
+        sections[shstrndx].sh_offset = out.len() as u64;
+        sections[shstrndx].sh_size = (names.len() + SECTION_NAME.len()) as u64;
+        out.extend_from_slice(names);
+        out.extend_from_slice(SECTION_NAME);
+        sections.push(SectionHeader {
+            sh_name: names.len() as u32,
+            ..*note
+        });
+
+        let shoff = (out.len() as u64).next_multiple_of(8);
+        out.resize(shoff as usize, 0);
+        for s in &sections {
+            out.extend_from_slice(&w.shdr(s));
+        }
+
+        // e_shoff and e_shnum
+        let (shoff_at, shnum_at) = match h.class {
+            Class::Elf32 => (32, 48),
+            Class::Elf64 => (40, 60),
+        };
+        put(out, shoff_at, |f| w.word(f, shoff));
+        put(out, shnum_at, |f| w.u16(f, sections.len() as u16));
+        Ok(())
+    }
+}
+
+/// Overwrite the bytes of `out` at `at` with a field produced by `write`
+fn put(out: &mut [u8], at: usize, write: impl FnOnce(&mut Vec<u8>)) {
+    let mut field = Vec::new();
+    write(&mut field);
+    out[at..at + field.len()].copy_from_slice(&field);
+}
+
+/// Size of a build-id note of `id_len` bytes, padded to `align`
+fn note_len(id_len: usize, align: u64) -> u64 {
+    // with a 4 byte name, the descriptor starts at offset 16 for either alignment
+    16 + (id_len as u64).next_multiple_of(align)
+}
+
+/// A build-id note with an all-zero descriptor of `id_len` bytes
+fn note(w: Writer, id_len: usize, align: u64) -> Vec<u8> {
+    let mut out = Vec::new();
+    w.u32(&mut out, 4);
+    w.u32(&mut out, id_len as u32);
+    w.u32(&mut out, NT_GNU_BUILD_ID);
+    out.extend_from_slice(b"GNU\0");
+    out.resize(note_len(id_len, align) as usize, 0);
+    out
+}
+
+/// Add a build-id note to the ELF file held in `data`, returning the new file
+///
+/// The build-id is computed from the content of the new file, the way `style` does (see
+/// [`recompute`]), so the result passes [`recompute::verify()`]. Returns
+/// [`Error::BuildIdPresent`] if the file already has a build-id, and [`Error::NoSpace`] if there
+/// is no room for the note (see the [module documentation](self)).
+pub fn add_build_id(data: &[u8], style: Style) -> Result<Vec<u8>, Error> {
+    let elf = ElfFile::parse(data)?;
+    match elf.build_id() {
+        Ok(_) => return Err(Error::BuildIdPresent),
+        Err(Error::NotFound) => {}
+        Err(e) => return Err(e),
+    }
+    let header = *elf.header();
+    let w = Writer {
+        class: header.class,
+        endian: header.endian,
+    };
+    let layout = Layout {
+        data,
+        header,
+        phdrs: elf.program_headers()?.collect::<Result<_, _>>()?,
+        sections: match elf.shnum()? {
+            0 => Vec::new(),
+            _ => elf.section_headers()?.collect::<Result<_, _>>()?,
+        },
+    };
+
+    // the note is placed relative to the existing segments, which have to be within the file
+    for p in &layout.phdrs {
+        if matches!(p.p_type, PT_LOAD | PT_NOTE) {
+            slice(data, p.file_range()?)?;
+        }
+    }
+
+    let id_len = style.id_len();
+    let place = match layout.extend_note(id_len)? {
+        Some(place) => place,
+        None => match layout.move_note(id_len)? {
+            Some(place) => place,
+            None => layout.append_load(id_len)?.ok_or(Error::NoSpace)?,
+        },
+    };
+    let note = note(w, id_len, place.align);
+
+    let mut out = data.to_vec();
+    let moved = slice(data, place.moved.clone())?.to_vec();
+    let start = place.offset as usize;
+    let note_at = start + moved.len();
+    out.resize(out.len().max(note_at + note.len()), 0);
+    out[start..note_at].copy_from_slice(&moved);
+    out[note_at..note_at + note.len()].copy_from_slice(&note);
+
+    for (i, p) in place.phdrs.iter().enumerate() {
+        let entry = header.e_phoff as usize + i * header.e_phentsize as usize;
+        put(&mut out, entry, |f| f.extend_from_slice(&w.phdr(p)));
+    }
+
+    layout.add_section(
+        w,
+        &mut out,
+        &SectionHeader {
+            sh_name: 0,
+            sh_type: SHT_NOTE,
+            sh_flags: SHF_ALLOC,
+            sh_addr: place.vaddr + moved.len() as u64,
+            sh_offset: note_at as u64,
+            sh_size: note.len() as u64,
+            sh_link: 0,
+            sh_info: 0,
+            sh_addralign: place.align,
+            sh_entsize: 0,
+        },
+    )?;
+
+    recompute::update(&mut out, style)?;
+    Ok(out)
+}
@@ -0,0 +1,103 @@
+//! Build-ids of the Linux kernel and its loadable modules
+//!
+//! The running kernel makes the notes of its image readable in `/sys/kernel/notes`, and each
+//! loaded module makes its note sections readable in `/sys/module/<name>/notes/`. These hold the
+//! notes alone, without any ELF headers, so [`notes_build_id()`] parses them directly. With the
+//! `std` feature, `build_id()`, `module_build_id()`, and `modules()` read them on Linux.
+//!
+//! The kernel image (`vmlinux`) and module files (`.ko`) on disk are ordinary ELF files, so
+//! [`elf_file`](crate::elf_file) (or `from_path()`) finds their build-ids. Compressed modules
+//! (such as `.ko.xz`) need to be decompressed first.
+use crate::note::NoteIter;
+use crate::{Endian, Error};
+#[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+use {
+    crate::{BuildId, Kind},
+    std::{format, string::String, vec::Vec},
+};
+
+/// Find the GNU build-id in `notes`, which holds a sequence of notes without any ELF headers
+///
+/// The files under `/sys` use the byte order of the running kernel, [`Endian::NATIVE`].
+pub fn notes_build_id(notes: &[u8], endian: Endian) -> Result<&[u8], Error> {
+    NoteIter::new(notes, endian)
+        .build_id()?
+        .ok_or(Error::NotFound)
+}
+
+#[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+fn read_notes(path: &str) -> Result<BuildId, Error> {
+    let notes = std::fs::read(path)?;
+    let id = notes_build_id(&notes, Endian::NATIVE)?;
+    BuildId::new(Kind::Gnu, id).ok_or(Error::TooLong(id.len()))
+}
+
+/// The build-id of the running kernel, from `/sys/kernel/notes`
+#[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+pub fn build_id() -> Result<BuildId, Error> {
+    read_notes("/sys/kernel/notes")
+}
+
+/// The build-id of the loaded kernel module `name`, from
+/// `/sys/module/<name>/notes/.note.gnu.build-id`
+///
+/// Modules built into the kernel have no notes of their own, and give an [`Error::Io`]. So do
+/// names that aren't a single path component (such as ones containing `/`), with
+/// `std::io::ErrorKind::InvalidInput`.
+#[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+pub fn module_build_id(name: &str) -> Result<BuildId, Error> {
+    if matches!(name, "" | "." | "..") || name.contains(['/', '\0']) {
+        return Err(Error::Io(std::io::ErrorKind::InvalidInput));
+    }
+    read_notes(&format!("/sys/module/{}/notes/.note.gnu.build-id", name))
+}
+
+/// A loaded kernel module, along with its build-id
+///
+/// Created by [`modules()`].
+#[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Module {
+    name: String,
+    build_id: Result<BuildId, Error>,
+}
+
+#[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+impl Module {
+    /// Name of the module, as used in `/sys/module`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The GNU build-id of the module, if it has one
+    pub fn build_id(&self) -> Option<BuildId> {
+        self.build_id.ok()
+    }
+
+    /// The GNU build-id of the module, or the reason it could not be found
+    pub fn try_build_id(&self) -> Result<BuildId, Error> {
+        self.build_id
+    }
+}
+
+/// List the loaded kernel modules, along with their build-ids, in order of name
+///
+/// Modules built into the kernel also appear in `/sys/module`, but are not included, as their
+/// build-id is the kernel's.
+#[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+pub fn modules() -> Result<Vec<Module>, Error> {
+    let mut modules = Vec::new();
+    for entry in std::fs::read_dir("/sys/module")? {
+        let entry = entry?;
+        if !entry.path().join("notes").is_dir() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        modules.push(Module {
+            build_id: module_build_id(&name),
+            name,
+        });
+    }
+    modules.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(modules)
+}
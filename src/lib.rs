@@ -7,7 +7,8 @@
 //! build-id is a value which is guaranteed to change when any of the component objects of a binary
 //! change. A change in the build-id does not guarantee that the executable or it's components are
 //! actually different. Two distinct executables may have a different build-id if they were
-//! modified after linking (for example, by `chrpath` or similar).
+//! modified after linking (for example, by `chrpath` or similar). [`recompute`] can detect such
+//! modifications.
 //!
 //! build-id is intended to be sufficient to identify the appropriate debug information to use for
 //! a given object, and is used for this purpose by `gdb` and other debuggers.
@@ -21,9 +22,69 @@
 //! external API, that call will return the build-id of the shared object/library (not the
 //! executable).
 //!
-//! By default, the `buildid` crate will pick the best build-id lookup function it can for your
-//! platform. If one is not available, it may fail to compile. If you have a custom build-id lookup
-//! mechanism you want to tell `buildid` about, enabling one of the features may help.
+//! By default, the `buildid` crate tries each build-id lookup method available on your platform,
+//! in order, until one finds a build-id. If you have a custom build-id lookup mechanism you want to
+//! tell `buildid` about, enabling one of the features may help: the methods they enable are tried
+//! instead of the default ones, which are then not linked in (so that they don't need libc).
+//! [`try_build_id_with()`] tries the methods of your choosing, and reports which one found the
+//! build-id.
+//!
+//! [`BuildId`] wraps the same value along with the kind of identifier it is, and handles
+//! formatting it as hex or as a UUID/GUID:
+//!
+//! ```
+//! if let Some(id) = buildid::BuildId::current() {
+//!     println!("{} {:?}", id, id.kind());
+//! }
+//! ```
+//!
+//! When no build-id is found, [`try_build_id()`] reports why (for example, to distinguish an
+//! object linked without a build-id from one containing malformed data) without needing a logger
+//! to be configured.
+//!
+//! # Other loaded objects
+//!
+//! On ELF platforms, [`loaded_objects()`] enumerates every object loaded into the process (the
+//! executable, shared libraries, the dynamic loader, and the vDSO), along with where each is mapped
+//! and its build-id. This is useful for recording a module table in crash reports or profiles.
+//!
+//! [`build_id_for_addr()`] finds the object containing a given address (for example, a program
+//! counter from a backtrace) and returns its build-id along with the address's offset within the
+//! object, so that the address can be symbolized offline.
+//!
+//! [`LoadedObject::notes()`] iterates over every note of an object, not just the build-id, so that
+//! other notes (including ones you embed yourself) can be read at runtime. [`current_object()`]
+//! finds the object containing this crate.
+//!
+//! # Examining files
+//!
+//! The [`elf_file`] module finds the build-id in an ELF file held in memory, on any host. It
+//! shares its note parsing ([`note`]) with the runtime lookup. It can also overwrite the build-id
+//! in place ([`elf_file::set_build_id()`]).
+//!
+//! The [`mach_file`] module does the same for the `LC_UUID` of Mach-O files, including each
+//! architecture in a universal binary.
+//!
+//! The [`pe_file`] module returns the CodeView record (GUID, age, and PDB path) of PE/COFF
+//! files, so that Windows artifacts can be indexed without Windows tools.
+//!
+//! The [`wasm_file`] module reads the `build_id` custom section of WebAssembly modules.
+//!
+//! The [`debug_file`] module locates separate debug files by build-id, using the `.build-id`
+//! directory layout that debuggers and distributions use. The [`debug_link`] module handles files
+//! that instead name their debug file with `.gnu_debuglink` (or `.gnu_debugaltlink`).
+//!
+//! The [`core_file`] module lists the files mapped into a crashed process, along with their
+//! build-ids, from its core dump. On Linux, with the `std` feature, the `process` module does the
+//! same for another running process.
+//!
+//! The [`kernel`] module finds the build-ids of the running Linux kernel and its loaded modules.
+//!
+//! The [`recompute`] module recomputes an ELF file's build-id from its content the way the linker
+//! did, to detect files that were modified after linking.
+//!
+//! With the `std` feature, the `inject` module adds a build-id note to ELF files that were linked
+//! without one.
 //!
 //! # Optional Features
 //!
@@ -40,8 +101,18 @@
 //! ldscript (linker script). See `buildid-linker-symbols` for a linker script mechanism to provide
 //! these.
 //!
-//! This method takes precedence over automatically enable build-id
-//! lookup methods, and over `buildid-section-inject`.
+//! This method is tried before `buildid-ehdr-start`. Only `buildid-custom-inject` and
+//! `buildid-section-inject` are tried before it.
+//!
+//! ## `buildid-ehdr-start`
+//!
+//! When enabled, use the `__ehdr_start` symbol, which GNU ld (bfd), gold, lld, and mold define at
+//! the ELF header of the executable or shared library being linked, to find our own program
+//! headers, and find the build-id in their `PT_NOTE` segments. No linker script is needed, and
+//! neither libc nor the dynamic loader is used, so this works for static (and static-pie)
+//! executables, and for programs that don't use libc at all.
+//!
+//! This method is tried after the other lookup features.
 //!
 //! ## `buildid-custom-inject`
 //!
@@ -50,8 +121,8 @@
 //! return `1` if a build-id is located (and modify the `build_id` and `len` arguments to point to
 //! the memory containing the build-id and to contain the number of bytes in the build-id
 //! respectively), return `0` if no build-id exists, and return a negative error code if an
-//! unexpected error occurred. This method takes precedence over all other build-id lookup methods
-//! (if enabled).
+//! unexpected error occurred. This method is tried before the other build-id lookup features (if
+//! enabled), and they are tried if it returns `0` or an error.
 //!
 //! ## `buildid-section-inject`
 //!
@@ -63,7 +134,18 @@
 //! Note that in all cases this works, `buildid-symbol-start-end` is likely to work and be more
 //! reliable.
 //!
-//! This method takes precedence over the default lookup methods if enabled.
+//! This method is tried after `buildid-custom-inject`.
+//!
+//! ## `std`
+//!
+//! Enables `from_path()`, which reads the build-id from a file without reading the entire file,
+//! and other APIs that need `std::io` or the filesystem. Unlike the other features, this one does
+//! not change how our own build-id is located.
+//!
+//! ## `cli`
+//!
+//! Builds the `buildid` command line tool (and enables `std`), which shows, sets, or adds the
+//! build-ids of files, and lists the build-ids of the objects in a core dump or a running process.
 //!
 //! ## `buildid-linker-symbols`
 //!
@@ -75,13 +157,18 @@
 //!  - On unix variants other than those with apple as the vendor, the `.note.gnu.build-id` is
 //!    used. Note that GNU LD and LLD generate different sized build-ids using different hash
 //!    functions. Unless additional features are enabled, the `.note.gnu.build-id` is located via
-//!    `dl_iterate_phdr()`.
+//!    `dl_iterate_phdr()`, in the loaded object with a `PT_LOAD` segment containing this
+//!    library's code. If that fails on Linux, the main executable's program headers are found in
+//!    the auxiliary vector (`AT_PHDR`), and, with the `std` feature, the ELF header of the mapping
+//!    containing this library's code is found through `/proc/self/maps`.
 //!  - On Apple unix variants (MacOS), the `LC_UUID` (loader command uuid) is returned directly as
 //!    a slice.
 //!  - On windows, the module is parsed for a CodeView descriptor containing a GUID (which is
 //!    returned directly as a slice). If mingw is used, the same info will appear in the `.buildid`
 //!    section, but this lookup method is not used by this library.
-//!  - On wasm, no data is provided
+//!  - On wasm, the `build_id` custom section can't be read by the module itself. Instead, run
+//!    [`wasm_file::embed_build_id()`] on the linked module to copy the build-id into a static
+//!    reserved by this crate. Until that is done, no data is provided.
 //!
 //! # Ensuring build-id is enabled
 //!
@@ -93,75 +180,154 @@
 //!    platform does not have build-id enabled though. Set `RUSTFLAGS="-Clink-args=-Wl,--build-id"`
 //!    to ensure build id is enabled for clang or gcc
 //!
+//!  - On wasm, `wasm-ld` only emits a build-id when asked. Set
+//!    `RUSTFLAGS="-Clink-arg=--build-id"`.
+//!
 //!  - MacOS appears to enable build-id (LC_UUID) by default, with no change needed.
 //!  - Windows MSVC appears to enable build-id (CodeView GUID) by default, with no change needed.
 #![no_std]
 
 #[cfg(test)]
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
-cfg_if::cfg_if! {
-    if #[cfg(all(test,
-            not(all(
-                target_family = "unix",
-                not(target_vendor = "apple"),
-            ))
-        ))] {
-        mod align;
-    }
-}
+mod align;
+mod blake3;
 
-cfg_if::cfg_if! {
-    if #[cfg(any(test,
-            all(
-                not(feature = "buildid-custom-inject"),
-                feature = "buildid-section-inject")
-            )
-        )] {
-        mod constparse;
-    }
-}
+#[cfg(any(test, feature = "buildid-section-inject"))]
+mod constparse;
 
-cfg_if::cfg_if! {
+#[path = "core-file.rs"]
+pub mod core_file;
+mod crc32;
+#[path = "debug-file.rs"]
+pub mod debug_file;
+#[path = "debug-link.rs"]
+pub mod debug_link;
+#[path = "elf-file.rs"]
+pub mod elf_file;
+mod endian;
+mod error;
+mod id;
+#[cfg(feature = "std")]
+pub mod inject;
+pub mod kernel;
+#[path = "mach-file.rs"]
+pub mod mach_file;
+mod md5;
+pub mod method;
+pub mod note;
+#[path = "pe-file.rs"]
+pub mod pe_file;
+#[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+pub mod process;
+pub mod recompute;
+mod sha1;
+#[path = "wasm-file.rs"]
+pub mod wasm_file;
+mod xxhash;
+pub use endian::Endian;
+pub use error::Error;
+pub use id::{BuildId, Kind, ParseError, Uuid};
+pub use method::{try_build_id_with, Method};
+pub use note::NoteError;
 
-    if #[cfg(feature = "buildid-custom-inject")] {
-        #[path = "custom-inject.rs"]
-        mod target;
-    } else if  #[cfg(feature = "buildid-section-inject")] {
-        #[path = "section-inject.rs"]
-        mod target;
-    } else if #[cfg(feature = "buildid-symbol-start-end")] {
-        #[path = "symbol-start-end.rs"]
-        mod target;
-    } else if #[cfg(all(
-        target_family = "unix",
-        not(target_vendor = "apple"),
-    ))] {
-        #[path = "elf.rs"]
-        mod target;
-        mod align;
-    } else if #[cfg(all(
+// The dynamic loader walk is also used to examine objects other than our own, so it is built even
+// when a different method is used to find our own build-id.
+#[cfg(all(target_family = "unix", not(target_vendor = "apple")))]
+mod elf;
+
+#[cfg(all(target_family = "unix", not(target_vendor = "apple")))]
+pub use elf::{
+    build_id_for_addr, current_object, loaded_objects, object_for_addr, LoadedObject,
+    LoadedObjects, Notes, Segments,
+};
+
+// Each lookup method is built when it is available, so that `Method` can fall back from one to
+// the next at runtime.
+#[cfg(feature = "buildid-custom-inject")]
+#[path = "custom-inject.rs"]
+mod custom_inject;
+#[cfg(feature = "buildid-ehdr-start")]
+#[path = "ehdr-start.rs"]
+mod ehdr_start;
+#[cfg(feature = "buildid-section-inject")]
+#[path = "section-inject.rs"]
+mod section_inject;
+#[cfg(feature = "buildid-symbol-start-end")]
+#[path = "symbol-start-end.rs"]
+mod symbol_start_end;
+
+cfg_if::cfg_if! {
+    if #[cfg(all(
         target_family = "unix",
         target_vendor = "apple",
     ))] {
         #[path = "mach.rs"]
-        mod target;
+        mod platform;
     } else if #[cfg(target_family = "windows")] {
         #[path = "windows.rs"]
-        mod target;
+        mod platform;
     } else if #[cfg(target_family = "wasm")] {
-        mod target {
-            pub fn build_id() -> Option<&'static [u8]> {
-                // not sure how to implement this right now. need to introspect the wasm object in some way
-                None
-            }
-        }
+        #[path = "wasm.rs"]
+        mod platform;
     }
 }
 
 /// If present, return the build-id or platform equivalent
+///
+/// See [`BuildId::current()`] for a typed version of this value, and [`try_build_id()`] to find
+/// out why no build-id was returned.
 pub fn build_id() -> Option<&'static [u8]> {
-    target::build_id()
+    match method::find_own() {
+        Ok((v, _)) => Some(v),
+        Err(Error::NotFound) => None,
+        Err(e) => {
+            log::error!("{}", e);
+            None
+        }
+    }
+}
+
+/// Read the build-id (or platform equivalent) from the object file at `path`
+///
+/// The file format is detected from its content. Only the parts of the file needed to locate the
+/// build-id are read. See [`elf_file::read_build_id()`], [`mach_file::read_uuids()`], and
+/// [`pe_file::read_build_id()`], and [`wasm_file::read_build_id()`].
+///
+/// Universal binaries containing more than one architecture result in
+/// [`Error::MultipleArchitectures`].
+#[cfg(feature = "std")]
+pub fn from_path<P: AsRef<std::path::Path>>(path: P) -> Result<BuildId, Error> {
+    use std::io::{Read, Seek};
+
+    let mut f = std::io::BufReader::new(std::fs::File::open(path)?);
+    let mut magic = std::vec::Vec::new();
+    f.by_ref().take(8).read_to_end(&mut magic)?;
+    f.rewind()?;
+
+    if mach_file::Magic::parse(&magic).is_some() {
+        match mach_file::read_uuids(&mut f)?[..] {
+            [s] => s.map(|s| s.uuid),
+            [] => Err(Error::NotFound),
+            _ => Err(Error::MultipleArchitectures),
+        }
+    } else if magic.starts_with(b"MZ") {
+        pe_file::read_build_id(&mut f)
+    } else if magic.starts_with(b"\0asm") {
+        wasm_file::read_build_id(&mut f)
+    } else {
+        elf_file::read_build_id(&mut f)
+    }
+}
+
+/// Return the build-id or platform equivalent, or the reason it could not be found
+///
+/// Each of [`Method::ALL`] is tried in turn. See [`try_build_id_with()`] to choose the methods,
+/// and find out which one found the build-id.
+pub fn try_build_id() -> Result<BuildId, Error> {
+    method::find_own().and_then(method::typed).map(|(id, _)| id)
 }
 
 #[cfg(doctest)]
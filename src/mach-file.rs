@@ -0,0 +1,446 @@
+//! Parse the `LC_UUID` out of Mach-O files held in memory
+//!
+//! This works on any host, for 32 and 64 bit Mach-O files of either byte order, and for
+//! universal ("fat") binaries containing several of them.
+//!
+//! ```no_run
+//! # fn example(data: &[u8]) -> Result<(), buildid::Error> {
+//! for slice in buildid::mach_file::uuids(data)? {
+//!     let slice = slice?;
+//!     println!("{:#x} {}", slice.cpu_type, slice.uuid.uuid().unwrap());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use crate::{BuildId, Endian, Error, Kind};
+
+const MH_MAGIC: u32 = 0xfeedface;
+const MH_MAGIC_64: u32 = 0xfeedfacf;
+const FAT_MAGIC: u32 = 0xcafebabe;
+const FAT_MAGIC_64: u32 = 0xcafebabf;
+
+/// Load command holding the uuid
+pub const LC_UUID: u32 = 0x1b;
+
+/// Flag in `cpu_type` indicating a 64-bit ABI
+pub const CPU_ARCH_ABI64: u32 = 0x0100_0000;
+pub const CPU_TYPE_X86: u32 = 7;
+pub const CPU_TYPE_X86_64: u32 = CPU_TYPE_X86 | CPU_ARCH_ABI64;
+pub const CPU_TYPE_ARM: u32 = 12;
+pub const CPU_TYPE_ARM64: u32 = CPU_TYPE_ARM | CPU_ARCH_ABI64;
+pub const CPU_TYPE_POWERPC: u32 = 18;
+pub const CPU_TYPE_POWERPC64: u32 = CPU_TYPE_POWERPC | CPU_ARCH_ABI64;
+
+// `0xcafebabe` is also the magic number of java class files, which have the class file version
+// where we expect the architecture count. Those versions are all well above any reasonable
+// number of architectures.
+const MAX_FAT_ARCHS: u32 = 30;
+
+const FAT_HEADER_SIZE: usize = 8;
+const LOAD_COMMAND_SIZE: usize = 8;
+
+/// Format of a Mach-O file, as determined from its magic number
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Magic {
+    /// A single Mach-O object
+    Thin { endian: Endian, is_64: bool },
+    /// A universal binary. Always big-endian.
+    Fat { is_64: bool },
+}
+
+impl Magic {
+    /// Identify a Mach-O file from its first 8 bytes
+    pub(crate) fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 8 {
+            return None;
+        }
+        let be = Endian::Big.u32(data);
+        let le = Endian::Little.u32(data);
+        match (be, le) {
+            (MH_MAGIC, _) => Some(Magic::Thin {
+                endian: Endian::Big,
+                is_64: false,
+            }),
+            (MH_MAGIC_64, _) => Some(Magic::Thin {
+                endian: Endian::Big,
+                is_64: true,
+            }),
+            (_, MH_MAGIC) => Some(Magic::Thin {
+                endian: Endian::Little,
+                is_64: false,
+            }),
+            (_, MH_MAGIC_64) => Some(Magic::Thin {
+                endian: Endian::Little,
+                is_64: true,
+            }),
+            (FAT_MAGIC | FAT_MAGIC_64, _) if Endian::Big.u32(&data[4..]) <= MAX_FAT_ARCHS => {
+                Some(Magic::Fat {
+                    is_64: be == FAT_MAGIC_64,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A single (not universal) Mach-O file held in memory
+#[derive(Debug, Clone, Copy)]
+pub struct MachFile<'a> {
+    data: &'a [u8],
+    endian: Endian,
+    is_64: bool,
+    /// `cputype` from the mach header
+    pub cpu_type: u32,
+    /// `cpusubtype` from the mach header, including any capability bits in the high byte
+    pub cpu_subtype: u32,
+    /// `filetype` from the mach header (`MH_EXECUTE`, `MH_DYLIB`, etc)
+    pub file_type: u32,
+    ncmds: u32,
+    sizeofcmds: u32,
+}
+
+impl<'a> MachFile<'a> {
+    /// Parse the mach header at the start of `data`
+    ///
+    /// `data` must contain at least the header and the load commands. Universal binaries are not
+    /// accepted, use [`slices()`] to handle them.
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        let (endian, is_64) = match Magic::parse(data) {
+            Some(Magic::Thin { endian, is_64 }) => (endian, is_64),
+            _ => return Err(Error::UnrecognizedFormat),
+        };
+
+        let hsize = Self::header_size_for(is_64);
+        if data.len() < hsize {
+            return Err(Error::Truncated);
+        }
+
+        let f = MachFile {
+            data,
+            endian,
+            is_64,
+            cpu_type: endian.u32(&data[4..]),
+            cpu_subtype: endian.u32(&data[8..]),
+            file_type: endian.u32(&data[12..]),
+            ncmds: endian.u32(&data[16..]),
+            sizeofcmds: endian.u32(&data[20..]),
+        };
+
+        if data.len() - hsize < f.sizeofcmds as usize {
+            return Err(Error::Truncated);
+        }
+
+        Ok(f)
+    }
+
+    fn header_size_for(is_64: bool) -> usize {
+        if is_64 {
+            32
+        } else {
+            28
+        }
+    }
+
+    /// Byte order of the file
+    pub fn endian(&self) -> Endian {
+        self.endian
+    }
+
+    /// Return true if this uses the 64-bit mach header
+    pub fn is_64(&self) -> bool {
+        self.is_64
+    }
+
+    /// Size of the mach header, plus all of the load commands
+    pub fn headers_size(&self) -> usize {
+        Self::header_size_for(self.is_64) + self.sizeofcmds as usize
+    }
+
+    /// Iterate over the load commands
+    pub fn load_commands(&self) -> LoadCommands<'a> {
+        let start = Self::header_size_for(self.is_64);
+        LoadCommands {
+            data: &self.data[start..start + self.sizeofcmds as usize],
+            endian: self.endian,
+            remaining: self.ncmds,
+        }
+    }
+
+    /// The content of the `LC_UUID` load command
+    pub fn uuid(&self) -> Result<BuildId, Error> {
+        for cmd in self.load_commands() {
+            let cmd = cmd?;
+            if cmd.cmd == LC_UUID {
+                let uuid = cmd
+                    .data
+                    .get(..16)
+                    .ok_or(Error::Malformed("LC_UUID is too small"))?;
+                return BuildId::new(Kind::MachUuid, uuid).ok_or(Error::TooLong(uuid.len()));
+            }
+        }
+
+        Err(Error::NotFound)
+    }
+}
+
+/// A Mach-O load command
+#[derive(Debug, Clone, Copy)]
+pub struct LoadCommand<'a> {
+    pub cmd: u32,
+    /// Content of the command, following `cmd` and `cmdsize`
+    pub data: &'a [u8],
+}
+
+/// Iterate over the load commands of a [`MachFile`]
+#[derive(Debug, Clone)]
+pub struct LoadCommands<'a> {
+    data: &'a [u8],
+    endian: Endian,
+    remaining: u32,
+}
+
+impl<'a> Iterator for LoadCommands<'a> {
+    type Item = Result<LoadCommand<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        if self.data.len() < LOAD_COMMAND_SIZE {
+            self.remaining = 0;
+            return Some(Err(Error::Truncated));
+        }
+        let cmd = self.endian.u32(self.data);
+        let cmdsize = self.endian.u32(&self.data[4..]) as usize;
+        if cmdsize < LOAD_COMMAND_SIZE || cmdsize > self.data.len() {
+            self.remaining = 0;
+            return Some(Err(Error::Malformed("invalid load command size")));
+        }
+
+        let (c, rest) = self.data.split_at(cmdsize);
+        self.data = rest;
+        Some(Ok(LoadCommand {
+            cmd,
+            data: &c[LOAD_COMMAND_SIZE..],
+        }))
+    }
+}
+
+/// An architecture entry in a universal binary's header (`fat_arch`/`fat_arch_64`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FatArch {
+    cpu_type: u32,
+    cpu_subtype: u32,
+    offset: u64,
+    size: u64,
+    align: u32,
+}
+
+impl FatArch {
+    fn size_for(is_64: bool) -> usize {
+        if is_64 {
+            32
+        } else {
+            20
+        }
+    }
+
+    fn parse(data: &[u8], is_64: bool) -> Self {
+        let e = Endian::Big;
+        if is_64 {
+            FatArch {
+                cpu_type: e.u32(data),
+                cpu_subtype: e.u32(&data[4..]),
+                offset: e.u64(&data[8..]),
+                size: e.u64(&data[16..]),
+                align: e.u32(&data[24..]),
+            }
+        } else {
+            FatArch {
+                cpu_type: e.u32(data),
+                cpu_subtype: e.u32(&data[4..]),
+                offset: e.u32(&data[8..]) as u64,
+                size: e.u32(&data[12..]) as u64,
+                align: e.u32(&data[16..]),
+            }
+        }
+    }
+
+    /// Parse the architecture table of a universal binary from `data`, which must start with
+    /// the fat header
+    pub(crate) fn table(data: &[u8]) -> Result<FatArchs<'_>, Error> {
+        let is_64 = match Magic::parse(data) {
+            Some(Magic::Fat { is_64 }) => is_64,
+            _ => return Err(Error::UnrecognizedFormat),
+        };
+        let n = Endian::Big.u32(&data[4..]) as usize;
+        let len = n
+            .checked_mul(Self::size_for(is_64))
+            .ok_or(Error::Truncated)?;
+        let table = data
+            .get(FAT_HEADER_SIZE..)
+            .and_then(|d| d.get(..len))
+            .ok_or(Error::Truncated)?;
+        Ok(FatArchs { table, is_64 })
+    }
+
+    /// Size of the fat header and architecture table, given the first 8 bytes of the file
+    #[cfg(feature = "std")]
+    pub(crate) fn table_size(data: &[u8]) -> Option<usize> {
+        match Magic::parse(data)? {
+            Magic::Fat { is_64 } => (Endian::Big.u32(&data[4..]) as usize)
+                .checked_mul(Self::size_for(is_64))?
+                .checked_add(FAT_HEADER_SIZE),
+            Magic::Thin { .. } => None,
+        }
+    }
+}
+
+/// Iterate over the architecture table of a universal binary
+#[derive(Debug, Clone)]
+pub(crate) struct FatArchs<'a> {
+    table: &'a [u8],
+    is_64: bool,
+}
+
+impl Iterator for FatArchs<'_> {
+    type Item = FatArch;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let size = FatArch::size_for(self.is_64);
+        if self.table.len() < size {
+            return None;
+        }
+        let (e, rest) = self.table.split_at(size);
+        self.table = rest;
+        Some(FatArch::parse(e, self.is_64))
+    }
+}
+
+/// Iterate over each Mach-O file contained in a universal binary (or the single file in a thin
+/// binary)
+///
+/// Created by [`slices()`].
+#[derive(Debug, Clone)]
+pub struct Slices<'a> {
+    data: &'a [u8],
+    inner: SlicesInner<'a>,
+}
+
+#[derive(Debug, Clone)]
+enum SlicesInner<'a> {
+    Thin(bool),
+    Fat(FatArchs<'a>),
+}
+
+impl<'a> Iterator for Slices<'a> {
+    type Item = Result<MachFile<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.inner {
+            SlicesInner::Thin(done) => {
+                if *done {
+                    return None;
+                }
+                *done = true;
+                Some(MachFile::parse(self.data))
+            }
+            SlicesInner::Fat(archs) => {
+                let arch = archs.next()?;
+                let data = usize::try_from(arch.offset)
+                    .ok()
+                    .zip(usize::try_from(arch.size).ok())
+                    .and_then(|(o, s)| self.data.get(o..o.checked_add(s)?));
+                Some(match data {
+                    Some(d) => MachFile::parse(d),
+                    None => Err(Error::Truncated),
+                })
+            }
+        }
+    }
+}
+
+/// Iterate over the Mach-O files in `data`, which may be a universal binary or a single Mach-O
+/// file
+pub fn slices(data: &[u8]) -> Result<Slices<'_>, Error> {
+    let inner = match Magic::parse(data) {
+        Some(Magic::Thin { .. }) => SlicesInner::Thin(false),
+        Some(Magic::Fat { .. }) => SlicesInner::Fat(FatArch::table(data)?),
+        None => return Err(Error::UnrecognizedFormat),
+    };
+
+    Ok(Slices { data, inner })
+}
+
+/// The uuid of one Mach-O file, and the architecture it is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SliceUuid {
+    pub cpu_type: u32,
+    pub cpu_subtype: u32,
+    pub uuid: BuildId,
+}
+
+impl SliceUuid {
+    fn from_file(f: &MachFile<'_>) -> Result<Self, Error> {
+        Ok(SliceUuid {
+            cpu_type: f.cpu_type,
+            cpu_subtype: f.cpu_subtype,
+            uuid: f.uuid()?,
+        })
+    }
+}
+
+/// Iterate over the `LC_UUID` of each Mach-O file in `data`, which may be a universal binary or
+/// a single Mach-O file
+pub fn uuids(data: &[u8]) -> Result<impl Iterator<Item = Result<SliceUuid, Error>> + '_, Error> {
+    Ok(slices(data)?.map(|f| SliceUuid::from_file(&f?)))
+}
+
+/// Read the `LC_UUID` of each Mach-O file in a universal binary or single Mach-O file
+///
+/// Only the headers and load commands of each file are read. Like [`uuids()`], there is a result
+/// for each file, so one that can't be read doesn't hide the others.
+#[cfg(feature = "std")]
+pub fn read_uuids<R: std::io::Read + std::io::Seek>(
+    r: &mut R,
+) -> Result<std::vec::Vec<Result<SliceUuid, Error>>, Error> {
+    use crate::elf_file::read_range;
+
+    let magic = read_range(r, 0..8)?;
+    let mut res = std::vec::Vec::new();
+    match Magic::parse(&magic) {
+        Some(Magic::Fat { .. }) => {
+            let size = FatArch::table_size(&magic).ok_or(Error::Truncated)?;
+            let table = read_range(r, 0..size as u64)?;
+            for arch in FatArch::table(&table)? {
+                res.push(read_slice(r, arch.offset));
+            }
+        }
+        Some(Magic::Thin { .. }) => res.push(read_slice(r, 0)),
+        None => return Err(Error::UnrecognizedFormat),
+    }
+
+    Ok(res)
+}
+
+#[cfg(feature = "std")]
+fn read_slice<R: std::io::Read + std::io::Seek>(
+    r: &mut R,
+    offset: u64,
+) -> Result<SliceUuid, Error> {
+    use crate::elf_file::read_range;
+
+    // the 64-bit header is the larger one
+    let end = offset.checked_add(32).ok_or(Error::Truncated)?;
+    let header = read_range(r, offset..end)?;
+    let (is_64, endian) = match Magic::parse(&header) {
+        Some(Magic::Thin { endian, is_64 }) => (is_64, endian),
+        _ => return Err(Error::UnrecognizedFormat),
+    };
+    let size = MachFile::header_size_for(is_64) as u64 + endian.u32(&header[20..]) as u64;
+    let end = offset.checked_add(size).ok_or(Error::Truncated)?;
+    let headers = read_range(r, offset..end)?;
+    SliceUuid::from_file(&MachFile::parse(&headers)?)
+}
@@ -97,13 +97,13 @@ extern "C" {
 }
 
 // mach-o only
-pub fn build_id() -> Option<&'static [u8]> {
+pub fn build_id() -> Result<&'static [u8], crate::Error> {
     // _mh_execute_header
     for cmd in CommandIter::new_execute() {
         if cmd.cmd == LC_UUID {
-            return Some(cmd.data);
+            return Ok(cmd.data);
         }
     }
 
-    None
+    Err(crate::Error::NotFound)
 }
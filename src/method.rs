@@ -0,0 +1,221 @@
+//! The ways our own build-id can be located
+//!
+//! [`try_build_id()`](crate::try_build_id) tries each of [`Method::ALL`] in order, until one finds
+//! a build-id. [`try_build_id_with()`] tries a
+//! list of methods of your choosing instead, and reports which one found the build-id:
+//!
+//! ```
+//! use buildid::Method;
+//!
+//! match buildid::try_build_id_with(&[Method::DynamicLoader, Method::ProcMaps]) {
+//!     Ok((id, method)) => println!("{} (found with {:?})", id, method),
+//!     Err(e) => println!("no build-id: {}", e),
+//! }
+//! ```
+use crate::{BuildId, Error, Kind};
+use log::debug;
+
+/// A way of locating the build-id of the object this crate is linked into
+///
+/// Most methods are only available on some platforms, or with one of the optional features (see
+/// the [crate documentation](crate#optional-features)). [`Method::build_id()`] returns
+/// [`Error::Unavailable`] for the others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Method {
+    /// The `build_id__get()` function provided for `buildid-custom-inject`
+    CustomInject,
+    /// A symbol placed at the end of the `.note.gnu.build-id` section, with
+    /// `buildid-section-inject`
+    SectionInject,
+    /// The `__build_id_start` and `__build_id_end` symbols, with `buildid-symbol-start-end`
+    SymbolStartEnd,
+    /// The program headers at the `__ehdr_start` symbol, with `buildid-ehdr-start`
+    EhdrStart,
+    /// The loaded object containing our code, from `dl_iterate_phdr()` (ELF platforms)
+    DynamicLoader,
+    /// The main executable's program headers, from the auxiliary vector (Linux, with glibc or
+    /// musl)
+    AuxVector,
+    /// The ELF header of the file mapping containing our code, from `/proc/self/maps` (Linux, with
+    /// the `std` feature)
+    ProcMaps,
+    /// The platform's own record: the `LC_UUID` of the Mach-O image on Apple platforms, the
+    /// CodeView record of the module on Windows, and the build-id copied in by
+    /// [`wasm_file::embed_build_id()`](crate::wasm_file::embed_build_id) on wasm
+    Platform,
+}
+
+impl Method {
+    /// The methods [`try_build_id()`](crate::try_build_id) tries, in order of precedence
+    ///
+    /// These are the methods enabled by features, if there are any, as they are only enabled when
+    /// the final link is known to support them. The default methods aren't tried then (or linked
+    /// in, as they may need libc), but [`try_build_id_with()`] can still try them. Without any of
+    /// those features, these are the default methods available on this platform.
+    pub const ALL: &'static [Method] = CHAIN;
+
+    /// Return true if this method is supported on this platform, with the enabled features
+    pub fn is_available(self) -> bool {
+        match self {
+            Self::CustomInject => cfg!(feature = "buildid-custom-inject"),
+            Self::SectionInject => cfg!(feature = "buildid-section-inject"),
+            Self::SymbolStartEnd => cfg!(feature = "buildid-symbol-start-end"),
+            Self::EhdrStart => cfg!(feature = "buildid-ehdr-start"),
+            Self::DynamicLoader => cfg!(all(target_family = "unix", not(target_vendor = "apple"))),
+            Self::AuxVector => cfg!(all(
+                target_os = "linux",
+                any(target_env = "gnu", target_env = "musl")
+            )),
+            Self::ProcMaps => cfg!(all(
+                feature = "std",
+                any(target_os = "linux", target_os = "android")
+            )),
+            Self::Platform => cfg!(any(
+                all(target_family = "unix", target_vendor = "apple"),
+                target_family = "windows",
+                target_family = "wasm"
+            )),
+        }
+    }
+
+    /// The kind of build-id this method finds
+    pub fn kind(self) -> Kind {
+        match self {
+            // `build_id__get` could return anything
+            Self::CustomInject => Kind::Unknown,
+            Self::Platform if cfg!(target_vendor = "apple") => Kind::MachUuid,
+            Self::Platform if cfg!(target_family = "windows") => Kind::CodeView,
+            Self::Platform if cfg!(target_family = "wasm") => Kind::Wasm,
+            Self::Platform => Kind::Unknown,
+            _ => Kind::Gnu,
+        }
+    }
+
+    /// Locate our own build-id with this method
+    pub fn build_id(self) -> Result<&'static [u8], Error> {
+        match self {
+            Self::CustomInject | Self::SectionInject | Self::SymbolStartEnd | Self::EhdrStart => {
+                self.feature_build_id()
+            }
+            #[cfg(all(target_family = "unix", not(target_vendor = "apple")))]
+            Self::DynamicLoader => crate::elf::loader_object()?.try_build_id(),
+            #[cfg(all(target_os = "linux", any(target_env = "gnu", target_env = "musl")))]
+            Self::AuxVector => crate::elf::auxv_object()?.try_build_id(),
+            #[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+            Self::ProcMaps => crate::elf::maps_object()?.try_build_id(),
+            #[cfg(any(
+                all(target_family = "unix", target_vendor = "apple"),
+                target_family = "windows",
+                target_family = "wasm"
+            ))]
+            Self::Platform => crate::platform::build_id(),
+            #[allow(unreachable_patterns)]
+            _ => Err(Error::Unavailable),
+        }
+    }
+
+    /// Locate our own build-id with this method, if it is one enabled by a feature
+    fn feature_build_id(self) -> Result<&'static [u8], Error> {
+        match self {
+            #[cfg(feature = "buildid-custom-inject")]
+            Self::CustomInject => crate::custom_inject::build_id(),
+            #[cfg(feature = "buildid-section-inject")]
+            Self::SectionInject => crate::section_inject::build_id(),
+            #[cfg(feature = "buildid-symbol-start-end")]
+            Self::SymbolStartEnd => crate::symbol_start_end::build_id(),
+            #[cfg(feature = "buildid-ehdr-start")]
+            Self::EhdrStart => crate::ehdr_start::build_id(),
+            _ => Err(Error::Unavailable),
+        }
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(any(
+        feature = "buildid-custom-inject",
+        feature = "buildid-section-inject",
+        feature = "buildid-symbol-start-end",
+        feature = "buildid-ehdr-start"
+    ))] {
+        const CHAIN: &[Method] = &[
+            #[cfg(feature = "buildid-custom-inject")]
+            Method::CustomInject,
+            #[cfg(feature = "buildid-section-inject")]
+            Method::SectionInject,
+            #[cfg(feature = "buildid-symbol-start-end")]
+            Method::SymbolStartEnd,
+            #[cfg(feature = "buildid-ehdr-start")]
+            Method::EhdrStart,
+        ];
+
+        // only the feature methods are looked up, so that `dl_iterate_phdr()` and `getauxval()`
+        // aren't linked in
+        fn chain_build_id(method: Method) -> Result<&'static [u8], Error> {
+            method.feature_build_id()
+        }
+    } else {
+        const CHAIN: &[Method] = &[
+            #[cfg(all(target_family = "unix", not(target_vendor = "apple")))]
+            Method::DynamicLoader,
+            #[cfg(all(target_os = "linux", any(target_env = "gnu", target_env = "musl")))]
+            Method::AuxVector,
+            #[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+            Method::ProcMaps,
+            #[cfg(any(
+                all(target_family = "unix", target_vendor = "apple"),
+                target_family = "windows",
+                target_family = "wasm"
+            ))]
+            Method::Platform,
+        ];
+
+        fn chain_build_id(method: Method) -> Result<&'static [u8], Error> {
+            method.build_id()
+        }
+    }
+}
+
+/// Try each of [`Method::ALL`] in order, returning the first build-id found, and the method that
+/// found it
+pub(crate) fn find_own() -> Result<(&'static [u8], Method), Error> {
+    find(Method::ALL, chain_build_id)
+}
+
+/// Try each of `methods` in order with `lookup`, returning the first build-id found, and the
+/// method that found it
+fn find(
+    methods: &[Method],
+    lookup: fn(Method) -> Result<&'static [u8], Error>,
+) -> Result<(&'static [u8], Method), Error> {
+    let mut err = Error::Unavailable;
+    for &method in methods {
+        let e = match lookup(method) {
+            Ok(id) if !id.is_empty() => return Ok((id, method)),
+            Ok(_) => Error::NotFound,
+            Err(e) => e,
+        };
+        debug!("{:?} did not find a build-id: {}", method, e);
+        // report the first failure that says more than that there's nothing to find
+        if matches!(err, Error::Unavailable | Error::NotFound) && e != Error::Unavailable {
+            err = e;
+        }
+    }
+    Err(err)
+}
+
+/// Locate our own build-id by trying each of `methods` in order, returning it along with the
+/// method that found it
+///
+/// Methods that are unavailable, fail, or find an empty build-id are skipped. If none of them find
+/// a build-id, the first error other than [`Error::NotFound`] is returned ([`Error::NotFound`] if
+/// there is none, and [`Error::Unavailable`] if none of the methods are available).
+pub fn try_build_id_with(methods: &[Method]) -> Result<(BuildId, Method), Error> {
+    typed(find(methods, Method::build_id)?)
+}
+
+/// Wrap a build-id found by `method` in a [`BuildId`] of the kind it finds
+pub(crate) fn typed((id, method): (&'static [u8], Method)) -> Result<(BuildId, Method), Error> {
+    let id = BuildId::new(method.kind(), id).ok_or(Error::TooLong(id.len()))?;
+    Ok((id, method))
+}
@@ -0,0 +1,413 @@
+//! Parsing of ELF notes (the content of `PT_NOTE` segments and `SHT_NOTE` sections)
+//!
+//! Each note consists of a header (name size, descriptor size, and type), followed by the name
+//! and the descriptor, each padded to the note alignment. That is the `p_align` of the segment (or
+//! `sh_addralign` of the section) holding the notes: almost always 4, but 8 for the
+//! `.note.gnu.property` segments of 64-bit objects built for CET or BTI.
+//!
+//! Besides the build-id, [`Note`] decodes the ABI tag ([`Note::abi_tag()`]), program properties
+//! ([`Note::gnu_properties()`]), Go build-id ([`Note::go_build_id()`]), and package metadata
+//! ([`Note::package_metadata()`]) notes. Other notes can be read with [`Note::name()`],
+//! [`Note::type_()`], and [`Note::desc()`].
+use crate::align::align_up;
+use crate::Endian;
+use core::fmt;
+use core::mem;
+
+/// Note type of the ABI tag note, the OS and minimum kernel version an object requires (when the
+/// note name is `"GNU\0"`)
+pub const NT_GNU_ABI_TAG: u32 = 1;
+/// Note type of a GNU build-id note (when the note name is `"GNU\0"`)
+pub const NT_GNU_BUILD_ID: u32 = 3;
+/// Note type of the GNU program properties note, such as the CET and BTI markings (when the note
+/// name is `"GNU\0"`)
+pub const NT_GNU_PROPERTY_TYPE_0: u32 = 5;
+/// Note type of the Go toolchain's build-id note (when the note name is `"Go\0\0"`)
+pub const NT_GO_BUILD_ID: u32 = 4;
+/// Note type of the package metadata note (`.note.package`) described by the freedesktop.org
+/// "Package Metadata for Core Files" spec (when the note name is `"FDO\0"`)
+pub const NT_FDO_PACKAGING_METADATA: u32 = 0xcafe_1a7e;
+
+/// The x86 features (such as `IBT` and `SHSTK`) an object is compatible with
+pub const GNU_PROPERTY_X86_FEATURE_1_AND: u32 = 0xc000_0002;
+/// The AArch64 features (such as `BTI` and `PAC`) an object is compatible with
+pub const GNU_PROPERTY_AARCH64_FEATURE_1_AND: u32 = 0xc000_0000;
+
+/// A single ELF note
+#[derive(Debug, Clone, Copy)]
+pub struct Note<'a> {
+    data: &'a [u8],
+    endian: Endian,
+    align: usize,
+}
+
+const MIN_NOTE_SIZE: usize = mem::size_of::<u32>() * 3;
+
+/// A note could not be parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteError {
+    /// Not enough bytes remain for a note header
+    MissingHeader { size: usize },
+    /// The note header describes a name and descriptor that extend past the available bytes
+    Truncated { have: usize, need: usize },
+}
+
+impl fmt::Display for NoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingHeader { size } => write!(
+                f,
+                "have {} bytes, but need at least {}",
+                size, MIN_NOTE_SIZE
+            ),
+            Self::Truncated { have, need } => {
+                write!(f, "have {} bytes, but need at least {}", have, need)
+            }
+        }
+    }
+}
+
+/// The note alignment of a segment or section with alignment `align`
+///
+/// Like glibc, only 8 is treated as 8-byte alignment. Everything else (including 0 and 1, which
+/// some linkers use) means 4 bytes, as the _standards_ say to use 8 byte alignment in 64-bit land,
+/// but llvm and others note that everyone actually uses 4 byte alignment.
+fn note_align(align: usize) -> usize {
+    if align == 8 {
+        8
+    } else {
+        4
+    }
+}
+
+impl<'a> Note<'a> {
+    fn from_bytes_raw(data: &'a [u8], endian: Endian, align: usize) -> Self {
+        Note {
+            data,
+            endian,
+            align,
+        }
+    }
+
+    /// Parse the 4-byte aligned note at the start of `data`, returning it and the bytes that
+    /// follow it
+    pub fn from_bytes(data: &'a [u8], endian: Endian) -> Result<(Self, &'a [u8]), NoteError> {
+        Self::from_bytes_aligned(data, endian, 4)
+    }
+
+    /// Parse the note at the start of `data`, which is part of a segment or section with
+    /// alignment `align`, returning it and the bytes that follow it
+    pub fn from_bytes_aligned(
+        data: &'a [u8],
+        endian: Endian,
+        align: usize,
+    ) -> Result<(Self, &'a [u8]), NoteError> {
+        if data.len() < MIN_NOTE_SIZE {
+            return Err(NoteError::MissingHeader { size: data.len() });
+        }
+
+        Self::from_bytes_raw(data, endian, note_align(align)).split_trailing()
+    }
+
+    /// The alignment of the name and descriptor: 4 or 8 bytes
+    pub fn align(&self) -> usize {
+        self.align
+    }
+
+    fn name_len(&self) -> usize {
+        self.endian.u32(self.data) as usize
+    }
+
+    fn desc_len(&self) -> usize {
+        let u = mem::size_of::<u32>();
+        self.endian.u32(&self.data[u..]) as usize
+    }
+
+    /// The note type. Its meaning depends on the note name.
+    pub fn type_(&self) -> u32 {
+        let u = mem::size_of::<u32>();
+        self.endian.u32(&self.data[(u + u)..])
+    }
+
+    /// The note name (owner), including the trailing nul byte (for example, `b"GNU\0"`)
+    pub fn name(&self) -> &'a [u8] {
+        let u = mem::size_of::<u32>();
+        let b = u * 3;
+        &self.data[b..(b + self.name_len())]
+    }
+
+    /// Offset of the descriptor from the start of the note
+    pub(crate) fn desc_offset(&self) -> usize {
+        let u = mem::size_of::<u32>();
+        align_up(u * 3 + self.name_len(), self.align)
+    }
+
+    /// The note descriptor (the note's content)
+    pub fn desc(&self) -> &'a [u8] {
+        let b = self.desc_offset();
+        &self.data[b..(b + self.desc_len())]
+    }
+
+    /// Return true if this is a non-empty `NT_GNU_BUILD_ID` note
+    pub fn is_build_id(&self) -> bool {
+        self.type_() == NT_GNU_BUILD_ID && !self.desc().is_empty() && self.name() == b"GNU\0"
+    }
+
+    fn is(&self, name: &[u8], type_: u32) -> bool {
+        self.type_() == type_ && self.name() == name
+    }
+
+    /// Decode an `NT_GNU_ABI_TAG` note
+    ///
+    /// Returns `None` if this is a different note, or its descriptor is too short.
+    pub fn abi_tag(&self) -> Option<AbiTag> {
+        let desc = self.desc();
+        if !self.is(b"GNU\0", NT_GNU_ABI_TAG) || desc.len() < 16 {
+            return None;
+        }
+        let word = |i: usize| self.endian.u32(&desc[i * 4..]);
+        Some(AbiTag {
+            os: word(0),
+            version: [word(1), word(2), word(3)],
+        })
+    }
+
+    /// Iterate over the properties of an `NT_GNU_PROPERTY_TYPE_0` note
+    ///
+    /// Each property is padded to `align` bytes, which is 8 in 64-bit objects and 4 in 32-bit ones
+    /// (so, when the note was read from its segment, the note's own [`Note::align()`]).
+    /// Returns `None` if this is a different note.
+    pub fn gnu_properties(&self, align: usize) -> Option<GnuProperties<'a>> {
+        if !self.is(b"GNU\0", NT_GNU_PROPERTY_TYPE_0) {
+            return None;
+        }
+        Some(GnuProperties {
+            data: self.desc(),
+            endian: self.endian,
+            align,
+        })
+    }
+
+    /// The build-id of a Go program, from the Go toolchain's `NT_GO_BUILD_ID` note
+    ///
+    /// This is the value printed by `go tool buildid`, which is unrelated to the GNU build-id the
+    /// external linker may also add. Returns `None` if this is a different note, or the build-id is
+    /// not UTF-8.
+    pub fn go_build_id(&self) -> Option<&'a str> {
+        if !self.is(b"Go\0\0", NT_GO_BUILD_ID) {
+            return None;
+        }
+        core::str::from_utf8(self.desc()).ok()
+    }
+
+    /// The package metadata of an `NT_FDO_PACKAGING_METADATA` note
+    ///
+    /// Returns `None` if this is a different note, or the metadata is not UTF-8.
+    pub fn package_metadata(&self) -> Option<PackageMetadata<'a>> {
+        if !self.is(b"FDO\0", NT_FDO_PACKAGING_METADATA) {
+            return None;
+        }
+        let desc = self.desc();
+        let len = desc.iter().position(|&c| c == 0).unwrap_or(desc.len());
+        Some(PackageMetadata {
+            json: core::str::from_utf8(&desc[..len]).ok()?,
+        })
+    }
+
+    fn split_trailing(&self) -> Result<(Self, &'a [u8]), NoteError> {
+        // the sizes come from the note itself, so these can overflow (on 32-bit hosts)
+        let end = MIN_NOTE_SIZE
+            .checked_add(self.name_len())
+            .and_then(|n| n.checked_next_multiple_of(self.align))
+            .and_then(|n| n.checked_add(self.desc_len()))
+            .unwrap_or(usize::MAX);
+        if end > self.data.len() {
+            return Err(NoteError::Truncated {
+                need: end,
+                have: self.data.len(),
+            });
+        }
+        // some linkers don't pad the descriptor of the last note in a segment
+        let end = align_up(end, self.align).min(self.data.len());
+        Ok((
+            Self::from_bytes_raw(&self.data[0..end], self.endian, self.align),
+            &self.data[end..],
+        ))
+    }
+}
+
+/// The content of an `NT_GNU_ABI_TAG` note
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AbiTag {
+    /// The OS: 0 for Linux, 1 for GNU Hurd, 2 for Solaris, and 3 for FreeBSD
+    pub os: u32,
+    /// The minimum kernel version (major, minor, and patch) the object needs
+    pub version: [u32; 3],
+}
+
+/// A single GNU program property
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GnuProperty<'a> {
+    /// The property type, such as [`GNU_PROPERTY_X86_FEATURE_1_AND`]
+    pub type_: u32,
+    /// The property's data, without padding
+    pub data: &'a [u8],
+    endian: Endian,
+}
+
+impl GnuProperty<'_> {
+    /// The data as a 32-bit value (as used by the `FEATURE_1_AND` bitmasks), if it is 4 bytes long
+    pub fn value_u32(&self) -> Option<u32> {
+        (self.data.len() == 4).then(|| self.endian.u32(self.data))
+    }
+}
+
+/// Iterate over the properties of an `NT_GNU_PROPERTY_TYPE_0` note
+///
+/// Created by [`Note::gnu_properties()`].
+#[derive(Debug, Clone)]
+pub struct GnuProperties<'a> {
+    data: &'a [u8],
+    endian: Endian,
+    align: usize,
+}
+
+impl<'a> Iterator for GnuProperties<'a> {
+    type Item = Result<GnuProperty<'a>, NoteError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+        if self.data.len() < 8 {
+            let size = self.data.len();
+            self.data = &[];
+            return Some(Err(NoteError::MissingHeader { size }));
+        }
+        let type_ = self.endian.u32(self.data);
+        let len = self.endian.u32(&self.data[4..]) as usize;
+        let end = len.saturating_add(8);
+        if end > self.data.len() {
+            let have = self.data.len();
+            self.data = &[];
+            return Some(Err(NoteError::Truncated { have, need: end }));
+        }
+        let data = &self.data[8..end];
+        self.data = &self.data[align_up(end, self.align.max(1)).min(self.data.len())..];
+        Some(Ok(GnuProperty {
+            type_,
+            data,
+            endian: self.endian,
+        }))
+    }
+}
+
+/// The package metadata of an `NT_FDO_PACKAGING_METADATA` note, a flat JSON object such as
+/// `{"type":"rpm","name":"curl","version":"8.2.1-1.fc39","architecture":"x86_64"}`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackageMetadata<'a> {
+    json: &'a str,
+}
+
+impl<'a> PackageMetadata<'a> {
+    /// The JSON text
+    pub fn json(&self) -> &'a str {
+        self.json
+    }
+
+    /// Find the value of the string field `key` (such as `"name"` or `"version"`)
+    ///
+    /// The value is returned as it appears in the JSON text: escape sequences are not decoded.
+    /// Returns `None` if there is no such field, or if the text is not a flat JSON object with only
+    /// string values (which the spec requires).
+    pub fn get(&self, key: &str) -> Option<&'a str> {
+        let mut rest = self.json.trim_start().strip_prefix('{')?;
+        loop {
+            rest = rest.trim_start();
+            let (k, r) = json_string(rest)?;
+            let r = r.trim_start().strip_prefix(':')?.trim_start();
+            let (v, r) = json_string(r)?;
+            if k == key {
+                return Some(v);
+            }
+            rest = r.trim_start().strip_prefix(',')?;
+        }
+    }
+}
+
+/// Split the JSON string at the start of `s` into its (undecoded) content and the text after it
+fn json_string(s: &str) -> Option<(&str, &str)> {
+    let s = s.strip_prefix('"')?;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            '"' if !escaped => return Some((&s[..i], &s[i + 1..])),
+            '\\' => escaped = !escaped,
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+/// Iterate over notes stored in a PT_NOTE program section (or SHT_NOTE section)
+#[derive(Debug, Clone)]
+pub struct NoteIter<'a> {
+    segment: &'a [u8],
+    endian: Endian,
+    align: usize,
+}
+
+impl<'a> NoteIter<'a> {
+    /// Iterate over the 4-byte aligned notes in `segment`, which holds the entire content of a
+    /// note segment or section
+    pub fn new(segment: &'a [u8], endian: Endian) -> Self {
+        Self::with_align(segment, endian, 4)
+    }
+
+    /// Iterate over the notes in `segment`, whose alignment (`p_align` or `sh_addralign`) is
+    /// `align`
+    pub fn with_align(segment: &'a [u8], endian: Endian, align: usize) -> Self {
+        NoteIter {
+            segment,
+            endian,
+            align,
+        }
+    }
+
+    /// Find the descriptor of the first `NT_GNU_BUILD_ID` note
+    ///
+    /// Returns `Ok(None)` if no build-id note is present, and an error if a malformed note is
+    /// encountered before a build-id note is found.
+    pub fn build_id(self) -> Result<Option<&'a [u8]>, NoteError> {
+        for note in self {
+            let note = note?;
+            if note.is_build_id() {
+                return Ok(Some(note.desc()));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl<'a> Iterator for NoteIter<'a> {
+    type Item = Result<Note<'a>, NoteError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.segment.is_empty() {
+            return None;
+        }
+
+        let (n, r) = match Note::from_bytes_aligned(self.segment, self.endian, self.align) {
+            Err(e) => {
+                // don't keep returning the same error
+                self.segment = &[];
+                return Some(Err(e));
+            }
+            Ok(v) => v,
+        };
+
+        self.segment = r;
+
+        Some(Ok(n))
+    }
+}
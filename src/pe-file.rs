@@ -0,0 +1,457 @@
+//! Parse the CodeView record (GUID, age, and PDB path) out of PE/COFF files held in memory
+//!
+//! This works on any host, for both PE32 and PE32+ images. All `IMAGE_DEBUG_DIRECTORY` entries
+//! are examined, and the `.buildid` section emitted by mingw (GNU ld) is used if the debug
+//! directory doesn't lead to a CodeView record.
+//!
+//! ```no_run
+//! # fn example(data: &[u8]) -> Result<(), buildid::Error> {
+//! let pe = buildid::pe_file::PeFile::parse(data)?;
+//! let cv = pe.codeview()?;
+//! println!("{:X} {}", cv.guid.uuid().unwrap(), cv.age);
+//! # Ok(())
+//! # }
+//! ```
+use crate::{BuildId, Endian, Error, Kind};
+use core::ops::Range;
+
+const IMAGE_DOS_SIGNATURE: &[u8; 2] = b"MZ";
+const IMAGE_NT_SIGNATURE: &[u8; 4] = b"PE\0\0";
+const IMAGE_NT_OPTIONAL_HDR32_MAGIC: u16 = 0x10b;
+const IMAGE_NT_OPTIONAL_HDR64_MAGIC: u16 = 0x20b;
+const IMAGE_DIRECTORY_ENTRY_DEBUG: usize = 6;
+const CV_SIGNATURE_RSDS: u32 = u32::from_le_bytes(*b"RSDS");
+
+/// Debug directory entry type of a CodeView record
+pub const IMAGE_DEBUG_TYPE_CODEVIEW: u32 = 2;
+/// Debug directory entry type of the hash used in place of a timestamp by `/Brepro` builds
+pub const IMAGE_DEBUG_TYPE_REPRO: u32 = 16;
+
+const E_LFANEW_OFFSET: usize = 0x3c;
+const FILE_HEADER_SIZE: usize = 20;
+const SECTION_HEADER_SIZE: usize = 40;
+const DATA_DIRECTORY_SIZE: usize = 8;
+/// Size of an `IMAGE_DEBUG_DIRECTORY`
+pub const DEBUG_DIRECTORY_SIZE: usize = 28;
+const CV_INFO_PDB70_SIZE: usize = 24;
+
+// PE files are always little-endian
+const LE: Endian = Endian::Little;
+
+/// A PE section header (`IMAGE_SECTION_HEADER`), omitting fields we don't use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Section {
+    /// Section name, nul padded
+    pub name: [u8; 8],
+    pub virtual_size: u32,
+    pub virtual_address: u32,
+    pub size_of_raw_data: u32,
+    pub pointer_to_raw_data: u32,
+}
+
+impl Section {
+    fn parse(data: &[u8]) -> Self {
+        Section {
+            name: data[..8].try_into().unwrap(),
+            virtual_size: LE.u32(&data[8..]),
+            virtual_address: LE.u32(&data[12..]),
+            size_of_raw_data: LE.u32(&data[16..]),
+            pointer_to_raw_data: LE.u32(&data[20..]),
+        }
+    }
+
+    /// Section name with trailing nul padding removed
+    pub fn name(&self) -> &[u8] {
+        let len = self.name.iter().position(|&c| c == 0).unwrap_or(8);
+        &self.name[..len]
+    }
+
+    /// Location of the section's content in the file
+    pub fn file_range(&self) -> Range<u64> {
+        let start = self.pointer_to_raw_data as u64;
+        start..start + self.size_of_raw_data as u64
+    }
+}
+
+/// An `IMAGE_DEBUG_DIRECTORY` entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugDirectory {
+    pub time_date_stamp: u32,
+    pub type_: u32,
+    pub size_of_data: u32,
+    pub address_of_raw_data: u32,
+    pub pointer_to_raw_data: u32,
+}
+
+impl DebugDirectory {
+    /// Parse a debug directory entry at the start of `data`
+    pub fn parse(data: &[u8]) -> Result<Self, Error> {
+        if data.len() < DEBUG_DIRECTORY_SIZE {
+            return Err(Error::Truncated);
+        }
+        Ok(DebugDirectory {
+            time_date_stamp: LE.u32(&data[4..]),
+            type_: LE.u32(&data[12..]),
+            size_of_data: LE.u32(&data[16..]),
+            address_of_raw_data: LE.u32(&data[20..]),
+            pointer_to_raw_data: LE.u32(&data[24..]),
+        })
+    }
+}
+
+/// A CodeView PDB 7.0 (`RSDS`) record, which identifies the PDB matching an image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeView<'a> {
+    /// The PDB GUID, as returned by [`build_id()`](crate::build_id) on windows
+    pub guid: BuildId,
+    /// Incremented each time the PDB is updated. Symbol servers key PDBs by GUID and age.
+    pub age: u32,
+    /// Path of the PDB file, as recorded by the linker (without the trailing nul). Not
+    /// necessarily UTF-8.
+    pub pdb_path: &'a [u8],
+}
+
+impl<'a> CodeView<'a> {
+    /// Parse a `CV_INFO_PDB70` record at the start of `data`
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        if data.len() < 4 {
+            return Err(Error::Truncated);
+        }
+        let sig = LE.u32(data);
+        if sig != CV_SIGNATURE_RSDS {
+            return Err(Error::UnexpectedCodeViewSignature(sig));
+        }
+        if data.len() < CV_INFO_PDB70_SIZE {
+            return Err(Error::Truncated);
+        }
+
+        let path = &data[CV_INFO_PDB70_SIZE..];
+        let path_len = path.iter().position(|&c| c == 0).unwrap_or(path.len());
+        Ok(CodeView {
+            guid: BuildId::new(Kind::CodeView, &data[4..20]).unwrap(),
+            age: LE.u32(&data[20..]),
+            pdb_path: &path[..path_len],
+        })
+    }
+}
+
+/// The headers of a PE image
+///
+/// Only the headers need to be held in memory to parse this, but most methods need the entire
+/// file.
+#[derive(Debug, Clone, Copy)]
+pub struct PeFile<'a> {
+    data: &'a [u8],
+    machine: u16,
+    pe32plus: bool,
+    sections: &'a [u8],
+    debug_dir: Option<(u32, u32)>,
+}
+
+impl<'a> PeFile<'a> {
+    /// Parse the headers of the PE file held in `data`
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        if data.len() < E_LFANEW_OFFSET + 4 || &data[..2] != IMAGE_DOS_SIGNATURE {
+            return Err(Error::UnrecognizedFormat);
+        }
+        // `e_lfanew` can be anywhere in a 32-bit range, so the offsets after it may not fit in a
+        // 32-bit usize
+        let range = |start: usize, len: usize| {
+            let end = start.checked_add(len).ok_or(Error::Truncated)?;
+            data.get(start..end).ok_or(Error::Truncated)
+        };
+        let nt = LE.u32(&data[E_LFANEW_OFFSET..]) as usize;
+        let sig = range(nt, 4)?;
+        if sig != IMAGE_NT_SIGNATURE {
+            // a plain DOS executable
+            return Err(Error::UnrecognizedFormat);
+        }
+
+        let fh_start = nt.checked_add(4).ok_or(Error::Truncated)?;
+        let fh = range(fh_start, FILE_HEADER_SIZE)?;
+        let machine = LE.u16(fh);
+        let nsections = LE.u16(&fh[2..]) as usize;
+        let opt_size = LE.u16(&fh[16..]) as usize;
+
+        let opt_start = fh_start + FILE_HEADER_SIZE;
+        let opt = range(opt_start, opt_size)?;
+        let sections_start = opt_start + opt_size;
+        let sections = range(sections_start, nsections * SECTION_HEADER_SIZE)?;
+
+        if opt.is_empty() {
+            // object files have no optional header, and no debug directory
+            return Ok(PeFile {
+                data,
+                machine,
+                pe32plus: false,
+                sections,
+                debug_dir: None,
+            });
+        }
+
+        if opt.len() < 2 {
+            return Err(Error::NoOptionalHeader);
+        }
+        let (pe32plus, num_dirs_off) = match LE.u16(opt) {
+            IMAGE_NT_OPTIONAL_HDR32_MAGIC => (false, 92),
+            IMAGE_NT_OPTIONAL_HDR64_MAGIC => (true, 108),
+            _ => return Err(Error::Malformed("unknown optional header magic")),
+        };
+        let num_dirs = opt
+            .get(num_dirs_off..num_dirs_off + 4)
+            .map(|d| LE.u32(d) as usize)
+            .ok_or(Error::Truncated)?;
+        let dirs = &opt[num_dirs_off + 4..];
+        let debug_dir = if num_dirs > IMAGE_DIRECTORY_ENTRY_DEBUG {
+            let d = dirs
+                .get(IMAGE_DIRECTORY_ENTRY_DEBUG * DATA_DIRECTORY_SIZE..)
+                .filter(|d| d.len() >= DATA_DIRECTORY_SIZE)
+                .ok_or(Error::Truncated)?;
+            Some((LE.u32(d), LE.u32(&d[4..]))).filter(|(_, size)| *size != 0)
+        } else {
+            None
+        };
+
+        Ok(PeFile {
+            data,
+            machine,
+            pe32plus,
+            sections,
+            debug_dir,
+        })
+    }
+
+    /// `Machine` from the COFF file header (`IMAGE_FILE_MACHINE_AMD64`, etc)
+    pub fn machine(&self) -> u16 {
+        self.machine
+    }
+
+    /// Return true if the image uses the PE32+ (64-bit) optional header
+    pub fn is_pe32plus(&self) -> bool {
+        self.pe32plus
+    }
+
+    /// Iterate over the section headers
+    pub fn sections(&self) -> impl Iterator<Item = Section> + 'a {
+        self.sections
+            .chunks_exact(SECTION_HEADER_SIZE)
+            .map(Section::parse)
+    }
+
+    /// Find the first section named `name`
+    pub fn section_by_name(&self, name: &[u8]) -> Option<Section> {
+        self.sections().find(|s| s.name() == name)
+    }
+
+    /// Translate a relative virtual address to a file offset
+    pub fn rva_to_offset(&self, rva: u32) -> Option<u64> {
+        self.sections().find_map(|s| {
+            let off = rva.checked_sub(s.virtual_address)?;
+            // only the part of the section present in the file can be translated
+            if off < s.size_of_raw_data {
+                Some(s.pointer_to_raw_data as u64 + off as u64)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Location of the debug directory in the file, if the image has one
+    pub fn debug_directory_range(&self) -> Result<Option<Range<u64>>, Error> {
+        let (rva, size) = match self.debug_dir {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        let start = self
+            .rva_to_offset(rva)
+            .ok_or(Error::Malformed("debug directory is not within a section"))?;
+        Ok(Some(start..start + size as u64))
+    }
+
+    /// Iterate over the entries of the debug directory
+    ///
+    /// Returns [`Error::NoDebugDirectory`] if the image doesn't have one.
+    pub fn debug_directories(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<DebugDirectory, Error>> + 'a, Error> {
+        let d = self.debug_directory_data()?;
+        Ok(d.chunks(DEBUG_DIRECTORY_SIZE).map(DebugDirectory::parse))
+    }
+
+    /// The content of the debug directory
+    fn debug_directory_data(&self) -> Result<&'a [u8], Error> {
+        let r = self
+            .debug_directory_range()?
+            .ok_or(Error::NoDebugDirectory)?;
+        crate::elf_file::slice(self.data, r)
+    }
+
+    /// Location of the data a debug directory entry refers to in the file
+    pub fn debug_data_range(&self, dir: &DebugDirectory) -> Result<Range<u64>, Error> {
+        let start = if dir.pointer_to_raw_data != 0 {
+            dir.pointer_to_raw_data as u64
+        } else {
+            self.rva_to_offset(dir.address_of_raw_data)
+                .ok_or(Error::Malformed("debug data is not within a section"))?
+        };
+        Ok(start..start + dir.size_of_data as u64)
+    }
+
+    /// The data a debug directory entry refers to
+    pub fn debug_data(&self, dir: &DebugDirectory) -> Result<&'a [u8], Error> {
+        crate::elf_file::slice(self.data, self.debug_data_range(dir)?)
+    }
+
+    /// The CodeView (`RSDS`) record identifying the matching PDB
+    ///
+    /// Every debug directory entry is examined. If none of them is a CodeView record, the
+    /// `.buildid` section (emitted by GNU ld when `--build-id` is used) is examined.
+    pub fn codeview(&self) -> Result<CodeView<'a>, Error> {
+        let parse_entry = |dir: &DebugDirectory| CodeView::parse(self.debug_data(dir)?);
+        let err = match self
+            .debug_directory_data()
+            .and_then(|dirs| select_codeview(dirs, parse_entry))
+        {
+            Ok(cv) => return Ok(cv),
+            Err(e) => e,
+        };
+
+        match self.section_by_name(b".buildid") {
+            Some(s) => {
+                let d = crate::elf_file::slice(self.data, s.file_range())?;
+                buildid_section(d, CodeView::parse, parse_entry)
+            }
+            None => Err(err),
+        }
+    }
+
+    /// The hash stored in the `IMAGE_DEBUG_TYPE_REPRO` debug directory entry
+    ///
+    /// Images linked with `/Brepro` (MSVC link or lld-link) use this hash in place of timestamps.
+    /// Returns [`Error::NotFound`] if there is no such entry, or if it doesn't include a hash
+    /// (older linkers emit an empty entry).
+    pub fn repro_hash(&self) -> Result<&'a [u8], Error> {
+        // a truncated entry can only be the last one, and holds no hash
+        for dir in self.debug_directories()?.map_while(Result::ok) {
+            if dir.type_ == IMAGE_DEBUG_TYPE_REPRO {
+                return repro_hash(self.debug_data(&dir)?);
+            }
+        }
+        Err(Error::NotFound)
+    }
+}
+
+/// Extract the hash from the content of an `IMAGE_DEBUG_TYPE_REPRO` entry: a length, followed by
+/// the hash
+fn repro_hash(d: &[u8]) -> Result<&[u8], Error> {
+    if d.len() < 4 {
+        return Err(Error::NotFound);
+    }
+    let len = LE.u32(d) as usize;
+    d[4..].get(..len).ok_or(Error::Truncated)
+}
+
+/// Find the CodeView record in the content of a `.buildid` section
+///
+/// GNU ld places a debug directory entry in the section, followed by the CodeView record it
+/// refers to, which `parse_entry` is called with. A section holding the record itself is passed
+/// to `parse`.
+fn buildid_section<'d, T>(
+    d: &'d [u8],
+    parse: impl FnOnce(&'d [u8]) -> Result<T, Error>,
+    parse_entry: impl FnOnce(&DebugDirectory) -> Result<T, Error>,
+) -> Result<T, Error> {
+    if d.starts_with(b"RSDS") {
+        return parse(d);
+    }
+    let dir = DebugDirectory::parse(d)?;
+    if dir.type_ != IMAGE_DEBUG_TYPE_CODEVIEW {
+        return Err(Error::UnexpectedDebugType(dir.type_));
+    }
+    parse_entry(&dir)
+}
+
+/// Use the first CodeView entry in the debug directory `dirs` that `parse_entry` accepts
+///
+/// If there is none, the first problem seen is returned.
+fn select_codeview<T>(
+    dirs: &[u8],
+    mut parse_entry: impl FnMut(&DebugDirectory) -> Result<T, Error>,
+) -> Result<T, Error> {
+    let mut err = None;
+    for dir in dirs.chunks(DEBUG_DIRECTORY_SIZE) {
+        // a truncated entry can only be the last one
+        let dir = match DebugDirectory::parse(dir) {
+            Ok(dir) => dir,
+            Err(e) => {
+                err.get_or_insert(e);
+                break;
+            }
+        };
+        if dir.type_ != IMAGE_DEBUG_TYPE_CODEVIEW {
+            err.get_or_insert(Error::UnexpectedDebugType(dir.type_));
+            continue;
+        }
+        match parse_entry(&dir) {
+            Ok(v) => return Ok(v),
+            Err(e) => {
+                err.get_or_insert(e);
+            }
+        }
+    }
+    Err(err.unwrap_or(Error::NotFound))
+}
+
+/// Return the CodeView GUID of the PE file held in `data`
+///
+/// See [`PeFile::codeview()`].
+pub fn build_id(data: &[u8]) -> Result<BuildId, Error> {
+    Ok(PeFile::parse(data)?.codeview()?.guid)
+}
+
+/// Read the CodeView GUID of a PE file, without reading the whole file
+///
+/// Only the headers, the debug directory, and the debug data are read.
+#[cfg(feature = "std")]
+pub fn read_build_id<R: std::io::Read + std::io::Seek>(r: &mut R) -> Result<BuildId, Error> {
+    use crate::elf_file::read_range;
+
+    let dos = read_range(r, 0..E_LFANEW_OFFSET as u64 + 4)?;
+    if &dos[..2] != IMAGE_DOS_SIGNATURE {
+        return Err(Error::UnrecognizedFormat);
+    }
+    let nt = LE.u32(&dos[E_LFANEW_OFFSET..]) as u64;
+
+    // read enough to find the size of the optional header and the number of sections, then
+    // read everything up to the end of the section table
+    let fh = read_range(r, nt..nt + 4 + FILE_HEADER_SIZE as u64)?;
+    let nsections = LE.u16(&fh[6..]) as u64;
+    let opt_size = LE.u16(&fh[20..]) as u64;
+    let headers_end =
+        nt + 4 + FILE_HEADER_SIZE as u64 + opt_size + nsections * SECTION_HEADER_SIZE as u64;
+    let headers = read_range(r, 0..headers_end)?;
+    let pe = PeFile::parse(&headers)?;
+
+    let read_cv = |r: &mut R, dir: &DebugDirectory| -> Result<BuildId, Error> {
+        let d = read_range(r, pe.debug_data_range(dir)?)?;
+        Ok(CodeView::parse(&d)?.guid)
+    };
+
+    let dirs = match pe.debug_directory_range() {
+        Ok(Some(range)) => read_range(r, range),
+        Ok(None) => Err(Error::NoDebugDirectory),
+        Err(e) => Err(e),
+    };
+    let err = match dirs.and_then(|dirs| select_codeview(&dirs, |dir| read_cv(r, dir))) {
+        Ok(id) => return Ok(id),
+        Err(e) => e,
+    };
+
+    match pe.section_by_name(b".buildid") {
+        Some(s) => {
+            let d = read_range(r, s.file_range())?;
+            buildid_section(&d, |d| Ok(CodeView::parse(d)?.guid), |dir| read_cv(r, dir))
+        }
+        None => Err(err),
+    }
+}
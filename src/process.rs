@@ -0,0 +1,190 @@
+//! Examine the objects mapped into another running process, through `/proc/<pid>`
+//!
+//! [`modules()`] lists the files mapped into the process (from `/proc/<pid>/maps`), and reads the
+//! headers and notes of each ELF file out of the process's memory (`/proc/<pid>/mem`). As the
+//! notes come from memory rather than from the path in `maps`, the build-id is correct even if the
+//! file was deleted or replaced after it was mapped (for example, by a package upgrade).
+//!
+//! ```no_run
+//! # fn example(pid: u32) -> Result<(), buildid::Error> {
+//! for module in buildid::process::modules(pid)? {
+//!     println!("{:x?} {:?} {:?}", module.range(), module.build_id(), module.path());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Reading another process's memory needs the same permission as attaching a debugger to it. If
+//! it can't be read, the mapped file is read through `/proc/<pid>/map_files` instead, which needs
+//! `CAP_CHECKPOINT_RESTORE` (or `CAP_SYS_ADMIN`), but still finds deleted files.
+use crate::elf_file::{loaded_build_id, read_range};
+use crate::{BuildId, Error, Kind};
+use core::ops::Range;
+use std::ffi::OsStr;
+use std::format;
+use std::fs::File;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::string::String;
+use std::vec::Vec;
+
+const DELETED: &[u8] = b" (deleted)";
+
+/// A mapping of a process, from `/proc/<pid>/maps`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mapping {
+    /// Address range of the mapping
+    pub range: Range<u64>,
+    /// Permissions, such as `r-xp`
+    pub perms: String,
+    /// Offset in the file of the start of the mapping
+    pub offset: u64,
+    /// Inode of the file, or 0 for anonymous mappings
+    pub inode: u64,
+    /// Path of the file, or a description such as `[stack]` (or nothing) for other mappings
+    pub path: PathBuf,
+    /// The file was deleted (or replaced) after it was mapped
+    pub deleted: bool,
+}
+
+impl Mapping {
+    /// Parse a line of `/proc/<pid>/maps`
+    fn parse(line: &[u8]) -> Option<Self> {
+        let mut fields = line.splitn(6, |&c| c == b' ');
+        let mut field = || core::str::from_utf8(fields.next()?).ok();
+        let (start, end) = field()?.split_once('-')?;
+        let perms = field()?.into();
+        let offset = u64::from_str_radix(field()?, 16).ok()?;
+        let _dev = field()?;
+        let inode = field()?.parse().ok()?;
+        // the path is padded to line up, and may contain spaces of its own
+        let path = fields.next().unwrap_or_default();
+        let path = &path[path.iter().take_while(|&&c| c == b' ').count()..];
+        let deleted = path.starts_with(b"/") && path.ends_with(DELETED);
+        let path = match deleted {
+            true => &path[..path.len() - DELETED.len()],
+            false => path,
+        };
+        Some(Mapping {
+            range: u64::from_str_radix(start, 16).ok()?..u64::from_str_radix(end, 16).ok()?,
+            perms,
+            offset,
+            inode,
+            path: OsStr::from_bytes(path).into(),
+            deleted,
+        })
+    }
+}
+
+/// Read the mappings of the process `pid`, from `/proc/<pid>/maps`
+pub fn maps(pid: u32) -> Result<Vec<Mapping>, Error> {
+    let maps = std::fs::read(format!("/proc/{}/maps", pid))?;
+    maps.split(|&c| c == b'\n')
+        .filter(|l| !l.is_empty())
+        .map(|l| Mapping::parse(l).ok_or(Error::Malformed("unrecognized line in maps")))
+        .collect()
+}
+
+/// An ELF file (or the vDSO) mapped into a process, along with its build-id
+///
+/// Created by [`modules()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Module {
+    range: Range<u64>,
+    path: PathBuf,
+    deleted: bool,
+    build_id: Result<BuildId, Error>,
+}
+
+impl Module {
+    /// Address range from the start of the first mapping of the file to the end of the last
+    pub fn range(&self) -> Range<u64> {
+        self.range.clone()
+    }
+
+    /// Path of the file, as the kernel reported it (`[vdso]` for the vDSO)
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The file was deleted (or replaced) after it was mapped, so [`Module::path()`] no longer
+    /// refers to it
+    pub fn deleted(&self) -> bool {
+        self.deleted
+    }
+
+    /// The GNU build-id of the file, if it has one
+    pub fn build_id(&self) -> Option<BuildId> {
+        self.build_id.ok()
+    }
+
+    /// The GNU build-id of the file, or the reason it could not be found
+    pub fn try_build_id(&self) -> Result<BuildId, Error> {
+        self.build_id
+    }
+}
+
+/// Find the build-id of the ELF file whose headers are mapped at `addr` of `mem`
+fn build_id_at(mem: &mut File, addr: u64) -> Result<BuildId, Error> {
+    let (notes, id) = loaded_build_id(addr, |range| read_range(mem, range))?;
+    let id = &notes[id];
+    BuildId::new(Kind::Gnu, id).ok_or(Error::TooLong(id.len()))
+}
+
+/// Read the build-id from the file mapped at `range`, even if it was deleted
+fn read_map_file(pid: u32, range: &Range<u64>) -> Result<BuildId, Error> {
+    let path = format!("/proc/{}/map_files/{:x}-{:x}", pid, range.start, range.end);
+    let mut f = std::io::BufReader::new(File::open(path)?);
+    crate::elf_file::read_build_id(&mut f)
+}
+
+/// List the ELF files mapped into the process `pid`, along with their build-ids
+///
+/// Consecutive mappings of the same file are combined into one [`Module`]. Mapped files that
+/// are not ELF files are skipped.
+pub fn modules(pid: u32) -> Result<Vec<Module>, Error> {
+    let maps = maps(pid)?;
+    let mut mem = File::open(format!("/proc/{}/mem", pid));
+
+    let mut modules = Vec::new();
+    let mut rest = &maps[..];
+    while let [first, ..] = rest {
+        let len = rest
+            .iter()
+            .position(|m| m.path != first.path || m.inode != first.inode)
+            .unwrap_or(rest.len());
+        let (file, r) = rest.split_at(len);
+        rest = r;
+
+        let is_vdso = first.path.as_os_str() == "[vdso]";
+        if !first.path.starts_with("/") && !is_vdso {
+            continue;
+        }
+        let Some(header) = file.iter().find(|m| m.offset == 0) else {
+            continue;
+        };
+
+        let from_mem = match &mut mem {
+            Ok(mem) => build_id_at(mem, header.range.start),
+            Err(e) => Err(Error::Io(e.kind())),
+        };
+        let build_id = match from_mem {
+            Err(Error::Io(kind)) if !is_vdso => match read_map_file(pid, &header.range) {
+                Err(Error::Io(_)) => Err(Error::Io(kind)),
+                v => v,
+            },
+            v => v,
+        };
+        if build_id == Err(Error::UnrecognizedFormat) {
+            continue;
+        }
+
+        modules.push(Module {
+            range: first.range.start..file[file.len() - 1].range.end,
+            path: first.path.clone(),
+            deleted: first.deleted,
+            build_id,
+        });
+    }
+    Ok(modules)
+}
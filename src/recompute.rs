@@ -0,0 +1,387 @@
+//! Recompute the GNU build-id of an ELF file, to detect changes made after linking
+//!
+//! Linkers compute the build-id by hashing the output with the build-id note's descriptor set to
+//! zeros, and then store the hash in the descriptor. Tools that modify a file after linking
+//! (`chrpath`, `patchelf`, `strip`, or tampering) leave the build-id unchanged, so recomputing
+//! the hash reveals the change.
+//!
+//! Each linker hashes the file differently, so the [`Style`] used must match the linker and its
+//! `--build-id` option. [`verify()`] tries each style that produces a hash of the stored
+//! build-id's length.
+//!
+//! Build-ids generated with `--build-id=uuid` or `--build-id=0x...` are not derived from the
+//! file's content, and can't be recomputed.
+//!
+//! After editing a file, [`update()`] stores a freshly computed build-id, so that the build-id
+//! follows the content. The `buildid` command line tool (built with the `cli` feature) does the
+//! same with `buildid set FILE --recompute ld-sha1`.
+//!
+//! ```no_run
+//! # fn example(data: &[u8]) {
+//! match buildid::recompute::verify(data) {
+//!     Ok(style) => println!("unmodified (linked with {:?})", style),
+//!     Err(buildid::Error::HashMismatch) => println!("modified after linking"),
+//!     Err(e) => println!("could not check: {}", e),
+//! }
+//! # }
+//! ```
+use crate::blake3::Blake3;
+use crate::elf_file::{build_id_range, set_build_id, slice, Class, ElfFile, SHT_NOBITS};
+use crate::md5::Md5;
+use crate::sha1::Sha1;
+use crate::xxhash::{xxh3_64, xxh64};
+use crate::{BuildId, Error, Kind};
+use core::ops::Range;
+
+/// A way of computing the build-id from the content of the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Style {
+    /// GNU ld's `--build-id=sha1` (its default). Hashes the ELF header, program headers, section
+    /// headers, and the contents of most sections, with file offsets and the whole build-id note
+    /// set to zero.
+    LdSha1,
+    /// GNU ld's `--build-id=md5`, which hashes the same data as [`Style::LdSha1`]
+    LdMd5,
+    /// lld's `--build-id=sha1`. Despite the name, hashes each 1 MiB chunk of the file with
+    /// BLAKE3, and then the concatenation of those hashes, keeping the first 20 bytes.
+    LldSha1,
+    /// lld's `--build-id=md5`, which is [`Style::LldSha1`] keeping the first 16 bytes
+    LldMd5,
+    /// lld's `--build-id=fast` (its default) since lld 16, chunked like [`Style::LldSha1`] but
+    /// using XXH3
+    LldFast,
+    /// `--build-id=sha1` in older versions of lld, which used SHA-1 for each chunk
+    LldLegacySha1,
+    /// `--build-id=md5` in older versions of lld, which used MD5 for each chunk
+    LldLegacyMd5,
+    /// `--build-id=fast` before lld 16, which used XXH64 for each chunk
+    LldLegacyFast,
+    /// gold's `--build-id=sha1`, which hashes the whole file
+    GoldSha1,
+    /// gold's `--build-id=md5`, which hashes the whole file
+    GoldMd5,
+}
+
+impl Style {
+    /// All supported styles
+    pub const ALL: [Style; 10] = [
+        Style::LdSha1,
+        Style::LdMd5,
+        Style::LldSha1,
+        Style::LldMd5,
+        Style::LldFast,
+        Style::LldLegacySha1,
+        Style::LldLegacyMd5,
+        Style::LldLegacyFast,
+        Style::GoldSha1,
+        Style::GoldMd5,
+    ];
+
+    /// Length of the build-id this style produces
+    pub fn id_len(self) -> usize {
+        match self {
+            Style::LdSha1 | Style::LldSha1 | Style::LldLegacySha1 | Style::GoldSha1 => Sha1::LEN,
+            Style::LdMd5 | Style::LldMd5 | Style::LldLegacyMd5 | Style::GoldMd5 => Md5::LEN,
+            Style::LldFast | Style::LldLegacyFast => 8,
+        }
+    }
+}
+
+/// Bytes of a file, with `zero` (the build-id descriptor) replaced by zeros
+#[derive(Debug, Clone)]
+pub(crate) struct Masked<'a> {
+    pub(crate) data: &'a [u8],
+    pub(crate) zero: Range<usize>,
+}
+
+impl<'a> Masked<'a> {
+    pub(crate) fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub(crate) fn byte(&self, i: usize) -> u8 {
+        if self.zero.contains(&i) {
+            0
+        } else {
+            self.data[i]
+        }
+    }
+
+    fn bytes<const N: usize>(&self, off: usize) -> [u8; N] {
+        let mut b: [u8; N] = self.data[off..off + N].try_into().unwrap();
+        if off < self.zero.end && self.zero.start < off + N {
+            for (i, b) in b.iter_mut().enumerate() {
+                if self.zero.contains(&(off + i)) {
+                    *b = 0;
+                }
+            }
+        }
+        b
+    }
+
+    pub(crate) fn u32(&self, off: usize) -> u32 {
+        u32::from_le_bytes(self.bytes(off))
+    }
+
+    pub(crate) fn u64(&self, off: usize) -> u64 {
+        u64::from_le_bytes(self.bytes(off))
+    }
+
+    /// The bytes in `r`, with the same bytes zeroed
+    fn sub(&self, r: Range<usize>) -> Masked<'a> {
+        let (start, len) = (r.start, r.end - r.start);
+        let clamp = |v: usize| v.saturating_sub(start).min(len);
+        Masked {
+            data: &self.data[r],
+            zero: clamp(self.zero.start)..clamp(self.zero.end),
+        }
+    }
+
+    /// Pass the bytes to `f`, in pieces
+    fn feed<F: FnMut(&[u8])>(&self, mut f: F) {
+        const ZEROS: [u8; 64] = [0; 64];
+        let start = self.zero.start.min(self.data.len());
+        let end = self.zero.end.clamp(start, self.data.len());
+        f(&self.data[..start]);
+        let mut n = end - start;
+        while n > 0 {
+            let c = n.min(ZEROS.len());
+            f(&ZEROS[..c]);
+            n -= c;
+        }
+        f(&self.data[end..]);
+    }
+}
+
+/// A cryptographic hash that build-ids are computed with
+#[derive(Clone, Copy)]
+enum Digest {
+    Sha1,
+    Md5,
+    /// BLAKE3, keeping this many bytes of the output
+    Blake3(usize),
+}
+
+// only a couple are alive at once, so the size of BLAKE3's state doesn't matter
+#[allow(clippy::large_enum_variant)]
+enum Hasher {
+    Sha1(Sha1),
+    Md5(Md5),
+    Blake3(Blake3, usize),
+}
+
+impl Hasher {
+    fn new(digest: Digest) -> Self {
+        match digest {
+            Digest::Sha1 => Hasher::Sha1(Sha1::new()),
+            Digest::Md5 => Hasher::Md5(Md5::new()),
+            Digest::Blake3(len) => Hasher::Blake3(Blake3::new(), len),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha1(h) => h.update(data),
+            Hasher::Md5(h) => h.update(data),
+            Hasher::Blake3(h, _) => h.update(data),
+        }
+    }
+
+    fn update_masked(&mut self, input: &Masked<'_>) {
+        input.feed(|d| self.update(d));
+    }
+
+    /// Hash `input`, with the (sorted, non-overlapping) `fields` replaced by zeros. Each field is
+    /// an offset and a length of at most 8.
+    fn update_zeroed(&mut self, input: &Masked<'_>, fields: &[(usize, usize)]) {
+        let mut prev = 0;
+        for &(off, len) in fields {
+            self.update_masked(&input.sub(prev..off));
+            self.update(&[0u8; 8][..len]);
+            prev = off + len;
+        }
+        self.update_masked(&input.sub(prev..input.len()));
+    }
+
+    fn finish(self) -> BuildId {
+        match self {
+            Hasher::Sha1(h) => BuildId::new(Kind::Gnu, &h.finish()),
+            Hasher::Md5(h) => BuildId::new(Kind::Gnu, &h.finish()),
+            Hasher::Blake3(h, len) => BuildId::new(Kind::Gnu, &h.finish()[..len]),
+        }
+        .unwrap()
+    }
+}
+
+/// lld hashes the file in chunks of this size
+const LLD_CHUNK: usize = 1 << 20;
+
+fn lld_chunks<'a>(input: &'a Masked<'a>) -> impl Iterator<Item = Masked<'a>> + 'a {
+    let chunks = input.len().div_ceil(LLD_CHUNK);
+    (0..chunks).map(move |i| input.sub(i * LLD_CHUNK..((i + 1) * LLD_CHUNK).min(input.len())))
+}
+
+/// lld's tree hash: hash each chunk, then hash the concatenation of the chunk hashes
+fn lld(input: &Masked<'_>, digest: Digest) -> BuildId {
+    let mut top = Hasher::new(digest);
+    for chunk in lld_chunks(input) {
+        let mut h = Hasher::new(digest);
+        h.update_masked(&chunk);
+        top.update(h.finish().as_bytes());
+    }
+    top.finish()
+}
+
+/// lld's `fast` tree hash, using a 64-bit hash
+fn lld_fast(input: &Masked<'_>, hash: fn(Masked<'_>) -> u64) -> Result<BuildId, Error> {
+    // The chunk hashes have to be kept to hash them together. 16 KiB of them covers files up to
+    // 2 GiB.
+    let mut hashes = [0u8; 16 << 10];
+    let mut len = 0;
+    for chunk in lld_chunks(input) {
+        let h = hashes.get_mut(len..len + 8).ok_or(Error::Malformed(
+            "file is too large to recompute a fast build-id",
+        ))?;
+        h.copy_from_slice(&hash(chunk).to_le_bytes());
+        len += 8;
+    }
+    let top = hash(Masked {
+        data: &hashes[..len],
+        zero: 0..0,
+    });
+    Ok(BuildId::new(Kind::Gnu, &top.to_le_bytes()).unwrap())
+}
+
+const SHT_SYMTAB: u32 = 2;
+const SHT_SYMTAB_SHNDX: u32 = 18;
+const SHN_XINDEX: u16 = 0xffff;
+
+/// Length of the build-id note's header and name (`GNU\0`)
+const NOTE_HEADER_LEN: usize = 16;
+
+/// GNU ld's hash of the headers and the section contents, with file offsets zeroed
+fn ld(elf: &ElfFile<'_>, input: &Masked<'_>, digest: Digest) -> Result<BuildId, Error> {
+    let h = elf.header();
+    // offsets of e_phoff, e_shoff and sh_offset, and the size of each
+    let (phoff, shoff, sh_offset, word) = match h.class {
+        Class::Elf32 => (28, 32, 16, 4),
+        Class::Elf64 => (32, 40, 24, 8),
+    };
+    let range = |r: Range<u64>| -> Result<Masked, Error> {
+        slice(input.data, r.clone())?;
+        Ok(input.sub(r.start as usize..r.end as usize))
+    };
+
+    let mut hasher = Hasher::new(digest);
+    hasher.update_zeroed(
+        &range(0..h.class.ehdr_size() as u64)?,
+        &[(phoff, word), (shoff, word)],
+    );
+    let phnum = elf.phnum()?;
+    if phnum > 0 {
+        hasher.update_masked(&range(h.ph_table(phnum)?)?);
+    }
+    let shnum = elf.shnum()?;
+    if shnum == 0 {
+        return Ok(hasher.finish());
+    }
+
+    // The symbol table, its string table, and the section name string table are generated as the
+    // file is written, and their contents are not hashed.
+    let mut shstrndx = h.e_shstrndx as usize;
+    let mut strtab = None;
+    for (i, shdr) in elf.section_headers()?.enumerate() {
+        let shdr = shdr?;
+        if i == 0 && h.e_shstrndx == SHN_XINDEX {
+            shstrndx = shdr.sh_link as usize;
+        }
+        if shdr.sh_type == SHT_SYMTAB {
+            strtab = Some(shdr.sh_link as usize);
+        }
+    }
+    let table = range(h.sh_table(shnum)?)?;
+    let entsize = h.e_shentsize as usize;
+    for (i, shdr) in elf.section_headers()?.enumerate() {
+        let shdr = shdr?;
+        hasher.update_zeroed(
+            &table.sub(i * entsize..(i + 1) * entsize),
+            &[(sh_offset, word)],
+        );
+        let generated = match shdr.sh_type {
+            SHT_NOBITS | SHT_SYMTAB | SHT_SYMTAB_SHNDX => true,
+            _ => i == 0 || i == shstrndx || Some(i) == strtab,
+        };
+        if !generated {
+            hasher.update_masked(&range(shdr.file_range()?)?);
+        }
+    }
+    Ok(hasher.finish())
+}
+
+/// Compute the build-id of the ELF file held in `data` the way `style` does
+///
+/// The stored build-id note's descriptor is treated as zeros, as it was when the linker computed
+/// the hash. Returns [`Error::NotFound`] if the file has no build-id note.
+pub fn compute(data: &[u8], style: Style) -> Result<BuildId, Error> {
+    let elf = ElfFile::parse(data)?;
+    let zero = build_id_range(data)?;
+    // GNU ld hashes before writing any of the note: the header and name are zero too
+    let ld_input = Masked {
+        data,
+        zero: zero.start.saturating_sub(NOTE_HEADER_LEN)..zero.end,
+    };
+    let input = Masked { data, zero };
+
+    let whole = |digest: Digest| {
+        let mut h = Hasher::new(digest);
+        h.update_masked(&input);
+        h.finish()
+    };
+    Ok(match style {
+        Style::LdSha1 => ld(&elf, &ld_input, Digest::Sha1)?,
+        Style::LdMd5 => ld(&elf, &ld_input, Digest::Md5)?,
+        Style::LldSha1 => lld(&input, Digest::Blake3(Sha1::LEN)),
+        Style::LldMd5 => lld(&input, Digest::Blake3(Md5::LEN)),
+        Style::LldFast => lld_fast(&input, xxh3_64)?,
+        Style::LldLegacySha1 => lld(&input, Digest::Sha1),
+        Style::LldLegacyMd5 => lld(&input, Digest::Md5),
+        Style::LldLegacyFast => lld_fast(&input, xxh64)?,
+        Style::GoldSha1 => whole(Digest::Sha1),
+        Style::GoldMd5 => whole(Digest::Md5),
+    })
+}
+
+/// Check that the build-id stored in the ELF file held in `data` matches its content
+///
+/// Each [`Style`] producing a build-id of the stored length is tried. Returns the style that
+/// matched, or [`Error::HashMismatch`] if none do.
+pub fn verify(data: &[u8]) -> Result<Style, Error> {
+    let stored = crate::elf_file::build_id(data)?;
+    for style in Style::ALL {
+        if style.id_len() == stored.len() && compute(data, style)? == stored {
+            return Ok(style);
+        }
+    }
+    Err(Error::HashMismatch)
+}
+
+/// Recompute the build-id of the ELF file held in `data` and store it in the file
+///
+/// This makes the build-id follow the content again after the file is edited (for example, to
+/// normalize paths for a reproducible build). The result doesn't depend on the previously stored
+/// build-id. `style` must produce a build-id of the stored length, otherwise
+/// [`Error::LengthMismatch`] is returned.
+pub fn update(data: &mut [u8], style: Style) -> Result<BuildId, Error> {
+    let id = compute(data, style)?;
+    set_build_id(data, id.as_bytes())?;
+    Ok(id)
+}
+
+/// Like [`verify()`], for the ELF file at `path`
+///
+/// The whole file needs to be hashed, so the whole file is read.
+#[cfg(feature = "std")]
+pub fn verify_path<P: AsRef<std::path::Path>>(path: P) -> Result<Style, Error> {
+    verify(&std::fs::read(path)?)
+}
@@ -24,8 +24,8 @@ static NOTE_GNU_BUILD_ID_END: [u8; 0] = [];
 // 20 for GNU
 const BUILD_ID_LEN: usize = crate::constparse::parse_usize(env!("BUILD_ID_LEN"));
 
-pub fn build_id() -> Option<&'static [u8]> {
-    Some(unsafe {
+pub fn build_id() -> Result<&'static [u8], crate::Error> {
+    Ok(unsafe {
         core::slice::from_raw_parts(
             NOTE_GNU_BUILD_ID_END.as_ptr().sub(BUILD_ID_LEN),
             BUILD_ID_LEN,
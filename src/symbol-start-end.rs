@@ -3,11 +3,11 @@ extern "C" {
     static __build_id_end: [u8; 1];
 }
 
-pub fn build_id() -> Option<&'static [u8]> {
+pub fn build_id() -> Result<&'static [u8], crate::Error> {
     unsafe {
         let start = __build_id_start.as_ptr();
         let end = __build_id_end.as_ptr();
         let len = end.offset_from(start).try_into().unwrap();
-        Some(core::slice::from_raw_parts(start, len))
+        Ok(core::slice::from_raw_parts(start, len))
     }
 }
@@ -0,0 +1,369 @@
+//! Parse the `build_id` custom section out of WebAssembly modules held in memory
+//!
+//! The section is described by the [WebAssembly tool conventions][conv], and is emitted by
+//! `wasm-ld --build-id`. Its content is a single byte vector (a LEB128 length followed by the
+//! bytes).
+//!
+//! A module can't examine its own custom sections, so the runtime lookup on wasm
+//! ([`build_id()`](crate::build_id)) instead reads a static reserved by this crate. After linking,
+//! [`embed_build_id()`] copies the id from the custom section into that static.
+//!
+//! [conv]: https://github.com/WebAssembly/tool-conventions/blob/main/BuildId.md
+//!
+//! ```no_run
+//! # fn example(data: &[u8]) -> Result<(), buildid::Error> {
+//! let id = buildid::wasm_file::build_id(data)?;
+//! println!("{}", id);
+//! # Ok(())
+//! # }
+//! ```
+use crate::{BuildId, Error, Kind};
+
+const MAGIC: &[u8; 4] = b"\0asm";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 8;
+
+/// Name of the custom section holding the build-id
+pub const BUILD_ID_SECTION: &[u8] = b"build_id";
+
+/// Section id of custom sections
+pub const SECTION_CUSTOM: u8 = 0;
+/// Section id of the data section
+pub const SECTION_DATA: u8 = 11;
+
+/// Bytes identifying the static reserved for the build-id, inverted so that the copy used to
+/// search for it is never mistaken for the static itself
+const SLOT_MARKER_INV: [u8; 16] = {
+    let m = *b"buildid:wasm:v1\0";
+    let mut inv = [0; 16];
+    let mut i = 0;
+    while i < m.len() {
+        inv[i] = !m[i];
+        i += 1;
+    }
+    inv
+};
+
+/// Size of the static reserved for the build-id: the marker, a length, and the build-id
+pub(crate) const SLOT_SIZE: usize = SLOT_MARKER_INV.len() + 1 + BuildId::MAX_LEN;
+
+/// Offset of the length in the static reserved for the build-id
+pub(crate) const SLOT_LEN_OFFSET: usize = SLOT_MARKER_INV.len();
+
+/// Initial content of the static reserved for the build-id (a zero length)
+#[cfg_attr(not(any(target_family = "wasm", test)), allow(dead_code))]
+pub(crate) const EMPTY_SLOT: [u8; SLOT_SIZE] = {
+    let mut s = [0; SLOT_SIZE];
+    let mut i = 0;
+    while i < SLOT_MARKER_INV.len() {
+        s[i] = !SLOT_MARKER_INV[i];
+        i += 1;
+    }
+    s
+};
+
+/// Decode an unsigned LEB128 value of at most 32 bits from the start of `data`, returning it and
+/// the number of bytes used
+fn leb128_u32(data: &[u8]) -> Result<(u32, usize), Error> {
+    let mut v = 0u32;
+    for (i, &b) in data.iter().enumerate().take(5) {
+        v |= ((b & 0x7f) as u32) << (i * 7);
+        if b & 0x80 == 0 {
+            if i == 4 && b > 0x0f {
+                return Err(Error::Malformed("LEB128 value is too large"));
+            }
+            return Ok((v, i + 1));
+        }
+    }
+    if data.len() < 5 {
+        Err(Error::Truncated)
+    } else {
+        Err(Error::Malformed("LEB128 value is too large"))
+    }
+}
+
+/// Split a byte vector (LEB128 length and bytes) off the start of `data`
+fn vec(data: &[u8]) -> Result<(&[u8], &[u8]), Error> {
+    let (len, n) = leb128_u32(data)?;
+    let rest = &data[n..];
+    if rest.len() < len as usize {
+        return Err(Error::Truncated);
+    }
+    Ok(rest.split_at(len as usize))
+}
+
+/// Parse the content of a `build_id` custom section
+fn parse_build_id(payload: &[u8]) -> Result<BuildId, Error> {
+    let (id, _) = vec(payload)?;
+    if id.is_empty() {
+        return Err(Error::NotFound);
+    }
+    BuildId::new(Kind::Wasm, id).ok_or(Error::TooLong(id.len()))
+}
+
+/// A section of a WebAssembly module
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Section<'a> {
+    /// Section id. [`SECTION_CUSTOM`] for custom sections.
+    pub id: u8,
+    /// Offset of the section's content in the module
+    pub offset: usize,
+    /// Content of the section. For custom sections, the name is not included.
+    pub payload: &'a [u8],
+    /// Name of custom sections, and empty for others
+    pub name: &'a [u8],
+}
+
+/// Iterator over the sections of a module. See [`WasmFile::sections()`].
+#[derive(Debug, Clone)]
+pub struct Sections<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Sections<'a> {
+    fn section(&self) -> Result<(Section<'a>, usize), Error> {
+        let d = &self.data[self.offset..];
+        let id = d[0];
+        let (size, n) = leb128_u32(&d[1..])?;
+        let start = 1 + n;
+        let end = start.checked_add(size as usize).ok_or(Error::Truncated)?;
+        let content = d.get(start..end).ok_or(Error::Truncated)?;
+        let (name, payload) = if id == SECTION_CUSTOM {
+            vec(content)?
+        } else {
+            (&[][..], content)
+        };
+        let s = Section {
+            id,
+            offset: self.offset + start + (content.len() - payload.len()),
+            payload,
+            name,
+        };
+        Ok((s, end))
+    }
+}
+
+impl<'a> Iterator for Sections<'a> {
+    type Item = Result<Section<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.data.len() {
+            return None;
+        }
+        match self.section() {
+            Ok((s, len)) => {
+                self.offset += len;
+                Some(Ok(s))
+            }
+            Err(e) => {
+                // don't keep returning the same error
+                self.offset = self.data.len();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// A WebAssembly module (binary format)
+#[derive(Debug, Clone, Copy)]
+pub struct WasmFile<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> WasmFile<'a> {
+    /// Check the header of the module held in `data`
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        if data.len() < HEADER_SIZE || &data[..4] != MAGIC {
+            return Err(Error::UnrecognizedFormat);
+        }
+        if u32::from_le_bytes(data[4..8].try_into().unwrap()) != VERSION {
+            return Err(Error::Malformed("unsupported wasm version"));
+        }
+        Ok(WasmFile { data })
+    }
+
+    /// Iterate over the sections of the module
+    pub fn sections(&self) -> Sections<'a> {
+        Sections {
+            data: self.data,
+            offset: HEADER_SIZE,
+        }
+    }
+
+    /// Find the first custom section named `name`, returning its content
+    pub fn custom_section(&self, name: &[u8]) -> Result<Option<&'a [u8]>, Error> {
+        for s in self.sections() {
+            let s = s?;
+            if s.id == SECTION_CUSTOM && s.name == name {
+                return Ok(Some(s.payload));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The build-id stored in the `build_id` custom section
+    pub fn build_id(&self) -> Result<BuildId, Error> {
+        parse_build_id(
+            self.custom_section(BUILD_ID_SECTION)?
+                .ok_or(Error::NotFound)?,
+        )
+    }
+}
+
+/// Return the build-id of the module held in `data`
+///
+/// See [`WasmFile::build_id()`].
+pub fn build_id(data: &[u8]) -> Result<BuildId, Error> {
+    WasmFile::parse(data)?.build_id()
+}
+
+/// Copy the build-id from the `build_id` custom section into the static this crate reserves for
+/// it, so that [`build_id()`](crate::build_id) returns it when the module runs
+///
+/// Run this on the module after linking (with `-C link-arg=--build-id`). `module` is modified in
+/// place, and its size does not change. Running this again on the same module is harmless.
+///
+/// Returns [`Error::MissingSlot`] if the module does not use this crate's runtime lookup.
+pub fn embed_build_id(module: &mut [u8]) -> Result<BuildId, Error> {
+    let (id, data) = {
+        let f = WasmFile::parse(module)?;
+        let id = f.build_id()?;
+        let data = f
+            .sections()
+            .find(|s| s.map_or(true, |s| s.id == SECTION_DATA))
+            .ok_or(Error::MissingSlot)??;
+        (id, data.offset..data.offset + data.payload.len())
+    };
+
+    let marker = core::hint::black_box(SLOT_MARKER_INV);
+    let data = &mut module[data];
+    let pos = data
+        .windows(marker.len())
+        .position(|w| w.iter().zip(&marker).all(|(a, b)| *a == !*b))
+        .ok_or(Error::MissingSlot)?;
+    let slot = data
+        .get_mut(pos..pos + SLOT_SIZE)
+        .ok_or(Error::MissingSlot)?;
+    slot[SLOT_LEN_OFFSET] = id.len() as u8;
+    slot[SLOT_LEN_OFFSET + 1..][..id.len()].copy_from_slice(id.as_bytes());
+    Ok(id)
+}
+
+/// Read the build-id of a WebAssembly module, without reading the whole module
+///
+/// Only the section headers and the `build_id` section are read.
+#[cfg(feature = "std")]
+pub fn read_build_id<R: std::io::Read + std::io::Seek>(r: &mut R) -> Result<BuildId, Error> {
+    use std::io::{Read, SeekFrom};
+
+    fn read_leb128_u32<R: Read>(r: &mut R) -> Result<Option<u32>, Error> {
+        let mut buf = [0u8; 5];
+        for i in 0..buf.len() {
+            match r.read(&mut buf[i..i + 1])? {
+                0 if i == 0 => return Ok(None),
+                0 => return Err(Error::Truncated),
+                _ => {}
+            }
+            if buf[i] & 0x80 == 0 {
+                return leb128_u32(&buf[..i + 1]).map(|(v, _)| Some(v));
+            }
+        }
+        Err(Error::Malformed("LEB128 value is too large"))
+    }
+
+    // seeking past the end succeeds, so sections that claim to extend past it are only noticed
+    // by comparing them to its length
+    let file_len = r.seek(SeekFrom::End(0))?;
+    r.seek(SeekFrom::Start(0))?;
+    let mut header = [0u8; HEADER_SIZE];
+    r.read_exact(&mut header)?;
+    WasmFile::parse(&header)?;
+
+    loop {
+        let mut id = [0u8; 1];
+        if r.read(&mut id)? == 0 {
+            return Err(Error::NotFound);
+        }
+        let size = read_leb128_u32(r)?.ok_or(Error::Truncated)? as u64;
+        let start = r.stream_position()?;
+        if start + size > file_len {
+            return Err(Error::Truncated);
+        }
+        if id[0] == SECTION_CUSTOM {
+            let name_len = read_leb128_u32(r)?.ok_or(Error::Truncated)?;
+            if name_len as usize == BUILD_ID_SECTION.len() {
+                let mut name = [0u8; BUILD_ID_SECTION.len()];
+                r.read_exact(&mut name)?;
+                if name == BUILD_ID_SECTION {
+                    let pos = r.stream_position()?;
+                    let len = (start + size)
+                        .checked_sub(pos)
+                        .ok_or(Error::Malformed("section name extends past the section"))?;
+                    let payload = crate::elf_file::read_range(r, pos..pos + len)?;
+                    return parse_build_id(&payload);
+                }
+            }
+        }
+        r.seek(SeekFrom::Start(start + size))?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn leb128() {
+        assert_eq!(leb128_u32(&[0x08]).unwrap(), (8, 1));
+        assert_eq!(leb128_u32(&[0xe5, 0x8e, 0x26]).unwrap(), (624485, 3));
+        assert_eq!(
+            leb128_u32(&[0xff, 0xff, 0xff, 0xff, 0x0f]).unwrap(),
+            (u32::MAX, 5)
+        );
+        assert_eq!(leb128_u32(&[0x80, 0x80]), Err(Error::Truncated));
+        assert!(leb128_u32(&[0xff, 0xff, 0xff, 0xff, 0x1f]).is_err());
+    }
+
+    #[test]
+    fn embed() {
+        const ID: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+
+        let mut data = Vec::new();
+        // data segment count, then one active segment
+        data.extend_from_slice(&[1, 0, 0x41, 0, 0x0b]);
+        data.push((EMPTY_SLOT.len() + 4) as u8);
+        data.extend_from_slice(&[0xaa; 4]);
+        data.extend_from_slice(&EMPTY_SLOT);
+
+        let mut module = MAGIC.to_vec();
+        module.extend_from_slice(&VERSION.to_le_bytes());
+        module.push(SECTION_DATA);
+        module.push(data.len() as u8);
+        module.extend_from_slice(&data);
+        module.push(SECTION_CUSTOM);
+        module.push((1 + BUILD_ID_SECTION.len() + 1 + ID.len()) as u8);
+        module.push(BUILD_ID_SECTION.len() as u8);
+        module.extend_from_slice(BUILD_ID_SECTION);
+        module.push(ID.len() as u8);
+        module.extend_from_slice(&ID);
+
+        let mut twice = module.clone();
+        assert_eq!(embed_build_id(&mut module).unwrap(), &ID[..]);
+        let slot = module
+            .windows(SLOT_SIZE)
+            .find(|w| w[..SLOT_LEN_OFFSET] == EMPTY_SLOT[..SLOT_LEN_OFFSET])
+            .unwrap();
+        assert_eq!(slot[SLOT_LEN_OFFSET] as usize, ID.len());
+        assert_eq!(&slot[SLOT_LEN_OFFSET + 1..][..ID.len()], &ID[..]);
+
+        embed_build_id(&mut twice).unwrap();
+        embed_build_id(&mut twice).unwrap();
+        assert_eq!(twice, module);
+
+        let mut no_slot = module[..HEADER_SIZE].to_vec();
+        no_slot.extend_from_slice(&module[HEADER_SIZE + 2 + data.len()..]);
+        assert_eq!(embed_build_id(&mut no_slot), Err(Error::MissingSlot));
+    }
+}
@@ -0,0 +1,25 @@
+//! A wasm module can't read its own custom sections, so the build-id is copied into a static by
+//! `wasm_file::embed_build_id()` after linking.
+
+use crate::wasm_file::{EMPTY_SLOT, SLOT_LEN_OFFSET, SLOT_SIZE};
+use crate::Error;
+use core::cell::UnsafeCell;
+
+// `UnsafeCell` keeps the compiler from assuming the content is always `EMPTY_SLOT`
+struct Slot(UnsafeCell<[u8; SLOT_SIZE]>);
+
+// SAFETY: the slot is only modified before the module is instantiated
+unsafe impl Sync for Slot {}
+
+#[used]
+static SLOT: Slot = Slot(UnsafeCell::new(EMPTY_SLOT));
+
+pub fn build_id() -> Result<&'static [u8], Error> {
+    // SAFETY: see `Sync` impl above
+    let slot = unsafe { &*SLOT.0.get() };
+    match slot[SLOT_LEN_OFFSET] as usize {
+        // `embed_build_id()` has not been run on this module
+        0 => Err(Error::NotFound),
+        len => Ok(&slot[SLOT_LEN_OFFSET + 1..][..len]),
+    }
+}
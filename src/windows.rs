@@ -3,7 +3,7 @@
 // - `link.exe /DUMP /HEADERS .\target\debug\examples\simple.exe`
 //    - includes the `IMAGE_DEBUG_DIRECTORY` section pretty printed
 
-use log::error;
+use crate::Error;
 use winapi::um::libloaderapi::GetModuleHandleA;
 use winapi::um::winnt::IMAGE_DEBUG_DIRECTORY;
 use winapi::um::winnt::IMAGE_DEBUG_TYPE_CODEVIEW;
@@ -20,7 +20,7 @@ struct CV_INFO_PDB70 {
     // followed by pdb name
 }
 
-pub fn build_id() -> Option<&'static [u8]> {
+pub fn build_id() -> Result<&'static [u8], Error> {
     let module = unsafe { GetModuleHandleA(core::ptr::null_mut()) };
 
     let dos_header = unsafe { &*(module as *const IMAGE_DOS_HEADER) };
@@ -29,8 +29,7 @@ pub fn build_id() -> Option<&'static [u8]> {
     };
 
     if file_header.SizeOfOptionalHeader == 0 {
-        error!("no optional header found");
-        return None;
+        return Err(Error::NoOptionalHeader);
     }
 
     let opt_header = unsafe {
@@ -39,38 +38,42 @@ pub fn build_id() -> Option<&'static [u8]> {
     };
 
     if opt_header.NumberOfRvaAndSizes <= IMAGE_DIRECTORY_ENTRY_DEBUG.into() {
-        error!("IMAGE_DIRECTORY_ENTRY_DEBUG not included in executable");
-        return None;
+        // IMAGE_DIRECTORY_ENTRY_DEBUG not included in executable
+        return Err(Error::NoDebugDirectory);
     }
 
     let dir = &opt_header.DataDirectory[IMAGE_DIRECTORY_ENTRY_DEBUG as usize];
     if dir.Size == 0 {
-        error!("IMAGE_DIRECTORY_ENTRY_DEBUG is empty");
-        return None;
+        // IMAGE_DIRECTORY_ENTRY_DEBUG is empty
+        return Err(Error::NoDebugDirectory);
     }
 
-    let dbg_dir = unsafe {
-        &*((module as usize + dir.VirtualAddress as usize) as *const IMAGE_DEBUG_DIRECTORY)
+    let dbg_dirs = unsafe {
+        core::slice::from_raw_parts(
+            (module as usize + dir.VirtualAddress as usize) as *const IMAGE_DEBUG_DIRECTORY,
+            dir.Size as usize / core::mem::size_of::<IMAGE_DEBUG_DIRECTORY>(),
+        )
     };
 
-    // TODO: multiple debug directories can be present, we only examine the
-    // first one which is always the one we want. We could scan all of them.
-    if dbg_dir.Type != IMAGE_DEBUG_TYPE_CODEVIEW {
-        error!("wrong image type {:#x}", dbg_dir.Type);
-        return None;
-    }
+    // multiple debug directories can be present (for example, `/Brepro` adds one). Use the first
+    // CodeView one, but report the first problem we saw if there isn't a usable one.
+    let mut err = None;
+    for dbg_dir in dbg_dirs {
+        if dbg_dir.Type != IMAGE_DEBUG_TYPE_CODEVIEW {
+            err.get_or_insert(Error::UnexpectedDebugType(dbg_dir.Type));
+            continue;
+        }
 
-    let pdb_info = unsafe {
-        &*((module as usize + dbg_dir.AddressOfRawData as usize) as *const CV_INFO_PDB70)
-    };
-    // 0x53445352 == "RSDS"
-    if pdb_info.cv_signature != u32::from_le_bytes(*b"RSDS") {
-        error!(
-            "unexpected value for pdb_info cv_signature: got {:#x}",
-            pdb_info.cv_signature
-        );
-        None
-    } else {
-        Some(&pdb_info.signature[..])
+        let pdb_info = unsafe {
+            &*((module as usize + dbg_dir.AddressOfRawData as usize) as *const CV_INFO_PDB70)
+        };
+        // 0x53445352 == "RSDS"
+        if pdb_info.cv_signature != u32::from_le_bytes(*b"RSDS") {
+            err.get_or_insert(Error::UnexpectedCodeViewSignature(pdb_info.cv_signature));
+        } else {
+            return Ok(&pdb_info.signature[..]);
+        }
     }
+
+    Err(err.unwrap_or(Error::NotFound))
 }
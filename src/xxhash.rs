@@ -0,0 +1,295 @@
+//! XXH64 and XXH3 (64-bit, default secret, no seed), as used by lld's `--build-id=fast`
+
+use crate::recompute::Masked;
+
+const PRIME32_1: u64 = 0x9e37_79b1;
+const PRIME32_2: u64 = 0x85eb_ca77;
+const PRIME32_3: u64 = 0xc2b2_ae3d;
+const PRIME64_1: u64 = 0x9e37_79b1_85eb_ca87;
+const PRIME64_2: u64 = 0xc2b2_ae3d_27d4_eb4f;
+const PRIME64_3: u64 = 0x1656_67b1_9e37_79f9;
+const PRIME64_4: u64 = 0x85eb_ca77_c2b2_ae63;
+const PRIME64_5: u64 = 0x27d4_eb2f_1656_67c5;
+const PRIME_MX1: u64 = 0x1656_6791_9e37_79f9;
+const PRIME_MX2: u64 = 0x9fb2_1c65_1e98_df25;
+
+fn avalanche64(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(PRIME64_2);
+    h ^= h >> 29;
+    h = h.wrapping_mul(PRIME64_3);
+    h ^ (h >> 32)
+}
+
+fn round64(acc: u64, input: u64) -> u64 {
+    acc.wrapping_add(input.wrapping_mul(PRIME64_2))
+        .rotate_left(31)
+        .wrapping_mul(PRIME64_1)
+}
+
+fn merge_round64(acc: u64, v: u64) -> u64 {
+    (acc ^ round64(0, v))
+        .wrapping_mul(PRIME64_1)
+        .wrapping_add(PRIME64_4)
+}
+
+/// XXH64 with a seed of 0
+pub(crate) fn xxh64(input: Masked<'_>) -> u64 {
+    let len = input.len();
+    let mut p = 0;
+    let mut h = if len >= 32 {
+        let mut v = [
+            PRIME64_1.wrapping_add(PRIME64_2),
+            PRIME64_2,
+            0,
+            0u64.wrapping_sub(PRIME64_1),
+        ];
+        while p + 32 <= len {
+            for (i, v) in v.iter_mut().enumerate() {
+                *v = round64(*v, input.u64(p + i * 8));
+            }
+            p += 32;
+        }
+        let mut h = v[0]
+            .rotate_left(1)
+            .wrapping_add(v[1].rotate_left(7))
+            .wrapping_add(v[2].rotate_left(12))
+            .wrapping_add(v[3].rotate_left(18));
+        for v in v {
+            h = merge_round64(h, v);
+        }
+        h
+    } else {
+        PRIME64_5
+    };
+
+    h = h.wrapping_add(len as u64);
+    while p + 8 <= len {
+        h ^= round64(0, input.u64(p));
+        h = h
+            .rotate_left(27)
+            .wrapping_mul(PRIME64_1)
+            .wrapping_add(PRIME64_4);
+        p += 8;
+    }
+    if p + 4 <= len {
+        h ^= (input.u32(p) as u64).wrapping_mul(PRIME64_1);
+        h = h
+            .rotate_left(23)
+            .wrapping_mul(PRIME64_2)
+            .wrapping_add(PRIME64_3);
+        p += 4;
+    }
+    while p < len {
+        h ^= (input.byte(p) as u64).wrapping_mul(PRIME64_5);
+        h = h.rotate_left(11).wrapping_mul(PRIME64_1);
+        p += 1;
+    }
+    avalanche64(h)
+}
+
+const SECRET: [u8; 192] = [
+    0xb8, 0xfe, 0x6c, 0x39, 0x23, 0xa4, 0x4b, 0xbe, 0x7c, 0x01, 0x81, 0x2c, 0xf7, 0x21, 0xad, 0x1c,
+    0xde, 0xd4, 0x6d, 0xe9, 0x83, 0x90, 0x97, 0xdb, 0x72, 0x40, 0xa4, 0xa4, 0xb7, 0xb3, 0x67, 0x1f,
+    0xcb, 0x79, 0xe6, 0x4e, 0xcc, 0xc0, 0xe5, 0x78, 0x82, 0x5a, 0xd0, 0x7d, 0xcc, 0xff, 0x72, 0x21,
+    0xb8, 0x08, 0x46, 0x74, 0xf7, 0x43, 0x24, 0x8e, 0xe0, 0x35, 0x90, 0xe6, 0x81, 0x3a, 0x26, 0x4c,
+    0x3c, 0x28, 0x52, 0xbb, 0x91, 0xc3, 0x00, 0xcb, 0x88, 0xd0, 0x65, 0x8b, 0x1b, 0x53, 0x2e, 0xa3,
+    0x71, 0x64, 0x48, 0x97, 0xa2, 0x0d, 0xf9, 0x4e, 0x38, 0x19, 0xef, 0x46, 0xa9, 0xde, 0xac, 0xd8,
+    0xa8, 0xfa, 0x76, 0x3f, 0xe3, 0x9c, 0x34, 0x3f, 0xf9, 0xdc, 0xbb, 0xc7, 0xc7, 0x0b, 0x4f, 0x1d,
+    0x8a, 0x51, 0xe0, 0x4b, 0xcd, 0xb4, 0x59, 0x31, 0xc8, 0x9f, 0x7e, 0xc9, 0xd9, 0x78, 0x73, 0x64,
+    0xea, 0xc5, 0xac, 0x83, 0x34, 0xd3, 0xeb, 0xc3, 0xc5, 0x81, 0xa0, 0xff, 0xfa, 0x13, 0x63, 0xeb,
+    0x17, 0x0d, 0xdd, 0x51, 0xb7, 0xf0, 0xda, 0x49, 0xd3, 0x16, 0x55, 0x26, 0x29, 0xd4, 0x68, 0x9e,
+    0x2b, 0x16, 0xbe, 0x58, 0x7d, 0x47, 0xa1, 0xfc, 0x8f, 0xf8, 0xb8, 0xd1, 0x7a, 0xd0, 0x31, 0xce,
+    0x45, 0xcb, 0x3a, 0x8f, 0x95, 0x16, 0x04, 0x28, 0xaf, 0xd7, 0xfb, 0xca, 0xbb, 0x4b, 0x40, 0x7e,
+];
+
+const STRIPE_LEN: usize = 64;
+const SECRET_CONSUME_RATE: usize = 8;
+const STRIPES_PER_BLOCK: usize = (SECRET.len() - STRIPE_LEN) / SECRET_CONSUME_RATE;
+const BLOCK_LEN: usize = STRIPE_LEN * STRIPES_PER_BLOCK;
+
+fn secret64(off: usize) -> u64 {
+    u64::from_le_bytes(SECRET[off..off + 8].try_into().unwrap())
+}
+
+fn secret32(off: usize) -> u64 {
+    u32::from_le_bytes(SECRET[off..off + 4].try_into().unwrap()) as u64
+}
+
+fn mul128_fold64(a: u64, b: u64) -> u64 {
+    let p = a as u128 * b as u128;
+    (p as u64) ^ ((p >> 64) as u64)
+}
+
+fn avalanche3(mut h: u64) -> u64 {
+    h ^= h >> 37;
+    h = h.wrapping_mul(PRIME_MX1);
+    h ^ (h >> 32)
+}
+
+fn rrmxmx(mut h: u64, len: usize) -> u64 {
+    h ^= h.rotate_left(49) ^ h.rotate_left(24);
+    h = h.wrapping_mul(PRIME_MX2);
+    h ^= (h >> 35).wrapping_add(len as u64);
+    h = h.wrapping_mul(PRIME_MX2);
+    h ^ (h >> 28)
+}
+
+fn mix16(input: &Masked<'_>, p: usize, s: usize) -> u64 {
+    mul128_fold64(
+        input.u64(p) ^ secret64(s),
+        input.u64(p + 8) ^ secret64(s + 8),
+    )
+}
+
+fn accumulate_512(acc: &mut [u64; 8], input: &Masked<'_>, p: usize, s: usize) {
+    for i in 0..8 {
+        let v = input.u64(p + i * 8);
+        let k = v ^ secret64(s + i * 8);
+        acc[i ^ 1] = acc[i ^ 1].wrapping_add(v);
+        acc[i] = acc[i].wrapping_add((k & 0xffff_ffff).wrapping_mul(k >> 32));
+    }
+}
+
+fn scramble(acc: &mut [u64; 8]) {
+    let s = SECRET.len() - STRIPE_LEN;
+    for (i, a) in acc.iter_mut().enumerate() {
+        *a = (*a ^ (*a >> 47) ^ secret64(s + i * 8)).wrapping_mul(PRIME32_1);
+    }
+}
+
+fn long(input: &Masked<'_>) -> u64 {
+    let len = input.len();
+    let mut acc = [
+        PRIME32_3, PRIME64_1, PRIME64_2, PRIME64_3, PRIME64_4, PRIME32_2, PRIME64_5, PRIME32_1,
+    ];
+
+    let blocks = (len - 1) / BLOCK_LEN;
+    for b in 0..blocks {
+        for n in 0..STRIPES_PER_BLOCK {
+            accumulate_512(
+                &mut acc,
+                input,
+                b * BLOCK_LEN + n * STRIPE_LEN,
+                n * SECRET_CONSUME_RATE,
+            );
+        }
+        scramble(&mut acc);
+    }
+
+    let stripes = ((len - 1) - BLOCK_LEN * blocks) / STRIPE_LEN;
+    for n in 0..stripes {
+        accumulate_512(
+            &mut acc,
+            input,
+            blocks * BLOCK_LEN + n * STRIPE_LEN,
+            n * SECRET_CONSUME_RATE,
+        );
+    }
+    // the last stripe, which may overlap the previous one
+    accumulate_512(
+        &mut acc,
+        input,
+        len - STRIPE_LEN,
+        SECRET.len() - STRIPE_LEN - 7,
+    );
+
+    let mut h = (len as u64).wrapping_mul(PRIME64_1);
+    for i in 0..4 {
+        h = h.wrapping_add(mul128_fold64(
+            acc[2 * i] ^ secret64(11 + 16 * i),
+            acc[2 * i + 1] ^ secret64(11 + 16 * i + 8),
+        ));
+    }
+    avalanche3(h)
+}
+
+/// XXH3 (64-bit) with the default secret and a seed of 0
+pub(crate) fn xxh3_64(input: Masked<'_>) -> u64 {
+    let len = input.len();
+    match len {
+        0 => avalanche64(secret64(56) ^ secret64(64)),
+        1..=3 => {
+            let c = ((input.byte(0) as u64) << 16)
+                | ((input.byte(len >> 1) as u64) << 24)
+                | input.byte(len - 1) as u64
+                | ((len as u64) << 8);
+            avalanche64(c ^ (secret32(0) ^ secret32(4)))
+        }
+        4..=8 => {
+            let lo = input.u32(len - 4) as u64;
+            let hi = input.u32(0) as u64;
+            let v = lo.wrapping_add(hi << 32);
+            rrmxmx(v ^ (secret64(8) ^ secret64(16)), len)
+        }
+        9..=16 => {
+            let lo = input.u64(0) ^ (secret64(24) ^ secret64(32));
+            let hi = input.u64(len - 8) ^ (secret64(40) ^ secret64(48));
+            let acc = (len as u64)
+                .wrapping_add(lo.swap_bytes())
+                .wrapping_add(hi)
+                .wrapping_add(mul128_fold64(lo, hi));
+            avalanche3(acc)
+        }
+        17..=128 => {
+            let mut acc = (len as u64).wrapping_mul(PRIME64_1);
+            if len > 32 {
+                if len > 64 {
+                    if len > 96 {
+                        acc = acc.wrapping_add(mix16(&input, 48, 96));
+                        acc = acc.wrapping_add(mix16(&input, len - 64, 112));
+                    }
+                    acc = acc.wrapping_add(mix16(&input, 32, 64));
+                    acc = acc.wrapping_add(mix16(&input, len - 48, 80));
+                }
+                acc = acc.wrapping_add(mix16(&input, 16, 32));
+                acc = acc.wrapping_add(mix16(&input, len - 32, 48));
+            }
+            acc = acc.wrapping_add(mix16(&input, 0, 0));
+            acc = acc.wrapping_add(mix16(&input, len - 16, 16));
+            avalanche3(acc)
+        }
+        129..=240 => {
+            let mut acc = (len as u64).wrapping_mul(PRIME64_1);
+            for i in 0..8 {
+                acc = acc.wrapping_add(mix16(&input, 16 * i, 16 * i));
+            }
+            acc = avalanche3(acc);
+            for i in 8..len / 16 {
+                acc = acc.wrapping_add(mix16(&input, 16 * i, 16 * (i - 8) + 3));
+            }
+            // the last 16 bytes, using the end of the minimum secret size (136)
+            acc = acc.wrapping_add(mix16(&input, len - 16, 136 - 17));
+            avalanche3(acc)
+        }
+        _ => long(&input),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn m(data: &[u8]) -> Masked<'_> {
+        Masked { data, zero: 0..0 }
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(xxh64(m(b"")), 0xef46_db37_51d8_e999);
+        assert_eq!(xxh3_64(m(b"")), 0x2d06_8005_38d3_94c2);
+    }
+
+    #[test]
+    fn masked() {
+        let data: alloc::vec::Vec<u8> = (0..2000u32).map(|i| (i * 7) as u8).collect();
+        let mut zeroed = data.clone();
+        zeroed[1500..1520].fill(0);
+        let masked = Masked {
+            data: &data,
+            zero: 1500..1520,
+        };
+        assert_eq!(xxh3_64(masked.clone()), xxh3_64(m(&zeroed)));
+        assert_eq!(xxh64(masked), xxh64(m(&zeroed)));
+    }
+}
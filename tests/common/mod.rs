@@ -0,0 +1,299 @@
+//! Helpers for constructing small ELF files to use as test fixtures
+#![allow(dead_code)]
+
+pub const NT_GNU_BUILD_ID: u32 = 3;
+pub const PT_LOAD: u32 = 1;
+pub const PT_NOTE: u32 = 4;
+pub const SHT_PROGBITS: u32 = 1;
+pub const SHT_STRTAB: u32 = 3;
+pub const SHT_NOTE: u32 = 7;
+pub const ET_REL: u16 = 1;
+pub const ET_EXEC: u16 = 2;
+pub const ET_DYN: u16 = 3;
+
+fn align_up(v: usize, a: usize) -> usize {
+    v.div_ceil(a) * a
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Writer {
+    pub class64: bool,
+    pub big_endian: bool,
+}
+
+impl Writer {
+    pub fn u16(&self, out: &mut Vec<u8>, v: u16) {
+        if self.big_endian {
+            out.extend_from_slice(&v.to_be_bytes())
+        } else {
+            out.extend_from_slice(&v.to_le_bytes())
+        }
+    }
+
+    pub fn u32(&self, out: &mut Vec<u8>, v: u32) {
+        if self.big_endian {
+            out.extend_from_slice(&v.to_be_bytes())
+        } else {
+            out.extend_from_slice(&v.to_le_bytes())
+        }
+    }
+
+    pub fn u64(&self, out: &mut Vec<u8>, v: u64) {
+        if self.big_endian {
+            out.extend_from_slice(&v.to_be_bytes())
+        } else {
+            out.extend_from_slice(&v.to_le_bytes())
+        }
+    }
+
+    pub fn word(&self, out: &mut Vec<u8>, v: u64) {
+        if self.class64 {
+            self.u64(out, v)
+        } else {
+            self.u32(out, v as u32)
+        }
+    }
+
+    /// Encode a single note, padding the name and descriptor to `align`
+    pub fn note(&self, align: usize, name: &[u8], ty: u32, desc: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.u32(&mut out, name.len() as u32);
+        self.u32(&mut out, desc.len() as u32);
+        self.u32(&mut out, ty);
+        out.extend_from_slice(name);
+        out.resize(align_up(out.len(), align), 0);
+        out.extend_from_slice(desc);
+        out.resize(align_up(out.len(), align), 0);
+        out
+    }
+
+    pub fn build_id_note(&self, desc: &[u8]) -> Vec<u8> {
+        self.note(4, b"GNU\0", NT_GNU_BUILD_ID, desc)
+    }
+}
+
+/// A section (and optionally a segment) holding some content
+#[derive(Debug, Clone)]
+pub struct Blob {
+    pub name: String,
+    pub sh_type: u32,
+    pub align: u64,
+    pub data: Vec<u8>,
+    /// also describe this blob with a program header of this type
+    pub p_type: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ElfBuilder {
+    pub w: Writer,
+    pub e_type: u16,
+    /// emit section headers
+    pub sections: bool,
+    pub blobs: Vec<Blob>,
+}
+
+impl ElfBuilder {
+    pub fn new(class64: bool, big_endian: bool) -> Self {
+        ElfBuilder {
+            w: Writer {
+                class64,
+                big_endian,
+            },
+            e_type: ET_DYN,
+            sections: true,
+            blobs: Vec::new(),
+        }
+    }
+
+    /// Add a note section, also covered by a PT_NOTE segment
+    pub fn note_segment(mut self, name: &str, align: u64, data: Vec<u8>) -> Self {
+        self.blobs.push(Blob {
+            name: name.into(),
+            sh_type: SHT_NOTE,
+            align,
+            data,
+            p_type: Some(PT_NOTE),
+        });
+        self
+    }
+
+    /// Add a note section which is not described by any program header
+    pub fn note_section(mut self, name: &str, align: u64, data: Vec<u8>) -> Self {
+        self.blobs.push(Blob {
+            name: name.into(),
+            sh_type: SHT_NOTE,
+            align,
+            data,
+            p_type: None,
+        });
+        self
+    }
+
+    pub fn section(mut self, name: &str, sh_type: u32, data: Vec<u8>) -> Self {
+        self.blobs.push(Blob {
+            name: name.into(),
+            sh_type,
+            align: 1,
+            data,
+            p_type: None,
+        });
+        self
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let w = self.w;
+        let (ehsize, phentsize, shentsize) = if w.class64 {
+            (64, 56, 64)
+        } else {
+            (52, 32, 40)
+        };
+
+        let load = self.e_type != ET_REL;
+        let phnum = if load {
+            1 + self.blobs.iter().filter(|b| b.p_type.is_some()).count()
+        } else {
+            0
+        };
+        let phoff = if phnum > 0 { ehsize } else { 0 };
+
+        // lay out blob contents
+        let mut off = ehsize + phnum * phentsize;
+        let mut offsets = Vec::new();
+        for b in &self.blobs {
+            off = align_up(off, b.align.max(1) as usize);
+            offsets.push(off);
+            off += b.data.len();
+        }
+
+        let mut shstrtab = vec![0u8];
+        let mut names = Vec::new();
+        for b in &self.blobs {
+            names.push(shstrtab.len() as u32);
+            shstrtab.extend_from_slice(b.name.as_bytes());
+            shstrtab.push(0);
+        }
+        let shstrtab_name = shstrtab.len() as u32;
+        shstrtab.extend_from_slice(b".shstrtab\0");
+        let shstrtab_off = off;
+        off += shstrtab.len();
+
+        let shnum = if self.sections {
+            self.blobs.len() + 2
+        } else {
+            0
+        };
+        let shoff = if self.sections { align_up(off, 8) } else { 0 };
+        let total = if self.sections {
+            shoff + shnum * shentsize
+        } else {
+            off
+        };
+
+        let mut out = Vec::new();
+        out.extend_from_slice(b"\x7fELF");
+        out.push(if w.class64 { 2 } else { 1 });
+        out.push(if w.big_endian { 2 } else { 1 });
+        out.push(1);
+        out.resize(16, 0);
+        w.u16(&mut out, self.e_type);
+        // EM_X86_64 or EM_PPC
+        w.u16(&mut out, if w.big_endian { 20 } else { 62 });
+        w.u32(&mut out, 1);
+        w.word(&mut out, 0);
+        w.word(&mut out, phoff as u64);
+        w.word(&mut out, shoff as u64);
+        w.u32(&mut out, 0);
+        w.u16(&mut out, ehsize as u16);
+        w.u16(&mut out, phentsize as u16);
+        w.u16(&mut out, phnum as u16);
+        w.u16(&mut out, shentsize as u16);
+        w.u16(&mut out, shnum as u16);
+        w.u16(&mut out, if self.sections { shnum as u16 - 1 } else { 0 });
+        assert_eq!(out.len(), ehsize);
+
+        let phdr = |out: &mut Vec<u8>, p_type: u32, off: u64, size: u64, align: u64| {
+            if w.class64 {
+                w.u32(out, p_type);
+                w.u32(out, 4);
+                w.u64(out, off);
+                w.u64(out, off);
+                w.u64(out, off);
+                w.u64(out, size);
+                w.u64(out, size);
+                w.u64(out, align);
+            } else {
+                w.u32(out, p_type);
+                w.u32(out, off as u32);
+                w.u32(out, off as u32);
+                w.u32(out, off as u32);
+                w.u32(out, size as u32);
+                w.u32(out, size as u32);
+                w.u32(out, 4);
+                w.u32(out, align as u32);
+            }
+        };
+
+        if load {
+            phdr(&mut out, PT_LOAD, 0, total as u64, 0x1000);
+            for (b, o) in self.blobs.iter().zip(&offsets) {
+                if let Some(p_type) = b.p_type {
+                    phdr(&mut out, p_type, *o as u64, b.data.len() as u64, b.align);
+                }
+            }
+        }
+
+        for (b, o) in self.blobs.iter().zip(&offsets) {
+            out.resize(*o, 0);
+            out.extend_from_slice(&b.data);
+        }
+        out.resize(shstrtab_off, 0);
+        out.extend_from_slice(&shstrtab);
+
+        if self.sections {
+            out.resize(shoff, 0);
+            let shdr = |out: &mut Vec<u8>,
+                        name: u32,
+                        sh_type: u32,
+                        addr: u64,
+                        off: u64,
+                        size: u64,
+                        align: u64| {
+                w.u32(out, name);
+                w.u32(out, sh_type);
+                w.word(out, 2);
+                w.word(out, addr);
+                w.word(out, off);
+                w.word(out, size);
+                w.u32(out, 0);
+                w.u32(out, 0);
+                w.word(out, align);
+                w.word(out, 0);
+            };
+            out.resize(out.len() + shentsize, 0);
+            for ((b, o), n) in self.blobs.iter().zip(&offsets).zip(&names) {
+                let addr = if load { *o as u64 } else { 0 };
+                shdr(
+                    &mut out,
+                    *n,
+                    b.sh_type,
+                    addr,
+                    *o as u64,
+                    b.data.len() as u64,
+                    b.align,
+                );
+            }
+            shdr(
+                &mut out,
+                shstrtab_name,
+                SHT_STRTAB,
+                0,
+                shstrtab_off as u64,
+                shstrtab.len() as u64,
+                1,
+            );
+        }
+        assert_eq!(out.len(), total);
+
+        out
+    }
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use buildid::core_file::{CoreFile, MappedFile, NT_FILE};
+use buildid::Error;
+use common::{Blob, ElfBuilder, Writer, SHT_PROGBITS};
+
+const ET_CORE: u16 = 4;
+const ID: &[u8] = &[0x5a; 20];
+
+/// An `NT_FILE` note listing `(start, end, page offset, path)` mappings, with 4 KiB pages
+fn nt_file(w: Writer, files: &[(u64, u64, u64, &str)]) -> Vec<u8> {
+    let mut desc = Vec::new();
+    w.word(&mut desc, files.len() as u64);
+    w.word(&mut desc, 0x1000);
+    for (start, end, page_offset, _) in files {
+        w.word(&mut desc, *start);
+        w.word(&mut desc, *end);
+        w.word(&mut desc, *page_offset);
+    }
+    for (.., path) in files {
+        desc.extend_from_slice(path.as_bytes());
+        desc.push(0);
+    }
+    w.note(4, b"CORE\0", NT_FILE, &desc)
+}
+
+/// A core dump whose memory (at the same addresses as its file offsets) holds a shared library
+/// at 0x1000 and some other file after it
+fn core(class64: bool, big_endian: bool) -> Vec<u8> {
+    let b = ElfBuilder::new(class64, big_endian);
+    let lib = b
+        .clone()
+        .note_segment(".note.gnu.build-id", 4, b.w.build_id_note(ID))
+        .build();
+    let lib_end = 0x1000 + lib.len() as u64;
+    let data_start = lib_end.div_ceil(0x100) * 0x100;
+
+    let note = nt_file(
+        b.w,
+        &[
+            (0x1000, lib_end, 0, "/lib/libfoo.so"),
+            (lib_end, lib_end + 0x2000, 1, "/lib/libfoo.so"),
+            (data_start, data_start + 0x100, 0, "/usr/share/data"),
+            (0x7000_0000, 0x7000_1000, 0, "/lib/libbar.so"),
+        ],
+    );
+    let mut core = b.note_segment("", 4, note);
+    core.e_type = ET_CORE;
+    core.sections = false;
+    for (name, align, data) in [("lib", 0x1000, lib), ("data", 0x100, b"text".to_vec())] {
+        core.blobs.push(Blob {
+            name: name.into(),
+            sh_type: SHT_PROGBITS,
+            align,
+            data,
+            p_type: None,
+        });
+    }
+    core.build()
+}
+
+#[test]
+fn modules() {
+    for class64 in [false, true] {
+        for big_endian in [false, true] {
+            let data = core(class64, big_endian);
+            let core = CoreFile::parse(&data).unwrap();
+
+            let files: Vec<MappedFile> = core.mapped_files().unwrap().map(Result::unwrap).collect();
+            assert_eq!(files.len(), 4);
+            assert_eq!(files[1].offset, 0x1000);
+            assert_eq!(files[3].path, b"/lib/libbar.so");
+
+            let modules: Vec<_> = core.modules().unwrap().map(Result::unwrap).collect();
+            // the data file isn't an ELF file
+            assert_eq!(modules.len(), 2);
+            assert_eq!(modules[0].path(), b"/lib/libfoo.so");
+            assert_eq!(modules[0].range().start, 0x1000);
+            assert_eq!(modules[0].range().end, files[1].range.end);
+            assert_eq!(modules[0].build_id(), Some(ID));
+            let addr = modules[0].build_id_addr().unwrap();
+            assert_eq!(core.memory(addr).unwrap().unwrap()[..ID.len()], *ID);
+
+            assert_eq!(modules[1].path(), b"/lib/libbar.so");
+            assert_eq!(modules[1].try_build_id(), Err(Error::NotCaptured));
+            assert_eq!(modules[1].build_id_addr(), None);
+        }
+    }
+}
+
+#[test]
+fn not_a_core() {
+    let data = ElfBuilder::new(true, false).build();
+    assert!(matches!(CoreFile::parse(&data), Err(Error::Malformed(_))));
+
+    let mut b = ElfBuilder::new(true, false);
+    b.e_type = ET_CORE;
+    let data = b.build();
+    let core = CoreFile::parse(&data).unwrap();
+    assert_eq!(core.mapped_files().err(), Some(Error::NotFound));
+}
+
+#[test]
+fn note_segment_not_captured() {
+    // the first note segment is past the part of the library in the core dump, but the one with
+    // the build-id is in it
+    let b = ElfBuilder::new(true, false);
+    let mut lib = b
+        .clone()
+        .note_segment(".note.gnu.build-id", 4, b.w.build_id_note(ID))
+        .note_segment(".note.other", 4, b.w.note(4, b"GNU\0", 1, &[0; 16]))
+        .build();
+    let other = u64::from_le_bytes(lib[64 + 2 * 56 + 8..][..8].try_into().unwrap());
+    let (one, two) = lib[64 + 56..64 + 3 * 56].split_at_mut(56);
+    one.swap_with_slice(two);
+
+    let note = nt_file(
+        b.w,
+        &[(0x1000, 0x1000 + lib.len() as u64, 0, "/lib/libfoo.so")],
+    );
+    let mut core = b.note_segment("", 4, note);
+    core.e_type = ET_CORE;
+    core.sections = false;
+    core.blobs.push(Blob {
+        name: "lib".into(),
+        sh_type: SHT_PROGBITS,
+        align: 0x1000,
+        data: lib[..other as usize].to_vec(),
+        p_type: None,
+    });
+    let data = core.build();
+    let core = CoreFile::parse(&data).unwrap();
+    let modules: Vec<_> = core.modules().unwrap().map(Result::unwrap).collect();
+    assert_eq!(modules.len(), 1);
+    assert_eq!(modules[0].build_id(), Some(ID));
+}
@@ -0,0 +1,9 @@
+	.text
+	.globl _start
+_start:
+	mov $60, %eax
+	xor %edi, %edi
+	syscall
+
+	.section .rodata
+	.string "buildid recompute fixture"
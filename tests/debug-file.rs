@@ -0,0 +1,134 @@
+mod common;
+
+use buildid::debug_file::{self, DebugDirs, FileType, Mismatch};
+use buildid::{BuildId, Error};
+use common::ElfBuilder;
+use std::path::Path;
+
+const ID: &[u8] = &[
+    0xab, 0xcd, 0xef, 0x01, 0x23, 0x45, 0x67, 0x89, 0x00, 0x11, 0x22,
+];
+
+fn write_elf(path: &Path, id: &[u8]) {
+    let b = ElfBuilder::new(true, false);
+    let note = b.w.build_id_note(id);
+    let data = b.note_segment(".note.gnu.build-id", 4, note).build();
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, data).unwrap();
+}
+
+#[test]
+fn search_path() {
+    let dirs = DebugDirs::from_search_path("/usr/lib/debug::/opt/debug");
+    assert_eq!(dirs.dirs().len(), 2);
+
+    let id = BuildId::new(buildid::Kind::Gnu, ID).unwrap();
+    let c: Vec<_> = dirs.candidates(&id, FileType::Debug).collect();
+    assert_eq!(
+        c,
+        [
+            Path::new("/usr/lib/debug/.build-id/ab/cdef0123456789001122.debug"),
+            Path::new("/opt/debug/.build-id/ab/cdef0123456789001122.debug"),
+        ]
+    );
+    assert_eq!(DebugDirs::default().dirs(), [Path::new("/usr/lib/debug")]);
+}
+
+#[test]
+fn find() {
+    let root = std::env::temp_dir().join(format!("buildid-debug-{}", std::process::id()));
+    let id = BuildId::new(buildid::Kind::Gnu, ID).unwrap();
+
+    let mut dirs = DebugDirs::new();
+    dirs.push(root.join("missing"));
+    dirs.push(root.join("stale"));
+    dirs.push(root.join("good"));
+
+    // a file at the right path, but with a different build-id, is skipped
+    let mut other = ID.to_vec();
+    other[10] = 0;
+    write_elf(
+        &root.join("stale/.build-id/ab/cdef0123456789001122.debug"),
+        &other,
+    );
+    assert_eq!(dirs.find(&id, FileType::Debug), None);
+
+    let good = root.join("good/.build-id/ab/cdef0123456789001122.debug");
+    write_elf(&good, ID);
+    assert_eq!(dirs.find(&id, FileType::Debug), Some(good));
+    assert_eq!(dirs.find(&id, FileType::Executable), None);
+
+    let exe = root.join("good/.build-id/ab/cdef0123456789001122");
+    write_elf(&exe, ID);
+    assert_eq!(dirs.find(&id, FileType::Executable), Some(exe));
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+fn elf(id: Option<&[u8]>) -> Vec<u8> {
+    let b = ElfBuilder::new(true, false);
+    match id {
+        Some(id) => {
+            let note = b.w.build_id_note(id);
+            b.note_segment(".note.gnu.build-id", 4, note).build()
+        }
+        None => b.build(),
+    }
+}
+
+#[test]
+fn verify() {
+    let id = elf(Some(ID));
+    let sha1 = elf(Some(&[0x11; 20]));
+    let fast = elf(Some(&[0x11; 8]));
+    let none = elf(None);
+
+    assert_eq!(debug_file::verify(&id, &id).unwrap(), ID);
+    assert_eq!(
+        debug_file::verify(&none, &none),
+        Err(Error::Mismatch(Mismatch::NoBuildIds))
+    );
+    assert_eq!(
+        debug_file::verify(&none, &id),
+        Err(Error::Mismatch(Mismatch::MissingInObject))
+    );
+    assert_eq!(
+        debug_file::verify(&id, &none),
+        Err(Error::Mismatch(Mismatch::MissingInDebug))
+    );
+    assert_eq!(
+        debug_file::verify(&sha1, &fast),
+        Err(Error::Mismatch(Mismatch::DifferentLengths {
+            object: 20,
+            debug: 8
+        }))
+    );
+    let mut other = ID.to_vec();
+    other[0] = 0;
+    assert_eq!(
+        debug_file::verify(&id, &elf(Some(&other))),
+        Err(Error::Mismatch(Mismatch::Different))
+    );
+    assert_eq!(
+        debug_file::verify(&id, b"not an elf file"),
+        Err(Error::UnrecognizedFormat)
+    );
+
+    let root = std::env::temp_dir().join(format!("buildid-verify-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("app"), &id).unwrap();
+    std::fs::write(root.join("app.debug"), &id).unwrap();
+    std::fs::write(root.join("other.debug"), &sha1).unwrap();
+    assert_eq!(
+        debug_file::verify_paths(root.join("app"), root.join("app.debug")).unwrap(),
+        ID
+    );
+    assert_eq!(
+        debug_file::verify_paths(root.join("app"), root.join("other.debug")),
+        Err(Error::Mismatch(Mismatch::DifferentLengths {
+            object: 11,
+            debug: 20
+        }))
+    );
+    std::fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,151 @@
+mod common;
+
+use buildid::debug_file::DebugDirs;
+use buildid::debug_link::{self, DebugLink};
+use buildid::elf_file::ElfFile;
+use common::{ElfBuilder, ET_REL, SHT_PROGBITS};
+use std::path::Path;
+
+const ID: &[u8] = &[
+    0xab, 0xcd, 0xef, 0x01, 0x23, 0x45, 0x67, 0x89, 0x00, 0x11, 0x22,
+];
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn debuglink(b: &ElfBuilder, name: &str, crc: u32) -> Vec<u8> {
+    let mut out = name.as_bytes().to_vec();
+    out.push(0);
+    out.resize((out.len() + 3) & !3, 0);
+    b.w.u32(&mut out, crc);
+    out
+}
+
+fn debugaltlink(name: &str, id: &[u8]) -> Vec<u8> {
+    let mut out = name.as_bytes().to_vec();
+    out.push(0);
+    out.extend_from_slice(id);
+    out
+}
+
+/// A debug file with a build-id, and a `.gnu_debugaltlink` referring to `alt`
+fn debug_file(alt: &Path) -> Vec<u8> {
+    let b = ElfBuilder::new(true, false);
+    let note = b.w.build_id_note(ID);
+    b.note_segment(".note.gnu.build-id", 4, note)
+        .section(
+            ".gnu_debugaltlink",
+            SHT_PROGBITS,
+            debugaltlink(alt.to_str().unwrap(), &[0x55; 20]),
+        )
+        .build()
+}
+
+/// A stripped file with a `.gnu_debuglink` referring to the debug file `name`
+fn stripped(big_endian: bool, name: &str, crc: u32) -> Vec<u8> {
+    let b = ElfBuilder::new(true, big_endian);
+    let link = debuglink(&b, name, crc);
+    b.section(".gnu_debuglink", SHT_PROGBITS, link).build()
+}
+
+#[test]
+fn parse() {
+    for big_endian in [false, true] {
+        let data = stripped(big_endian, "app.debug", 0x12345678);
+        let elf = ElfFile::parse(&data).unwrap();
+        let link = elf.debuglink().unwrap().unwrap();
+        assert_eq!(link.file_name, b"app.debug");
+        assert_eq!(link.crc, 0x12345678);
+        assert_eq!(elf.debugaltlink().unwrap(), None);
+
+        let read = debug_link::read_debug_links(&mut std::io::Cursor::new(&data)).unwrap();
+        assert_eq!(read.debuglink().unwrap(), Some(link));
+        assert_eq!(read.debugaltlink().unwrap(), None);
+    }
+
+    let data = debug_file(Path::new("/usr/lib/debug/.dwz/x.debug"));
+    let elf = ElfFile::parse(&data).unwrap();
+    assert_eq!(elf.debuglink().unwrap(), None);
+    let alt = elf.debugaltlink().unwrap().unwrap();
+    assert_eq!(alt.file_name, b"/usr/lib/debug/.dwz/x.debug");
+    assert_eq!(alt.build_id, &[0x55; 20][..]);
+}
+
+#[test]
+fn no_section_header_table() {
+    // a header that claims one section, and that the section name string table is section 1,
+    // with no section header table and no e_shentsize
+    let mut b = ElfBuilder::new(true, false);
+    b.e_type = ET_REL;
+    b.sections = false;
+    let mut header = b.build();
+    header.truncate(64);
+    header[58..64].copy_from_slice(&[0, 0, 1, 0, 1, 0]);
+
+    let read = debug_link::read_debug_links(&mut std::io::Cursor::new(&header)).unwrap();
+    assert_eq!(read.debuglink().unwrap(), None);
+    assert_eq!(read.debugaltlink().unwrap(), None);
+}
+
+#[test]
+fn find() {
+    let root = std::env::temp_dir().join(format!("buildid-debuglink-{}", std::process::id()));
+    let bin = root.join("usr/bin");
+    let debug_root = root.join("usr/lib/debug");
+    std::fs::create_dir_all(&bin).unwrap();
+
+    // the supplementary file, only reachable through its build-id
+    let b = ElfBuilder::new(true, false);
+    let note = b.w.build_id_note(&[0x55; 20]);
+    let alt = b.note_segment(".note.gnu.build-id", 4, note).build();
+    let alt_path = debug_root.join(".build-id/55/55555555555555555555555555555555555555.debug");
+    std::fs::create_dir_all(alt_path.parent().unwrap()).unwrap();
+    std::fs::write(&alt_path, &alt).unwrap();
+
+    // the debug file, stored under the debug directory
+    let debug = debug_file(Path::new("../.dwz/missing.debug"));
+    let debug_path = debug_root
+        .join(bin.strip_prefix("/").unwrap())
+        .join("app.debug");
+    std::fs::create_dir_all(debug_path.parent().unwrap()).unwrap();
+    std::fs::write(&debug_path, &debug).unwrap();
+
+    let crc = crc32(&debug);
+    let link = DebugLink {
+        file_name: b"app.debug",
+        crc,
+    };
+    assert!(link.matches(&debug));
+    assert!(!link.matches(&alt));
+
+    let exe = bin.join("app");
+    std::fs::write(&exe, stripped(false, "app.debug", crc)).unwrap();
+    // a stale copy next to the executable is skipped
+    std::fs::write(bin.join("app.debug"), b"stale").unwrap();
+
+    let dirs = DebugDirs::from_search_path(debug_root.to_str().unwrap());
+    let links = debug_link::read_debug_links(&mut std::fs::File::open(&exe).unwrap()).unwrap();
+    assert_eq!(links.debuglink().unwrap(), Some(link));
+    let found = dirs.find_debuglink(&exe, &link).unwrap();
+    assert_eq!(found, debug_path);
+
+    // the path in the debug file doesn't exist, so the `.build-id` directory is used
+    let debug = ElfFile::parse(&debug).unwrap();
+    let alt_link = debug.debugaltlink().unwrap().unwrap();
+    assert!(alt_link.matches(&alt));
+    assert_eq!(dirs.find_debugaltlink(&found, &alt_link), Some(alt_path));
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,268 @@
+mod common;
+
+use buildid::elf_file::{self, Class, ElfFile};
+use buildid::{Endian, Error};
+use common::{ElfBuilder, ET_REL};
+
+const ID: &[u8] = &[
+    0xab, 0xcd, 0xef, 0x01, 0x23, 0x45, 0x67, 0x89, 0x00, 0x11, 0x22,
+];
+
+#[test]
+fn all_classes_and_byte_orders() {
+    for class64 in [false, true] {
+        for big_endian in [false, true] {
+            let b = ElfBuilder::new(class64, big_endian);
+            let abi_tag = b.w.note(4, b"GNU\0", 1, &[0; 16]);
+            let data = b
+                .clone()
+                .note_segment(".note.ABI-tag", 4, abi_tag)
+                .note_segment(".note.gnu.build-id", 4, b.w.build_id_note(ID))
+                .build();
+
+            let elf = ElfFile::parse(&data).unwrap();
+            assert_eq!(
+                elf.header().class,
+                if class64 { Class::Elf64 } else { Class::Elf32 }
+            );
+            assert_eq!(
+                elf.header().endian,
+                if big_endian {
+                    Endian::Big
+                } else {
+                    Endian::Little
+                }
+            );
+            assert_eq!(elf.build_id().unwrap(), ID);
+            assert_eq!(elf.program_headers().unwrap().count(), 3);
+            let shdr = elf.section_by_name(b".note.gnu.build-id").unwrap().unwrap();
+            assert_eq!(elf.section_name(&shdr).unwrap(), b".note.gnu.build-id");
+        }
+    }
+}
+
+/// An 8-aligned `.note.gnu.property` segment (as in CET and BTI enabled objects), which also holds
+/// a Go build-id and the GNU build-id, followed by a 4-aligned segment
+fn mixed_alignment_elf(class64: bool, big_endian: bool) -> Vec<u8> {
+    let b = ElfBuilder::new(class64, big_endian);
+    let mut property = Vec::new();
+    b.w.u32(&mut property, 0xc000_0002);
+    b.w.u32(&mut property, 4);
+    b.w.u32(&mut property, 3);
+    b.w.u32(&mut property, 0);
+    let mut aligned8 = b.w.note(8, b"GNU\0", 5, &property);
+    aligned8.extend(b.w.note(8, b"Go\0\0", 4, b"abc/def/ghi"));
+    aligned8.extend(b.w.note(8, b"GNU\0", 3, ID));
+    // the last descriptor isn't padded
+    aligned8.truncate(aligned8.len() - 5);
+
+    let mut aligned4 = b.w.note(4, b"GNU\0", 1, &[0; 16]);
+    aligned4.extend(b.w.note(4, b"FDO\0", 0xcafe_1a7e, b"{}\0"));
+    b.clone()
+        .note_segment(".note.gnu.property", 8, aligned8)
+        .note_segment(".note.ABI-tag", 4, aligned4)
+        .build()
+}
+
+#[test]
+fn mixed_alignment() {
+    for class64 in [false, true] {
+        for big_endian in [false, true] {
+            let data = mixed_alignment_elf(class64, big_endian);
+            let elf = ElfFile::parse(&data).unwrap();
+            let notes: Vec<_> = elf
+                .note_segments()
+                .unwrap()
+                .map(Result::unwrap)
+                .map(|ni| {
+                    ni.map(|n| n.map(|n| (n.align(), n.type_())))
+                        .collect::<Result<Vec<_>, _>>()
+                })
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_eq!(
+                notes,
+                [vec![(8, 5), (8, 4), (8, 3)], vec![(4, 1), (4, 0xcafe_1a7e)]]
+            );
+
+            assert_eq!(elf.build_id().unwrap(), ID);
+            let range = elf_file::build_id_range(&data).unwrap();
+            assert_eq!(&data[range], ID);
+            assert_eq!(
+                elf_file::read_build_id(&mut std::io::Cursor::new(&data)).unwrap(),
+                ID
+            );
+        }
+    }
+}
+
+#[test]
+fn sections_only() {
+    let mut b = ElfBuilder::new(true, false);
+    b.e_type = ET_REL;
+    let note = b.w.build_id_note(ID);
+    let data = b.note_section(".note.gnu.build-id", 4, note).build();
+
+    let elf = ElfFile::parse(&data).unwrap();
+    assert_eq!(elf.program_headers().unwrap().count(), 0);
+    assert_eq!(elf_file::build_id(&data).unwrap(), ID);
+}
+
+#[test]
+fn set_build_id() {
+    let b = ElfBuilder::new(false, true);
+    let abi_tag = b.w.note(4, b"GNU\0", 1, &[0; 16]);
+    let note = b.w.build_id_note(ID);
+    let mut data = b
+        .note_segment(".note.ABI-tag", 4, abi_tag)
+        .note_segment(".note.gnu.build-id", 4, note)
+        .build();
+    let orig = data.clone();
+
+    let range = elf_file::build_id_range(&data).unwrap();
+    assert_eq!(&data[range.clone()], ID);
+
+    let new = [0x55; 11];
+    elf_file::set_build_id(&mut data, &new).unwrap();
+    assert_eq!(elf_file::build_id(&data).unwrap(), new);
+    // nothing outside the descriptor changes
+    assert_eq!(data[..range.start], orig[..range.start]);
+    assert_eq!(data[range.end..], orig[range.end..]);
+
+    assert_eq!(
+        elf_file::set_build_id(&mut data, &[0x55; 20]),
+        Err(Error::LengthMismatch {
+            stored: 11,
+            new: 20
+        })
+    );
+    assert_eq!(elf_file::build_id(&data).unwrap(), new);
+}
+
+#[test]
+fn no_build_id() {
+    let b = ElfBuilder::new(true, false);
+    let abi_tag = b.w.note(4, b"GNU\0", 1, &[0; 16]);
+    let data = b.note_segment(".note.ABI-tag", 4, abi_tag).build();
+    assert_eq!(elf_file::build_id(&data), Err(Error::NotFound));
+}
+
+#[test]
+fn invalid() {
+    assert_eq!(
+        elf_file::build_id(b"not an elf file"),
+        Err(Error::UnrecognizedFormat)
+    );
+
+    let b = ElfBuilder::new(false, true);
+    let note = b.w.build_id_note(ID);
+    let data = b.note_segment(".note.gnu.build-id", 4, note).build();
+    assert_eq!(elf_file::build_id(&data[..40]), Err(Error::Truncated));
+
+    // a note which claims to be larger than its segment
+    let b = ElfBuilder::new(true, false);
+    let mut note = b.w.build_id_note(ID);
+    note[4] = 0xff;
+    let data = b.note_segment(".note.gnu.build-id", 4, note).build();
+    assert!(matches!(
+        elf_file::build_id(&data),
+        Err(Error::InvalidNote(_))
+    ));
+}
+
+#[test]
+fn no_section_header_table() {
+    // a header that claims one section, with no section header table and no e_shentsize
+    let mut b = ElfBuilder::new(true, false);
+    b.e_type = ET_REL;
+    b.sections = false;
+    let mut header = b.build();
+    header.truncate(64);
+    header[58..64].copy_from_slice(&[0, 0, 1, 0, 1, 0]);
+
+    let elf = ElfFile::parse(&header).unwrap();
+    assert_eq!(elf.shnum(), Ok(0));
+    assert_eq!(elf_file::build_id(&header), Err(Error::NotFound));
+    assert_eq!(
+        elf_file::read_build_id(&mut std::io::Cursor::new(&header)),
+        Err(Error::NotFound)
+    );
+}
+
+#[cfg(all(target_family = "unix", not(target_vendor = "apple")))]
+#[test]
+fn current_exe() {
+    let data = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+    assert_eq!(
+        Some(elf_file::build_id(&data).unwrap()),
+        buildid::build_id()
+    );
+}
+
+/// Counts the bytes read through it
+struct CountingReader<R> {
+    inner: R,
+    read: usize,
+}
+
+impl<R: std::io::Read> std::io::Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read += n;
+        Ok(n)
+    }
+}
+
+impl<R: std::io::Seek> std::io::Seek for CountingReader<R> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+#[test]
+fn read_only_notes() {
+    for class64 in [false, true] {
+        for big_endian in [false, true] {
+            let b = ElfBuilder::new(class64, big_endian);
+            let note = b.w.build_id_note(ID);
+            let data = b
+                .note_segment(".note.gnu.build-id", 4, note)
+                .section(".debug_info", common::SHT_PROGBITS, vec![0; 1 << 20])
+                .build();
+
+            let mut r = CountingReader {
+                inner: std::io::Cursor::new(&data),
+                read: 0,
+            };
+            let id = elf_file::read_build_id(&mut r).unwrap();
+            assert_eq!(id, ID);
+            assert_eq!(id.kind(), buildid::Kind::Gnu);
+            assert!(r.read < 1024, "read {} bytes", r.read);
+        }
+    }
+
+    let mut b = ElfBuilder::new(true, false);
+    b.e_type = ET_REL;
+    let note = b.w.build_id_note(ID);
+    let data = b.note_section(".note.gnu.build-id", 4, note).build();
+    assert_eq!(
+        elf_file::read_build_id(&mut std::io::Cursor::new(&data)).unwrap(),
+        ID
+    );
+    assert_eq!(
+        elf_file::read_build_id(&mut std::io::Cursor::new(&data[..100])),
+        Err(Error::Truncated)
+    );
+}
+
+#[cfg(all(target_family = "unix", not(target_vendor = "apple")))]
+#[test]
+fn from_path() {
+    let id = buildid::from_path(std::env::current_exe().unwrap()).unwrap();
+    assert_eq!(Some(id), buildid::BuildId::current());
+
+    assert_eq!(
+        buildid::from_path("/this/path/does/not/exist"),
+        Err(Error::Io(std::io::ErrorKind::NotFound))
+    );
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use buildid::elf_file::{self, ElfFile, PT_LOAD, PT_NOTE};
+use buildid::inject::add_build_id;
+use buildid::recompute::{self, Style};
+use buildid::Error;
+use common::{Blob, ElfBuilder, SHT_PROGBITS};
+
+const PT_NULL: u32 = 0;
+
+/// A file with an ABI tag note, followed by unused bytes if `gap` is set
+fn elf(class64: bool, big_endian: bool, gap: bool) -> ElfBuilder {
+    let b = ElfBuilder::new(class64, big_endian);
+    let abi_tag = b.w.note(4, b"GNU\0", 1, &[0; 16]);
+    let mut b = b.note_segment(".note.ABI-tag", 4, abi_tag);
+    b.blobs.push(Blob {
+        name: ".text".into(),
+        sh_type: SHT_PROGBITS,
+        align: if gap { 0x100 } else { 1 },
+        data: vec![0xc3; 16],
+        p_type: None,
+    });
+    b
+}
+
+/// The offset of the build-id, checking that the runtime lookup and `verify()` agree with it
+fn check(data: &[u8], style: Style) -> usize {
+    let range = elf_file::build_id_range(data).unwrap();
+    assert_eq!(range.len(), style.id_len());
+    assert_eq!(recompute::verify(data), Ok(style));
+    let elf = ElfFile::parse(data).unwrap();
+    let shdr = elf.section_by_name(b".note.gnu.build-id").unwrap().unwrap();
+    assert_eq!(shdr.sh_offset as usize + 16, range.start);
+    range.start
+}
+
+fn segments(data: &[u8]) -> Vec<u32> {
+    let elf = ElfFile::parse(data).unwrap();
+    let phdrs = elf.program_headers().unwrap();
+    phdrs.map(|p| p.unwrap().p_type).collect()
+}
+
+#[test]
+fn extend_note() {
+    for class64 in [false, true] {
+        for big_endian in [false, true] {
+            let data = elf(class64, big_endian, true).build();
+            assert_eq!(elf_file::build_id_range(&data), Err(Error::NotFound));
+            let note_end = if class64 { 64 + 2 * 56 } else { 52 + 2 * 32 } + 32;
+
+            let out = add_build_id(&data, Style::LdSha1).unwrap();
+            assert_eq!(check(&out, Style::LdSha1), note_end + 16);
+            assert_eq!(segments(&out), [PT_LOAD, PT_NOTE]);
+            assert_eq!(
+                add_build_id(&out, Style::LdSha1),
+                Err(Error::BuildIdPresent)
+            );
+        }
+    }
+}
+
+#[test]
+fn move_note() {
+    let data = elf(true, false, false).build();
+    let out = add_build_id(&data, Style::LldFast).unwrap();
+    // the notes follow the original end of the file, as the loaded segment grows into it
+    assert_eq!(check(&out, Style::LldFast), data.len() + 32 + 16);
+    assert_eq!(segments(&out), [PT_LOAD, PT_NOTE]);
+}
+
+#[test]
+fn append_load() {
+    let mut b = elf(true, false, false);
+    b.blobs.push(Blob {
+        name: ".unused".into(),
+        sh_type: SHT_PROGBITS,
+        align: 1,
+        data: Vec::new(),
+        p_type: Some(PT_NULL),
+    });
+    let mut data = b.build();
+    // the loaded segment can't grow once it has a .bss, so a new one is needed
+    data[64 + 40] += 0x10;
+
+    let out = add_build_id(&data, Style::GoldMd5).unwrap();
+    assert!(check(&out, Style::GoldMd5) >= data.len());
+    assert_eq!(segments(&out), [PT_LOAD, PT_LOAD, PT_NOTE]);
+}
+
+#[test]
+fn aligned_notes() {
+    let b = ElfBuilder::new(true, false);
+    let property = b.w.note(8, b"GNU\0", 5, &[0; 16]);
+    let mut b = b.note_segment(".note.gnu.property", 8, property);
+    b.blobs.push(Blob {
+        name: ".text".into(),
+        sh_type: SHT_PROGBITS,
+        align: 0x100,
+        data: vec![0xc3; 16],
+        p_type: None,
+    });
+    let data = b.build();
+
+    let out = add_build_id(&data, Style::LdSha1).unwrap();
+    check(&out, Style::LdSha1);
+    let elf = ElfFile::parse(&out).unwrap();
+    let note = elf.program_headers().unwrap().nth(1).unwrap().unwrap();
+    assert_eq!((note.p_align, note.p_filesz), (8, 32 + 40));
+}
+
+#[test]
+fn no_space() {
+    let data = ElfBuilder::new(true, false)
+        .section(".text", SHT_PROGBITS, vec![0xc3; 16])
+        .build();
+    assert_eq!(add_build_id(&data, Style::LdSha1), Err(Error::NoSpace));
+    assert_eq!(
+        add_build_id(b"not an elf file", Style::LdSha1),
+        Err(Error::UnrecognizedFormat)
+    );
+}
+
+#[test]
+fn overflow() {
+    // a note segment at the very end of the address space
+    let mut data = elf(true, false, true).build();
+    data[64 + 56 + 16..][..8].copy_from_slice(&(u64::MAX - 8).to_le_bytes());
+    assert!(matches!(
+        add_build_id(&data, Style::LdSha1),
+        Err(Error::Malformed(_))
+    ));
+
+    // a loaded segment with an alignment that the new one can't follow
+    let mut b = elf(true, false, false);
+    b.blobs.push(Blob {
+        name: ".unused".into(),
+        sh_type: SHT_PROGBITS,
+        align: 1,
+        data: Vec::new(),
+        p_type: Some(PT_NULL),
+    });
+    let mut data = b.build();
+    data[64 + 40] += 0x10;
+    data[64 + 48..][..8].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert!(matches!(
+        add_build_id(&data, Style::GoldMd5),
+        Err(Error::Malformed(_))
+    ));
+
+    // a segment that extends past the end of the file
+    let mut data = elf(true, false, true).build();
+    data[64 + 32..][..8].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert_eq!(add_build_id(&data, Style::LdSha1), Err(Error::Truncated));
+}
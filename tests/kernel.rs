@@ -0,0 +1,82 @@
+mod common;
+
+use buildid::{kernel, Endian, Error};
+use common::Writer;
+
+/// `/sys/kernel/notes` of an x86_64 kernel: the build-id, then `Linux` and `Xen` notes
+const KERNEL_NOTES: &str = "\
+    040000001400000003000000474e55004e0bf38b61d89656d28d6bcfd59b855c50cfdeaf060000000400000001\
+    0100004c696e757800000000000000060000001e000000000100004c696e7578000000362e312e3130322d312e\
+    3138322e616d7a6e323032332e7838365f3634000000040000000c0000001300000058656e0000000001000000\
+    01ffffff3f04000000080000001200000058656e0000e2e80200000000";
+
+fn unhex(s: &str) -> Vec<u8> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+#[test]
+fn kernel_notes() {
+    let notes = unhex(KERNEL_NOTES);
+    assert_eq!(
+        kernel::notes_build_id(&notes, Endian::Little).unwrap(),
+        unhex("4e0bf38b61d89656d28d6bcfd59b855c50cfdeaf")
+    );
+
+    // without the build-id note
+    assert_eq!(
+        kernel::notes_build_id(&notes[36..], Endian::Little),
+        Err(Error::NotFound)
+    );
+}
+
+#[test]
+fn module_notes() {
+    // a module's .note.gnu.build-id, on a big endian host
+    let w = Writer {
+        class64: true,
+        big_endian: true,
+    };
+    let id = [0x42; 20];
+    let notes = w.build_id_note(&id);
+    assert_eq!(kernel::notes_build_id(&notes, Endian::Big).unwrap(), id);
+    assert!(matches!(
+        kernel::notes_build_id(&notes[..30], Endian::Big),
+        Err(Error::InvalidNote(_))
+    ));
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[test]
+fn running_kernel() {
+    // not every system makes these readable (or has any modules), so only check they're
+    // consistent with a direct read
+    if let Ok(notes) = std::fs::read("/sys/kernel/notes") {
+        let id = kernel::notes_build_id(&notes, Endian::NATIVE);
+        assert_eq!(
+            kernel::build_id().map(|id| id.as_bytes().to_vec()),
+            id.map(<[u8]>::to_vec)
+        );
+    }
+    for module in kernel::modules().unwrap_or_default() {
+        assert_eq!(
+            kernel::module_build_id(module.name()),
+            module.try_build_id()
+        );
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[test]
+fn invalid_module_names() {
+    for name in ["", ".", "..", "../../kernel", "a/b"] {
+        assert_eq!(
+            kernel::module_build_id(name),
+            Err(Error::Io(std::io::ErrorKind::InvalidInput)),
+            "{:?}",
+            name
+        );
+    }
+}
@@ -0,0 +1,86 @@
+#![cfg(all(target_family = "unix", not(target_vendor = "apple")))]
+
+#[test]
+fn includes_self() {
+    let addr = includes_self as *const () as usize;
+    let objs: Vec<_> = buildid::loaded_objects().collect();
+    // every object has loaded segments, and none of them overlap
+    let segments: Vec<_> = objs.iter().flat_map(|o| o.segments()).collect();
+    assert!(objs.iter().all(|o| o.segments().next().is_some()));
+    for (i, a) in segments.iter().enumerate() {
+        for b in &segments[i + 1..] {
+            assert!(a.end <= b.start || b.end <= a.start, "{:x?} {:x?}", a, b);
+        }
+    }
+
+    let ours: Vec<_> = objs.iter().filter(|o| o.contains(addr)).collect();
+    assert_eq!(ours.len(), 1);
+    assert_eq!(ours[0].build_id(), buildid::build_id());
+    let current = buildid::current_object().unwrap();
+    assert_eq!(current.load_bias(), ours[0].load_bias());
+}
+
+#[test]
+fn stable_order() {
+    // the objects don't change while nothing is loaded or unloaded
+    let a: Vec<_> = buildid::loaded_objects().map(|o| o.load_bias()).collect();
+    let b: Vec<_> = buildid::loaded_objects().map(|o| o.load_bias()).collect();
+    assert_eq!(a, b);
+    assert_eq!(buildid::loaded_objects().count(), a.len());
+}
+
+#[test]
+fn includes_libc() {
+    // we're dynamically linked against libc, so it must show up along with the executable
+    assert!(buildid::loaded_objects().count() >= 2);
+    assert!(buildid::loaded_objects().any(|o| o
+        .name()
+        .to_bytes()
+        .windows(4)
+        .any(|w| w == b"libc")));
+}
+
+#[test]
+fn for_addr() {
+    let addr = for_addr as *const ();
+    let (id, offset) = buildid::build_id_for_addr(addr.cast()).unwrap();
+    assert_eq!(Some(id), buildid::build_id());
+
+    let obj = buildid::object_for_addr(addr.cast()).unwrap();
+    assert_eq!(obj.load_bias() + offset, addr as usize);
+
+    // data addresses work too
+    static DATA: u8 = 0;
+    let (id, _) = buildid::build_id_for_addr((&DATA as *const u8).cast()).unwrap();
+    assert_eq!(Some(id), buildid::build_id());
+
+    assert!(buildid::build_id_for_addr(core::ptr::null()).is_none());
+}
+
+#[test]
+fn debug_links() {
+    let exe =
+        buildid::object_for_addr(debug_links as *const () as *const std::ffi::c_void).unwrap();
+    assert_eq!(exe.path().unwrap(), std::env::current_exe().unwrap());
+    // test binaries are not stripped, so they have no debuglink
+    let links = exe.debug_links().unwrap();
+    assert_eq!(links.debuglink().unwrap(), None);
+}
+
+#[test]
+fn notes() {
+    let obj = buildid::current_object().unwrap();
+    // including any 8-aligned `.note.gnu.property` segment
+    let id = obj
+        .notes()
+        .map(Result::unwrap)
+        .find(|n| n.is_build_id())
+        .map(|n| n.desc());
+    assert_eq!(id, buildid::build_id());
+
+    for obj in buildid::loaded_objects() {
+        for note in obj.notes() {
+            note.unwrap();
+        }
+    }
+}
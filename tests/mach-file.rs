@@ -0,0 +1,224 @@
+use buildid::mach_file::{self, MachFile, CPU_TYPE_ARM64, CPU_TYPE_POWERPC, CPU_TYPE_X86_64};
+use buildid::{Endian, Error, Kind};
+
+const UUID: [u8; 16] = [
+    0x8a, 0x5e, 0x4c, 0x11, 0x3b, 0x5d, 0x37, 0x6c, 0x95, 0x0f, 0x2c, 0xc9, 0x1a, 0x6f, 0x8e, 0x7e,
+];
+
+fn put(out: &mut Vec<u8>, big_endian: bool, v: u32) {
+    if big_endian {
+        out.extend_from_slice(&v.to_be_bytes());
+    } else {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+}
+
+/// Build a minimal Mach-O file with a dummy segment command and (optionally) an `LC_UUID`
+fn macho(is_64: bool, big_endian: bool, cpu_type: u32, uuid: Option<&[u8; 16]>) -> Vec<u8> {
+    let mut cmds = Vec::new();
+    // LC_SEGMENT_64/LC_SEGMENT with no content
+    put(&mut cmds, big_endian, if is_64 { 0x19 } else { 0x1 });
+    put(&mut cmds, big_endian, 72);
+    cmds.resize(72, 0);
+    let mut ncmds = 1;
+    if let Some(uuid) = uuid {
+        put(&mut cmds, big_endian, 0x1b);
+        put(&mut cmds, big_endian, 24);
+        cmds.extend_from_slice(uuid);
+        ncmds += 1;
+    }
+
+    let mut out = Vec::new();
+    put(
+        &mut out,
+        big_endian,
+        if is_64 { 0xfeedfacf } else { 0xfeedface },
+    );
+    put(&mut out, big_endian, cpu_type);
+    put(&mut out, big_endian, 3);
+    // MH_EXECUTE
+    put(&mut out, big_endian, 2);
+    put(&mut out, big_endian, ncmds);
+    put(&mut out, big_endian, cmds.len() as u32);
+    put(&mut out, big_endian, 0);
+    if is_64 {
+        put(&mut out, big_endian, 0);
+    }
+    out.extend_from_slice(&cmds);
+    out
+}
+
+/// Build a universal binary containing `slices`
+fn fat(is_64: bool, slices: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    put(&mut out, true, if is_64 { 0xcafebabf } else { 0xcafebabe });
+    put(&mut out, true, slices.len() as u32);
+    let mut offset = 0x100u64;
+    let mut data = Vec::new();
+    for (cpu_type, s) in slices {
+        put(&mut out, true, *cpu_type);
+        put(&mut out, true, 3);
+        if is_64 {
+            out.extend_from_slice(&offset.to_be_bytes());
+            out.extend_from_slice(&(s.len() as u64).to_be_bytes());
+            put(&mut out, true, 8);
+            put(&mut out, true, 0);
+        } else {
+            put(&mut out, true, offset as u32);
+            put(&mut out, true, s.len() as u32);
+            put(&mut out, true, 8);
+        }
+        data.push((offset, s));
+        offset += 0x100;
+    }
+    for (offset, s) in data {
+        out.resize(offset as usize, 0);
+        out.extend_from_slice(s);
+    }
+    out
+}
+
+#[test]
+fn thin() {
+    for is_64 in [false, true] {
+        for big_endian in [false, true] {
+            let data = macho(is_64, big_endian, CPU_TYPE_POWERPC, Some(&UUID));
+            let f = MachFile::parse(&data).unwrap();
+            assert_eq!(f.is_64(), is_64);
+            assert_eq!(
+                f.endian(),
+                if big_endian {
+                    Endian::Big
+                } else {
+                    Endian::Little
+                }
+            );
+            assert_eq!(f.load_commands().count(), 2);
+            let uuid = f.uuid().unwrap();
+            assert_eq!(uuid, &UUID[..]);
+            assert_eq!(uuid.kind(), Kind::MachUuid);
+            assert_eq!(
+                uuid.uuid().unwrap().to_string(),
+                "8a5e4c11-3b5d-376c-950f-2cc91a6f8e7e"
+            );
+
+            let all: Vec<_> = mach_file::uuids(&data).unwrap().collect();
+            assert_eq!(all.len(), 1);
+            assert_eq!(all[0].unwrap().cpu_type, CPU_TYPE_POWERPC);
+        }
+    }
+}
+
+#[test]
+fn universal() {
+    let mut other = UUID;
+    other[0] = 0;
+    for is_64 in [false, true] {
+        let data = fat(
+            is_64,
+            &[
+                (
+                    CPU_TYPE_X86_64,
+                    macho(true, false, CPU_TYPE_X86_64, Some(&UUID)),
+                ),
+                (
+                    CPU_TYPE_ARM64,
+                    macho(true, false, CPU_TYPE_ARM64, Some(&other)),
+                ),
+            ],
+        );
+
+        assert_eq!(
+            MachFile::parse(&data).err(),
+            Some(Error::UnrecognizedFormat)
+        );
+        let all: Vec<_> = mach_file::uuids(&data)
+            .unwrap()
+            .map(|s| s.unwrap())
+            .collect();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].cpu_type, CPU_TYPE_X86_64);
+        assert_eq!(all[0].uuid, &UUID[..]);
+        assert_eq!(all[1].cpu_type, CPU_TYPE_ARM64);
+        assert_eq!(all[1].uuid, &other[..]);
+
+        let read = mach_file::read_uuids(&mut std::io::Cursor::new(&data)).unwrap();
+        assert_eq!(read, all.iter().copied().map(Ok).collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn missing_or_invalid() {
+    let data = macho(true, false, CPU_TYPE_X86_64, None);
+    assert_eq!(MachFile::parse(&data).unwrap().uuid(), Err(Error::NotFound));
+
+    let data = macho(true, false, CPU_TYPE_X86_64, Some(&UUID));
+    assert_eq!(
+        MachFile::parse(&data[..data.len() - 1]).err(),
+        Some(Error::Truncated)
+    );
+
+    // a java class file shares the universal binary magic
+    let class = b"\xca\xfe\xba\xbe\x00\x00\x00\x34";
+    assert_eq!(
+        mach_file::uuids(class).err(),
+        Some(Error::UnrecognizedFormat)
+    );
+
+    let data = fat(false, &[(CPU_TYPE_X86_64, data)]);
+    let mut it = mach_file::uuids(&data[..0x120]).unwrap();
+    assert_eq!(it.next(), Some(Err(Error::Truncated)));
+
+    // a slice without a uuid doesn't hide the others
+    let data = fat(
+        true,
+        &[
+            (CPU_TYPE_X86_64, macho(true, false, CPU_TYPE_X86_64, None)),
+            (
+                CPU_TYPE_ARM64,
+                macho(true, false, CPU_TYPE_ARM64, Some(&UUID)),
+            ),
+        ],
+    );
+    let all: Vec<_> = mach_file::uuids(&data).unwrap().collect();
+    assert_eq!(all[0], Err(Error::NotFound));
+    assert_eq!(all[1].unwrap().uuid, &UUID[..]);
+    let read = mach_file::read_uuids(&mut std::io::Cursor::new(&data)).unwrap();
+    assert_eq!(read, all);
+
+    // a slice at an offset that overflows
+    let mut data = fat(
+        true,
+        &[(CPU_TYPE_ARM64, macho(true, false, CPU_TYPE_ARM64, None))],
+    );
+    data[16..24].copy_from_slice(&(u64::MAX - 8).to_be_bytes());
+    let read = mach_file::read_uuids(&mut std::io::Cursor::new(&data)).unwrap();
+    assert_eq!(read, [Err(Error::Truncated)]);
+}
+
+#[test]
+fn from_path() {
+    let dir = std::env::temp_dir().join(format!("buildid-mach-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let thin = dir.join("thin");
+    std::fs::write(&thin, macho(true, false, CPU_TYPE_ARM64, Some(&UUID))).unwrap();
+    assert_eq!(buildid::from_path(&thin).unwrap(), &UUID[..]);
+
+    let universal = dir.join("universal");
+    let slice = macho(true, false, CPU_TYPE_ARM64, Some(&UUID));
+    std::fs::write(
+        &universal,
+        fat(
+            false,
+            &[(CPU_TYPE_ARM64, slice.clone()), (CPU_TYPE_ARM64, slice)],
+        ),
+    )
+    .unwrap();
+    assert_eq!(
+        buildid::from_path(&universal),
+        Err(Error::MultipleArchitectures)
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,97 @@
+use buildid::{Error, Method};
+
+#[test]
+fn all_methods() {
+    let (id, method) = buildid::try_build_id_with(Method::ALL).unwrap();
+    assert_eq!(Ok(id), buildid::try_build_id());
+    assert!(method.is_available());
+    assert_eq!(id.kind(), method.kind());
+    assert_eq!(method.build_id(), Ok(id.as_bytes()));
+}
+
+#[test]
+fn feature_method() {
+    // the method a feature enables must be the one that finds the build-id, as the default methods
+    // aren't tried then
+    let expected = if cfg!(feature = "buildid-custom-inject") {
+        Method::CustomInject
+    } else if cfg!(feature = "buildid-section-inject") {
+        Method::SectionInject
+    } else if cfg!(feature = "buildid-symbol-start-end") {
+        Method::SymbolStartEnd
+    } else if cfg!(feature = "buildid-ehdr-start") {
+        Method::EhdrStart
+    } else if cfg!(all(target_family = "unix", not(target_vendor = "apple"))) {
+        Method::DynamicLoader
+    } else {
+        Method::Platform
+    };
+    assert_eq!(Method::ALL[0], expected);
+    let (id, method) = buildid::try_build_id_with(Method::ALL).unwrap();
+    assert_eq!(method, expected);
+    assert_eq!(Ok(id), buildid::try_build_id());
+}
+
+#[test]
+fn all_available() {
+    assert!(!Method::ALL.is_empty());
+    assert!(Method::ALL.iter().all(|m| m.is_available()));
+}
+
+#[test]
+fn unavailable() {
+    assert_eq!(buildid::try_build_id_with(&[]), Err(Error::Unavailable));
+    for method in [
+        Method::CustomInject,
+        Method::SectionInject,
+        Method::SymbolStartEnd,
+        Method::EhdrStart,
+        Method::DynamicLoader,
+        Method::AuxVector,
+        Method::ProcMaps,
+        Method::Platform,
+    ] {
+        if !method.is_available() {
+            assert_eq!(method.build_id(), Err(Error::Unavailable));
+            assert_eq!(
+                buildid::try_build_id_with(&[method]),
+                Err(Error::Unavailable)
+            );
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn fallback() {
+    // with a method that isn't available first, the next one is used
+    if Method::CustomInject.is_available() {
+        return;
+    }
+    let (id, method) =
+        buildid::try_build_id_with(&[Method::CustomInject, Method::DynamicLoader]).unwrap();
+    assert_eq!(method, Method::DynamicLoader);
+    assert_eq!(
+        Ok(id),
+        buildid::try_build_id_with(&[Method::DynamicLoader]).map(|(id, _)| id)
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn elf_methods_agree() {
+    let (id, _) = buildid::try_build_id_with(&[Method::DynamicLoader]).unwrap();
+    for method in [Method::AuxVector, Method::ProcMaps] {
+        // only the main executable is found through the auxiliary vector, and the test harness
+        // could be a different object from the one this crate is linked into
+        match method.build_id() {
+            Ok(found) => assert_eq!(found, id.as_bytes(), "{:?}", method),
+            Err(e) => assert!(
+                !method.is_available() || e == Error::ObjectNotFound,
+                "{:?}: {}",
+                method,
+                e
+            ),
+        }
+    }
+}
@@ -0,0 +1,132 @@
+mod common;
+
+use buildid::note::{
+    AbiTag, NoteIter, GNU_PROPERTY_X86_FEATURE_1_AND, NT_FDO_PACKAGING_METADATA, NT_GNU_ABI_TAG,
+    NT_GNU_PROPERTY_TYPE_0, NT_GO_BUILD_ID,
+};
+use buildid::{Endian, NoteError};
+use common::Writer;
+
+const W: Writer = Writer {
+    class64: true,
+    big_endian: false,
+};
+
+fn note(data: &[u8]) -> buildid::note::Note<'_> {
+    NoteIter::new(data, Endian::Little).next().unwrap().unwrap()
+}
+
+#[test]
+fn abi_tag() {
+    let mut desc = Vec::new();
+    for v in [0, 3, 2, 0] {
+        W.u32(&mut desc, v);
+    }
+    let data = W.note(4, b"GNU\0", NT_GNU_ABI_TAG, &desc);
+    assert_eq!(
+        note(&data).abi_tag(),
+        Some(AbiTag {
+            os: 0,
+            version: [3, 2, 0]
+        })
+    );
+    assert_eq!(note(&data).go_build_id(), None);
+
+    let data = W.note(4, b"GNU\0", NT_GNU_ABI_TAG, &desc[..8]);
+    assert_eq!(note(&data).abi_tag(), None);
+}
+
+#[test]
+fn gnu_properties() {
+    let mut desc = Vec::new();
+    W.u32(&mut desc, GNU_PROPERTY_X86_FEATURE_1_AND);
+    W.u32(&mut desc, 4);
+    W.u32(&mut desc, 3);
+    W.u32(&mut desc, 0);
+    // GNU_PROPERTY_X86_ISA_1_NEEDED, with an 8 byte value
+    W.u32(&mut desc, 0xc000_8002);
+    W.u32(&mut desc, 8);
+    W.u64(&mut desc, 1);
+    let data = W.note(4, b"GNU\0", NT_GNU_PROPERTY_TYPE_0, &desc);
+
+    let props: Vec<_> = note(&data)
+        .gnu_properties(8)
+        .unwrap()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(props.len(), 2);
+    assert_eq!(props[0].type_, GNU_PROPERTY_X86_FEATURE_1_AND);
+    assert_eq!(props[0].value_u32(), Some(3));
+    assert_eq!(props[1].data, 1u64.to_le_bytes());
+    assert_eq!(props[1].value_u32(), None);
+
+    let data = W.note(4, b"GNU\0", NT_GNU_PROPERTY_TYPE_0, &desc[..20]);
+    let mut props = note(&data).gnu_properties(8).unwrap();
+    assert!(props.next().unwrap().is_ok());
+    assert_eq!(
+        props.next(),
+        Some(Err(NoteError::MissingHeader { size: 4 }))
+    );
+    assert_eq!(props.next(), None);
+}
+
+#[test]
+fn eight_byte_alignment() {
+    // the descriptor starts at offset 24 rather than 20, as the name is padded to 8 bytes
+    let mut data = W.note(8, b"CORE\0", 1, b"status");
+    data.extend(W.note(8, b"GNU\0", 3, &[0x42; 20]));
+    let notes: Vec<_> = NoteIter::with_align(&data, Endian::Little, 8)
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(notes.len(), 2);
+    assert_eq!(notes[0].name(), b"CORE\0");
+    assert_eq!(notes[0].desc(), b"status");
+    assert_eq!(notes[1].align(), 8);
+    assert_eq!(notes[1].desc(), [0x42; 20]);
+
+    // the same bytes don't make sense as 4-aligned notes
+    assert!(
+        NoteIter::new(&data, Endian::Little).any(|n| n.is_err() || n.unwrap().desc() != b"status")
+    );
+}
+
+#[test]
+fn huge_sizes() {
+    let mut data = Vec::new();
+    W.u32(&mut data, u32::MAX);
+    W.u32(&mut data, u32::MAX);
+    W.u32(&mut data, 3);
+    data.extend_from_slice(b"GNU\0");
+    assert!(matches!(
+        NoteIter::with_align(&data, Endian::Little, 8).next(),
+        Some(Err(NoteError::Truncated { have: 16, .. }))
+    ));
+}
+
+#[test]
+fn go_build_id() {
+    let id = "yzrCQRGWIcBHGHSJ4Gmb/g1TVxE4ItlEsWK6dQa7U/rCqMdbpyDNd4QmaVJJGX/2HSdsZpJfcO38KUFsWvY";
+    let data = W.note(4, b"Go\0\0", NT_GO_BUILD_ID, id.as_bytes());
+    assert_eq!(note(&data).go_build_id(), Some(id));
+
+    // the same type with another name is something else
+    let data = W.note(4, b"GNU\0", NT_GO_BUILD_ID, id.as_bytes());
+    assert_eq!(note(&data).go_build_id(), None);
+}
+
+#[test]
+fn package_metadata() {
+    let json = br#"{"type":"deb", "name" : "curl","version":"8.2.1-1","note":"a \"quoted\" word"}"#;
+    let data = W.note(
+        4,
+        b"FDO\0",
+        NT_FDO_PACKAGING_METADATA,
+        &[&json[..], b"\0"].concat(),
+    );
+    let metadata = note(&data).package_metadata().unwrap();
+    assert_eq!(metadata.json().as_bytes(), json);
+    assert_eq!(metadata.get("type"), Some("deb"));
+    assert_eq!(metadata.get("name"), Some("curl"));
+    assert_eq!(metadata.get("note"), Some(r#"a \"quoted\" word"#));
+    assert_eq!(metadata.get("architecture"), None);
+}
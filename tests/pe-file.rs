@@ -0,0 +1,236 @@
+use buildid::pe_file::{
+    self, PeFile, DEBUG_DIRECTORY_SIZE, IMAGE_DEBUG_TYPE_CODEVIEW, IMAGE_DEBUG_TYPE_REPRO,
+};
+use buildid::{Error, Kind};
+
+const GUID: [u8; 16] = [
+    0x11, 0x4c, 0x5e, 0x8a, 0x5d, 0x3b, 0x6c, 0x37, 0x95, 0x0f, 0x2c, 0xc9, 0x1a, 0x6f, 0x8e, 0x7e,
+];
+const HASH: [u8; 32] = [0xab; 32];
+const IMAGE_DEBUG_TYPE_POGO: u32 = 13;
+
+fn codeview(guid: &[u8; 16], age: u32, path: &str) -> Vec<u8> {
+    let mut out = b"RSDS".to_vec();
+    out.extend_from_slice(guid);
+    out.extend_from_slice(&age.to_le_bytes());
+    out.extend_from_slice(path.as_bytes());
+    out.push(0);
+    out
+}
+
+fn repro(hash: &[u8]) -> Vec<u8> {
+    let mut out = (hash.len() as u32).to_le_bytes().to_vec();
+    out.extend_from_slice(hash);
+    out
+}
+
+/// Build the content of a section at `file_offset`/`rva` holding debug directory entries of the
+/// given types, each followed by its data
+fn debug_section(file_offset: u32, rva: u32, entries: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let mut dirs = Vec::new();
+    let mut data = Vec::new();
+    let data_start = (entries.len() * DEBUG_DIRECTORY_SIZE) as u32;
+    for (ty, d) in entries {
+        let off = data_start + data.len() as u32;
+        dirs.extend_from_slice(&0u32.to_le_bytes());
+        dirs.extend_from_slice(&0u32.to_le_bytes());
+        dirs.extend_from_slice(&0u32.to_le_bytes());
+        dirs.extend_from_slice(&ty.to_le_bytes());
+        dirs.extend_from_slice(&(d.len() as u32).to_le_bytes());
+        dirs.extend_from_slice(&(rva + off).to_le_bytes());
+        dirs.extend_from_slice(&(file_offset + off).to_le_bytes());
+        data.extend_from_slice(d);
+    }
+    dirs.extend_from_slice(&data);
+    dirs
+}
+
+/// Build a minimal PE image. Each section is placed at file offset `0x200 * (i + 1)` and rva
+/// `0x1000 * (i + 1)`. If `debug` is set, the debug data directory covers the given number of
+/// entries at the start of that section.
+fn pe(pe32plus: bool, sections: &[(&[u8], Vec<u8>)], debug: Option<(usize, usize)>) -> Vec<u8> {
+    let mut out = vec![0u8; 0x40];
+    out[..2].copy_from_slice(b"MZ");
+    out[0x3c..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+
+    let opt_size: u16 = if pe32plus { 240 } else { 224 };
+    out.extend_from_slice(b"PE\0\0");
+    out.extend_from_slice(&0x8664u16.to_le_bytes());
+    out.extend_from_slice(&(sections.len() as u16).to_le_bytes());
+    out.extend_from_slice(&[0; 12]);
+    out.extend_from_slice(&opt_size.to_le_bytes());
+    out.extend_from_slice(&0x22u16.to_le_bytes());
+
+    let mut opt = vec![0u8; opt_size as usize];
+    let dirs = if pe32plus {
+        opt[..2].copy_from_slice(&0x20bu16.to_le_bytes());
+        108
+    } else {
+        opt[..2].copy_from_slice(&0x10bu16.to_le_bytes());
+        92
+    };
+    opt[dirs..dirs + 4].copy_from_slice(&16u32.to_le_bytes());
+    if let Some((i, n)) = debug {
+        let d = dirs + 4 + 6 * 8;
+        opt[d..d + 4].copy_from_slice(&(0x1000 * (i as u32 + 1)).to_le_bytes());
+        opt[d + 4..d + 8].copy_from_slice(&((n * DEBUG_DIRECTORY_SIZE) as u32).to_le_bytes());
+    }
+    out.extend_from_slice(&opt);
+
+    for (i, (name, data)) in sections.iter().enumerate() {
+        let mut n = [0u8; 8];
+        n[..name.len()].copy_from_slice(name);
+        out.extend_from_slice(&n);
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(0x1000 * (i as u32 + 1)).to_le_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(0x200 * (i as u32 + 1)).to_le_bytes());
+        out.extend_from_slice(&[0; 16]);
+    }
+    for (i, (_, data)) in sections.iter().enumerate() {
+        out.resize(0x200 * (i + 1), 0);
+        out.extend_from_slice(data);
+    }
+    out
+}
+
+#[test]
+fn all_debug_directories() {
+    for pe32plus in [false, true] {
+        let rdata = debug_section(
+            0x400,
+            0x2000,
+            &[
+                (IMAGE_DEBUG_TYPE_POGO, vec![0; 8]),
+                (
+                    IMAGE_DEBUG_TYPE_CODEVIEW,
+                    codeview(&GUID, 3, "C:\\b\\app.pdb"),
+                ),
+                (IMAGE_DEBUG_TYPE_REPRO, repro(&HASH)),
+            ],
+        );
+        let data = pe(
+            pe32plus,
+            &[(b".text", vec![0xc3]), (b".rdata", rdata)],
+            Some((1, 3)),
+        );
+
+        let f = PeFile::parse(&data).unwrap();
+        assert_eq!(f.is_pe32plus(), pe32plus);
+        assert_eq!(f.machine(), 0x8664);
+        assert_eq!(f.sections().count(), 2);
+        assert_eq!(f.debug_directories().unwrap().count(), 3);
+
+        let cv = f.codeview().unwrap();
+        assert_eq!(cv.guid, &GUID[..]);
+        assert_eq!(cv.guid.kind(), Kind::CodeView);
+        assert_eq!(
+            cv.guid.uuid().unwrap().to_string(),
+            "8a5e4c11-3b5d-376c-950f-2cc91a6f8e7e"
+        );
+        assert_eq!(cv.age, 3);
+        assert_eq!(cv.pdb_path, b"C:\\b\\app.pdb");
+        assert_eq!(f.repro_hash().unwrap(), &HASH[..]);
+
+        assert_eq!(pe_file::build_id(&data).unwrap(), &GUID[..]);
+        let read = pe_file::read_build_id(&mut std::io::Cursor::new(&data)).unwrap();
+        assert_eq!(read, &GUID[..]);
+    }
+}
+
+#[test]
+fn mingw_buildid_section() {
+    // GNU ld points the debug directory at the `.buildid` section
+    let buildid = debug_section(
+        0x400,
+        0x2000,
+        &[(IMAGE_DEBUG_TYPE_CODEVIEW, codeview(&GUID, 1, ""))],
+    );
+    let sections = [(&b".text"[..], vec![0xc3]), (&b".buildid"[..], buildid)];
+    let data = pe(true, &sections, Some((1, 1)));
+    let cv = PeFile::parse(&data).unwrap().codeview().unwrap();
+    assert_eq!(cv.guid, &GUID[..]);
+    assert_eq!(cv.pdb_path, b"");
+
+    // but the section is also found without the debug directory
+    let data = pe(true, &sections, None);
+    let f = PeFile::parse(&data).unwrap();
+    assert_eq!(f.debug_directories().err(), Some(Error::NoDebugDirectory));
+    assert_eq!(f.codeview().unwrap().guid, &GUID[..]);
+    let read = pe_file::read_build_id(&mut std::io::Cursor::new(&data)).unwrap();
+    assert_eq!(read, &GUID[..]);
+}
+
+#[test]
+fn truncated_debug_directory() {
+    // a debug directory whose size isn't a whole number of entries, followed by a `.buildid`
+    // section
+    let buildid = debug_section(
+        0x400,
+        0x2000,
+        &[(IMAGE_DEBUG_TYPE_CODEVIEW, codeview(&GUID, 1, ""))],
+    );
+    let sections = [(&b".text"[..], vec![0; 40]), (&b".buildid"[..], buildid)];
+    let mut data = pe(true, &sections, Some((0, 1)));
+    // the size of the debug data directory, in the PE32+ optional header
+    let size = 0x40 + 4 + 20 + 108 + 4 + 6 * 8 + 4;
+    data[size..size + 4].copy_from_slice(&40u32.to_le_bytes());
+
+    let f = PeFile::parse(&data).unwrap();
+    assert!(f.debug_directories().unwrap().last().unwrap().is_err());
+    assert_eq!(f.codeview().unwrap().guid, &GUID[..]);
+    assert_eq!(f.repro_hash(), Err(Error::NotFound));
+    let read = pe_file::read_build_id(&mut std::io::Cursor::new(&data)).unwrap();
+    assert_eq!(read, &GUID[..]);
+}
+
+#[test]
+fn missing_or_invalid() {
+    let data = pe(false, &[(b".text", vec![0xc3])], None);
+    let f = PeFile::parse(&data).unwrap();
+    assert_eq!(f.codeview(), Err(Error::NoDebugDirectory));
+    assert_eq!(f.repro_hash(), Err(Error::NoDebugDirectory));
+
+    let rdata = debug_section(0x200, 0x1000, &[(IMAGE_DEBUG_TYPE_POGO, vec![0; 8])]);
+    let data = pe(false, &[(b".rdata", rdata)], Some((0, 1)));
+    assert_eq!(
+        pe_file::build_id(&data),
+        Err(Error::UnexpectedDebugType(IMAGE_DEBUG_TYPE_POGO))
+    );
+
+    let mut cv = codeview(&GUID, 1, "a.pdb");
+    cv[..4].copy_from_slice(b"NB10");
+    let rdata = debug_section(0x200, 0x1000, &[(IMAGE_DEBUG_TYPE_CODEVIEW, cv)]);
+    let data = pe(false, &[(b".rdata", rdata)], Some((0, 1)));
+    assert_eq!(
+        pe_file::build_id(&data),
+        Err(Error::UnexpectedCodeViewSignature(u32::from_le_bytes(
+            *b"NB10"
+        )))
+    );
+
+    assert_eq!(pe_file::build_id(&data[..0x100]), Err(Error::Truncated));
+    assert_eq!(
+        pe_file::build_id(b"\x7fELF\x02\x01\x01\0"),
+        Err(Error::UnrecognizedFormat)
+    );
+}
+
+#[test]
+fn from_path() {
+    let dir = std::env::temp_dir().join(format!("buildid-pe-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let rdata = debug_section(
+        0x200,
+        0x1000,
+        &[(IMAGE_DEBUG_TYPE_CODEVIEW, codeview(&GUID, 1, "app.pdb"))],
+    );
+    let path = dir.join("app.exe");
+    std::fs::write(&path, pe(true, &[(b".rdata", rdata)], Some((0, 1)))).unwrap();
+    let id = buildid::from_path(&path).unwrap();
+    assert_eq!(id, &GUID[..]);
+    assert_eq!(id.kind(), Kind::CodeView);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
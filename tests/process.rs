@@ -0,0 +1,114 @@
+#![cfg(any(target_os = "linux", target_os = "android"))]
+
+use buildid::process;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::time::Duration;
+
+#[test]
+fn current_process() {
+    let addr = current_process as *const () as u64;
+    let modules = process::modules(std::process::id()).unwrap();
+    let ours: Vec<_> = modules
+        .iter()
+        .filter(|m| m.range().contains(&addr))
+        .collect();
+    assert_eq!(ours.len(), 1);
+    assert_eq!(ours[0].build_id(), buildid::BuildId::current());
+    assert_eq!(ours[0].path(), std::env::current_exe().unwrap());
+
+    let maps = process::maps(std::process::id()).unwrap();
+    assert!(maps.iter().any(|m| m.path.as_os_str() == "[stack]"));
+}
+
+#[test]
+fn header_without_program_headers() {
+    // an ELF header with no program headers, and no e_phentsize
+    let mut header = vec![0u8; 4096];
+    header[..7].copy_from_slice(b"\x7fELF\x02\x01\x01");
+    header[16] = 3;
+    header[18] = 62;
+    header[20] = 1;
+    header[52] = 64;
+    let path = std::env::temp_dir().join(format!("buildid-phnum-{}", std::process::id()));
+    std::fs::write(&path, &header).unwrap();
+    let file = std::fs::File::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    use std::os::fd::AsRawFd;
+    let len = header.len();
+    let map = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ,
+            libc::MAP_PRIVATE,
+            file.as_raw_fd(),
+            0,
+        )
+    };
+    assert_ne!(map, libc::MAP_FAILED);
+    let modules = process::modules(std::process::id());
+    unsafe { libc::munmap(map, len) };
+
+    let addr = map as u64;
+    let module = modules
+        .unwrap()
+        .into_iter()
+        .find(|m| m.range().contains(&addr))
+        .unwrap();
+    assert_eq!(module.try_build_id(), Err(buildid::Error::NotFound));
+}
+
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|d| d.join(name))
+        .find(|p| p.is_file())
+}
+
+fn spawn(exe: &PathBuf) -> Child {
+    // other tests may be forking while the copy is open for writing, which makes exec fail with
+    // ETXTBSY until their child execs too
+    for _ in 0..50 {
+        match Command::new(exe).arg("30").spawn() {
+            Err(e) if e.raw_os_error() == Some(libc::ETXTBSY) => {
+                std::thread::sleep(Duration::from_millis(20))
+            }
+            r => return r.unwrap(),
+        }
+    }
+    panic!("{} stayed busy", exe.display());
+}
+
+#[test]
+fn deleted_executable() {
+    let Some(sleep) = find_in_path("sleep") else {
+        return;
+    };
+    let dir = std::env::temp_dir().join(format!("buildid-process-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let exe = dir.join("sleep");
+    std::fs::copy(&sleep, &exe).unwrap();
+
+    let mut child = spawn(&exe);
+    std::fs::remove_file(&exe).unwrap();
+    std::fs::remove_dir(&dir).unwrap();
+
+    // wait for the exec to finish
+    let mut found = None;
+    for _ in 0..100 {
+        let modules = process::modules(child.id()).unwrap();
+        found = modules.into_iter().find(|m| m.path() == exe);
+        if found.is_some() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    let module = found.unwrap();
+    assert!(module.deleted());
+    assert_eq!(module.try_build_id(), buildid::from_path(&sleep));
+}
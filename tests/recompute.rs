@@ -0,0 +1,118 @@
+mod common;
+
+use buildid::recompute::{self, Style};
+use buildid::{BuildId, Error};
+use common::ElfBuilder;
+
+/// An ELF file whose build-id is all zeros, and the offset of the build-id
+fn elf(class64: bool, len: usize) -> (Vec<u8>, usize) {
+    let b = ElfBuilder::new(class64, false);
+    let note = b.w.build_id_note(&vec![0; len]);
+    let data = b
+        .note_segment(".note.gnu.build-id", 4, note)
+        .section(".comment", 1, b"GCC: (GNU) 12.2.0\0".to_vec())
+        .build();
+    let id = buildid::elf_file::build_id(&data).unwrap();
+    let off = id.as_ptr() as usize - data.as_ptr() as usize;
+    (data, off)
+}
+
+#[test]
+fn round_trip() {
+    for class64 in [true, false] {
+        for style in Style::ALL {
+            let (mut data, off) = elf(class64, style.id_len());
+            let id = recompute::compute(&data, style).unwrap();
+            assert_eq!(id.len(), style.id_len());
+            data[off..off + id.len()].copy_from_slice(id.as_bytes());
+
+            // the stored build-id doesn't affect the result
+            assert_eq!(recompute::compute(&data, style).unwrap(), id);
+            assert_eq!(recompute::verify(&data), Ok(style), "{:?}", style);
+
+            let comment = data.windows(3).position(|w| w == b"GCC").unwrap();
+            data[comment] ^= 1;
+            assert_eq!(recompute::verify(&data), Err(Error::HashMismatch));
+        }
+    }
+}
+
+#[test]
+fn linker_output() {
+    // tests/data/recompute.s, assembled with `as --64`, and linked with GNU ld 2.40
+    // (`ld.bfd -static -nostdlib -z noseparate-code --build-id=<hash>`) and gold 1.16
+    // (`ld.gold -static -nostdlib -z max-page-size=0x10 -z common-page-size=0x10
+    // --build-id=<hash>`)
+    for (style, data) in [
+        (Style::LdSha1, &include_bytes!("data/recompute-ld-sha1")[..]),
+        (Style::LdMd5, include_bytes!("data/recompute-ld-md5")),
+        (Style::GoldSha1, include_bytes!("data/recompute-gold-sha1")),
+        (Style::GoldMd5, include_bytes!("data/recompute-gold-md5")),
+    ] {
+        let id = buildid::elf_file::build_id(data).unwrap();
+        assert_eq!(recompute::compute(data, style).unwrap(), id, "{:?}", style);
+        assert_eq!(recompute::verify(data), Ok(style));
+    }
+}
+
+#[test]
+fn known_hashes() {
+    // regression snapshots of our own results, which `linker_output` checks against the linkers
+    for (style, hex) in [
+        (Style::LdSha1, "ec56c9118c23c0fd9298e2278faaa976cf152b66"),
+        (Style::GoldSha1, "46a46ef8796b760d88fc530c82367451f3dd50d5"),
+        (Style::LdMd5, "c0c5951cffc26b5e4d2decfa6e188c49"),
+        (Style::GoldMd5, "c8f17127c884fcb5d9df5494a0850f37"),
+    ] {
+        let (data, _) = elf(true, style.id_len());
+        assert_eq!(
+            recompute::compute(&data, style).unwrap(),
+            hex.parse::<BuildId>().unwrap(),
+            "{:?}",
+            style
+        );
+    }
+}
+
+#[test]
+fn update() {
+    let (mut data, off) = elf(false, 20);
+    data[off..off + 20].copy_from_slice(&[0x11; 20]);
+    let id = recompute::update(&mut data, Style::LldSha1).unwrap();
+    assert_eq!(id, buildid::elf_file::build_id(&data).unwrap());
+    assert_eq!(recompute::verify(&data), Ok(Style::LldSha1));
+
+    // edit the content, then make the build-id follow it
+    let comment = data.windows(3).position(|w| w == b"GCC").unwrap();
+    data[comment] = b'g';
+    assert_eq!(recompute::verify(&data), Err(Error::HashMismatch));
+    let new = recompute::update(&mut data, Style::LldSha1).unwrap();
+    assert_ne!(new, id);
+    assert_eq!(recompute::verify(&data), Ok(Style::LldSha1));
+
+    assert_eq!(
+        recompute::update(&mut data, Style::LldFast),
+        Err(Error::LengthMismatch { stored: 20, new: 8 })
+    );
+}
+
+#[test]
+fn missing() {
+    let data = ElfBuilder::new(true, false).build();
+    assert_eq!(recompute::verify(&data), Err(Error::NotFound));
+    assert_eq!(
+        recompute::compute(b"not an elf file", Style::LdSha1),
+        Err(Error::UnrecognizedFormat)
+    );
+}
+
+#[test]
+fn current_exe() {
+    // the scripts/test-* runners force a build-id, rather than letting the linker compute one
+    if std::env::var_os("BUILD_ID_TEST_EXPECTED").is_some() {
+        return;
+    }
+    // linked with whatever linker the toolchain uses, which should be one we know about
+    let style = recompute::verify_path(std::env::current_exe().unwrap()).unwrap();
+    assert_eq!(buildid::build_id().unwrap().len(), style.id_len());
+}
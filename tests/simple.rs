@@ -1,7 +1,7 @@
-fn expected_build_id() -> Option<Vec<u8>> {
+fn expected_build_id() -> Option<buildid::BuildId> {
     let expected_build_id = std::env::var("BUILD_ID_TEST_EXPECTED");
     match expected_build_id {
-        Ok(v) => Some(hex::decode(v).unwrap()),
+        Ok(v) => Some(v.parse().unwrap()),
         Err(std::env::VarError::NotPresent) => None,
         Err(e) => panic!("{}", e),
     }
@@ -12,15 +12,22 @@ fn has_build_id() {
     let id = buildid::build_id().unwrap();
     assert!(!id.is_empty());
 
-    println!("{}", hex::encode(id));
+    println!("{}", buildid::BuildId::current().unwrap());
     if let Some(expected_id) = expected_build_id() {
         assert_eq!(expected_id, id);
     }
 }
 
+#[test]
+fn try_build_id() {
+    let id = buildid::try_build_id().unwrap();
+    assert_eq!(id, buildid::build_id().unwrap());
+    assert_eq!(Some(id), buildid::BuildId::current());
+}
+
 #[cfg(all(target_family = "unix", target_vendor = "apple",))]
 mod mach {
-    fn otool_uuid(exe_path: &std::path::Path) -> Option<Vec<u8>> {
+    fn otool_uuid(exe_path: &std::path::Path) -> Option<buildid::BuildId> {
         use std::process::Command;
         let otool_l = Command::new("otool")
             .arg("-l")
@@ -38,7 +45,7 @@ mod mach {
                 let parts: Vec<&str> = uuid_line.trim().split_whitespace().collect();
                 assert!(parts.len() == 2 && parts[0] == "uuid");
                 let uuid_str = parts[1].replace("-", "");
-                return Some(hex::decode(uuid_str).unwrap());
+                return Some(uuid_str.parse().unwrap());
             }
         }
         None
@@ -49,7 +56,18 @@ mod mach {
         let exe_path = std::env::current_exe().unwrap();
         let uuid = otool_uuid(exe_path.as_ref()).unwrap();
 
-        let id = buildid::build_id().unwrap();
+        let id = buildid::BuildId::current().unwrap();
         assert_eq!(uuid, id);
+        assert_eq!(
+            id.uuid().unwrap().to_string().to_uppercase(),
+            format!("{:X}", uuid.uuid().unwrap())
+        );
+    }
+
+    #[test]
+    fn has_build_id_mach_file() {
+        let exe_path = std::env::current_exe().unwrap();
+        let uuid = buildid::from_path(exe_path).unwrap();
+        assert_eq!(Some(uuid), buildid::BuildId::current());
     }
 }
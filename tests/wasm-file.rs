@@ -0,0 +1,115 @@
+use buildid::wasm_file::{self, WasmFile, SECTION_CUSTOM};
+use buildid::{Error, Kind};
+
+const ID: [u8; 16] = [
+    0x8a, 0x5e, 0x4c, 0x11, 0x3b, 0x5d, 0x37, 0x6c, 0x95, 0x0f, 0x2c, 0xc9, 0x1a, 0x6f, 0x8e, 0x7e,
+];
+
+fn leb128(out: &mut Vec<u8>, mut v: usize) {
+    loop {
+        let b = (v & 0x7f) as u8;
+        v >>= 7;
+        if v == 0 {
+            out.push(b);
+            return;
+        }
+        out.push(b | 0x80);
+    }
+}
+
+fn section(out: &mut Vec<u8>, id: u8, content: &[u8]) {
+    out.push(id);
+    leb128(out, content.len());
+    out.extend_from_slice(content);
+}
+
+fn custom(out: &mut Vec<u8>, name: &str, payload: &[u8]) {
+    let mut content = Vec::new();
+    leb128(&mut content, name.len());
+    content.extend_from_slice(name.as_bytes());
+    content.extend_from_slice(payload);
+    section(out, SECTION_CUSTOM, &content);
+}
+
+/// Build a module with a type section, some custom sections, and (optionally) a `build_id`
+/// section
+fn module(id: Option<&[u8]>) -> Vec<u8> {
+    let mut out = b"\0asm\x01\0\0\0".to_vec();
+    // type section: one `() -> ()` function type
+    section(&mut out, 1, &[1, 0x60, 0, 0]);
+    custom(&mut out, "producers", &[0; 200]);
+    if let Some(id) = id {
+        let mut payload = Vec::new();
+        leb128(&mut payload, id.len());
+        payload.extend_from_slice(id);
+        custom(&mut out, "build_id", &payload);
+    }
+    custom(&mut out, "name", &[0; 4]);
+    out
+}
+
+#[test]
+fn build_id() {
+    let data = module(Some(&ID));
+    let f = WasmFile::parse(&data).unwrap();
+    assert_eq!(f.sections().count(), 4);
+    assert_eq!(f.custom_section(b"name").unwrap(), Some(&[0u8; 4][..]));
+
+    let id = f.build_id().unwrap();
+    assert_eq!(id, &ID[..]);
+    assert_eq!(id.kind(), Kind::Wasm);
+    assert_eq!(wasm_file::build_id(&data).unwrap(), id);
+
+    let read = wasm_file::read_build_id(&mut std::io::Cursor::new(&data)).unwrap();
+    assert_eq!(read, id);
+}
+
+#[test]
+fn missing_or_invalid() {
+    let data = module(None);
+    assert_eq!(wasm_file::build_id(&data), Err(Error::NotFound));
+    assert_eq!(
+        wasm_file::read_build_id(&mut std::io::Cursor::new(&data)),
+        Err(Error::NotFound)
+    );
+
+    // cut into the `build_id` section, before the final `name` section
+    let data = module(Some(&ID));
+    assert_eq!(
+        wasm_file::build_id(&data[..data.len() - 15]),
+        Err(Error::Truncated)
+    );
+    assert_eq!(
+        wasm_file::read_build_id(&mut std::io::Cursor::new(&data[..data.len() - 15])),
+        Err(Error::Truncated)
+    );
+
+    // a section without a build-id that extends past the end
+    let data = module(None);
+    let data = &data[..data.len() - 2];
+    assert_eq!(wasm_file::build_id(data), Err(Error::Truncated));
+    assert_eq!(
+        wasm_file::read_build_id(&mut std::io::Cursor::new(data)),
+        Err(Error::Truncated)
+    );
+    assert_eq!(
+        wasm_file::build_id(b"\0asm\x02\0\0\0"),
+        Err(Error::Malformed("unsupported wasm version"))
+    );
+    assert_eq!(
+        wasm_file::build_id(b"\x7fELF\x02\x01\x01\0"),
+        Err(Error::UnrecognizedFormat)
+    );
+}
+
+#[test]
+fn from_path() {
+    let dir = std::env::temp_dir().join(format!("buildid-wasm-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let path = dir.join("plugin.wasm");
+    std::fs::write(&path, module(Some(&ID))).unwrap();
+    assert_eq!(buildid::from_path(&path).unwrap(), &ID[..]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}